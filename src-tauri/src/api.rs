@@ -0,0 +1,267 @@
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::commands;
+use crate::db::{Database, Note};
+use crate::note_window::{create_note_window, refresh_note_window};
+
+/// Largest request body accepted, in bytes.
+const MAX_BODY_LEN: u64 = 1024 * 1024;
+
+/// The local HTTP server for scripts and the thread serving it, while it is
+/// running.
+#[derive(Default)]
+pub struct ApiServer(Mutex<Option<(Arc<Server>, JoinHandle<()>)>>);
+
+/// Note fields a request may set; anything left out keeps its value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct NoteFields {
+    title: Option<String>,
+    content: Option<String>,
+    mode: Option<String>,
+    pos_x: Option<i32>,
+    pos_y: Option<i32>,
+    width: Option<i32>,
+    height: Option<i32>,
+    opacity: Option<f64>,
+    always_on_top: Option<bool>,
+}
+
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+}
+
+/// Errors from commands are mostly rejected input, e.g. an invalid mode.
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        Self::new(400, message)
+    }
+}
+
+impl From<rusqlite::Error> for ApiError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::new(500, e.to_string())
+    }
+}
+
+type ApiResult = Result<(u16, serde_json::Value), ApiError>;
+
+fn json(status: u16, value: impl Serialize) -> ApiResult {
+    serde_json::to_value(value)
+        .map(|value| (status, value))
+        .map_err(|e| ApiError::new(500, e.to_string()))
+}
+
+/// Starts the server on `127.0.0.1` if the API is turned on in the settings
+/// and it isn't running yet.
+pub fn start_server(app: &AppHandle) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    if !settings.api_enabled {
+        return Ok(());
+    }
+
+    let state = app.state::<ApiServer>();
+    let mut running = state.0.lock().map_err(|e| e.to_string())?;
+    if running.is_some() {
+        return Ok(());
+    }
+
+    let server = Server::http(("127.0.0.1", settings.api_port))
+        .map(Arc::new)
+        .map_err(|e| format!("Failed to listen on port {}: {e}", settings.api_port))?;
+
+    let app = app.clone();
+    let serving = server.clone();
+    let thread = std::thread::spawn(move || {
+        for request in serving.incoming_requests() {
+            handle_request(&app, request);
+        }
+    });
+    *running = Some((server, thread));
+
+    Ok(())
+}
+
+/// Stops the server if it is running, and waits for the request in
+/// progress, if any, so the port is free again once this returns.
+pub fn stop_server(app: &AppHandle) {
+    let state = app.state::<ApiServer>();
+    let running = state.0.lock().ok().and_then(|mut running| running.take());
+    if let Some((server, thread)) = running {
+        server.unblock();
+        if thread.join().is_err() {
+            eprintln!("API server thread panicked");
+        }
+        // Last reference, so the listening socket is closed here
+        drop(server);
+    }
+}
+
+fn handle_request(app: &AppHandle, mut request: Request) {
+    let result = authorize(app, &request).and_then(|()| {
+        let mut body = String::new();
+        request
+            .as_reader()
+            .take(MAX_BODY_LEN)
+            .read_to_string(&mut body)
+            .map_err(|_| ApiError::new(400, "Request body must be UTF-8 text"))?;
+        route(app, request.method(), request.url(), &body)
+    });
+
+    let (status, value) = result.unwrap_or_else(|e| (e.status, serde_json::json!({ "error": e.message })));
+    let content_type = Header::from_bytes("Content-Type", "application/json").expect("valid header");
+    let response = Response::from_data(value.to_string())
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        eprintln!("Failed to answer API request: {}", e);
+    }
+}
+
+/// Compares two secrets in time that depends only on their lengths, so
+/// response timing doesn't reveal how much of a guess was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
+/// Requires `Authorization: Bearer <api_token>` on every request.
+fn authorize(app: &AppHandle, request: &Request) -> Result<(), ApiError> {
+    let token = app.state::<Database>().get_setting("api_token")?;
+    let authorized = request.headers().iter().any(|header| {
+        header.field.equiv("Authorization")
+            && header
+                .value
+                .as_str()
+                .strip_prefix("Bearer ")
+                .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+    });
+    if authorized {
+        Ok(())
+    } else {
+        Err(ApiError::new(401, "Missing or invalid API token"))
+    }
+}
+
+fn parse_fields(body: &str) -> Result<NoteFields, ApiError> {
+    if body.trim().is_empty() {
+        return Ok(NoteFields::default());
+    }
+    serde_json::from_str(body).map_err(|e| ApiError::new(400, format!("Invalid JSON body: {e}")))
+}
+
+fn route(app: &AppHandle, method: &Method, url: &str, body: &str) -> ApiResult {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+    match (method, segments.as_slice()) {
+        (Method::Get, ["notes"]) => json(200, commands::get_all_notes(app.clone())?),
+        (Method::Post, ["notes"]) => create_note(app, parse_fields(body)?),
+        (Method::Get, ["notes", id]) => json(200, find_note(app, id)?),
+        (Method::Patch, ["notes", id]) => update_note(app, id, parse_fields(body)?),
+        (Method::Delete, ["notes", id]) => {
+            find_note(app, id)?;
+            commands::delete_note(app.clone(), id.to_string())?;
+            json(200, serde_json::json!({ "deleted": id }))
+        }
+        (Method::Post, ["notes", id, "open"]) => {
+            find_note(app, id)?;
+            json(200, commands::open_note(app.clone(), id.to_string())?)
+        }
+        (Method::Post, ["notes", id, "close"]) => {
+            find_note(app, id)?;
+            commands::close_note(app.clone(), id.to_string())?;
+            json(200, find_note(app, id)?)
+        }
+        (_, ["notes"] | ["notes", _] | ["notes", _, "open" | "close"]) => {
+            Err(ApiError::new(405, "Method not allowed"))
+        }
+        _ => Err(ApiError::new(404, "Not found")),
+    }
+}
+
+/// A note outside the trash.
+fn find_note(app: &AppHandle, id: &str) -> Result<Note, ApiError> {
+    commands::get_note(app.clone(), id.to_string())?
+        .filter(|note| note.deleted_at.is_none())
+        .ok_or_else(|| ApiError::new(404, "Note not found"))
+}
+
+/// Creates a note and opens its window.
+fn create_note(app: &AppHandle, fields: NoteFields) -> ApiResult {
+    // Checked up front so a rejected request doesn't leave a blank note
+    if let Some(mode) = fields.mode.as_deref().filter(|mode| !matches!(*mode, "text" | "todo")) {
+        return Err(ApiError::new(400, format!("Invalid mode: {mode}. Must be \"text\" or \"todo\"")));
+    }
+
+    let db = app.state::<Database>();
+    let note = db.create_note(fields.pos_x.unwrap_or(100), fields.pos_y.unwrap_or(100))?;
+    commands::update_note(
+        app.clone(),
+        note.id.clone(),
+        fields.title,
+        fields.content,
+        fields.mode,
+        None,
+        None,
+        fields.width,
+        fields.height,
+        fields.opacity,
+        fields.always_on_top,
+    )?;
+
+    let note = find_note(app, &note.id)?;
+    create_note_window(app, &note)?;
+    json(201, note)
+}
+
+fn update_note(app: &AppHandle, id: &str, fields: NoteFields) -> ApiResult {
+    find_note(app, id)?;
+    commands::update_note(
+        app.clone(),
+        id.to_string(),
+        fields.title,
+        fields.content,
+        fields.mode,
+        fields.pos_x,
+        fields.pos_y,
+        fields.width,
+        fields.height,
+        fields.opacity,
+        fields.always_on_top,
+    )?;
+
+    let note = find_note(app, id)?;
+    refresh_note_window(app, &note);
+    json(200, note)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_time_eq_matches_only_identical_bytes() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
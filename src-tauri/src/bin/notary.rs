@@ -0,0 +1,291 @@
+//! Command-line access to the notes database. Works alongside the app: the
+//! app picks up changes made here and refreshes its note windows.
+
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use hthud_lib::{Database, Note};
+use uuid::Uuid;
+
+/// Bundle identifier from `tauri.conf.json`, which names the app data folder.
+const IDENTIFIER: &str = "com.hoverthought.hud";
+
+const USAGE: &str = "\
+Usage: notary <command> [args]
+
+Commands:
+  list                         List notes
+  show <id>                    Print a note
+  new [--title <title>] [text] Create a note from text or stdin
+  append <id> [text]           Add a line from text or stdin to a note
+  edit <id>                    Replace a note's content from stdin or $EDITOR
+  edit <id> --title <title>    Rename a note
+  delete <id>                  Move a note to the trash
+  search <query>               Full-text search
+  export <dir>                 Write notes to <dir> as Markdown
+  export --archive <file>      Write notes and settings to a JSON archive
+
+<id> may be any unique prefix of a note id.
+
+Environment:
+  NOTARY_DATA_DIR   Folder holding notary.db, instead of the app's
+  NOTARY_PASSWORD   Master password of an encrypted database";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("notary: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let Some((command, args)) = args.split_first() else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+    if matches!(command.as_str(), "help" | "-h" | "--help") {
+        println!("{USAGE}");
+        return Ok(());
+    }
+
+    let db = open_database()?;
+    match command.as_str() {
+        "list" => list(&db),
+        "show" => show(&db, args),
+        "new" => new(&db, args),
+        "append" => append(&db, args),
+        "edit" => edit(&db, args),
+        "delete" => delete(&db, args),
+        "search" => search(&db, args),
+        "export" => export(&db, args),
+        _ => Err(format!("unknown command: {command}\n\n{USAGE}")),
+    }
+}
+
+fn data_dir() -> Result<PathBuf, String> {
+    if let Some(dir) = std::env::var_os("NOTARY_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    dirs::data_dir()
+        .map(|dir| dir.join(IDENTIFIER))
+        .ok_or_else(|| "could not find the app data folder; set NOTARY_DATA_DIR".to_string())
+}
+
+fn open_database() -> Result<Database, String> {
+    let dir = data_dir()?;
+    if !Database::is_encrypted(&dir) {
+        return Database::new(dir).map_err(|e| e.to_string());
+    }
+
+    let password = match std::env::var("NOTARY_PASSWORD") {
+        Ok(password) => password,
+        Err(_) if std::io::stdin().is_terminal() => {
+            rpassword::prompt_password("Master password: ").map_err(|e| e.to_string())?
+        }
+        Err(_) => return Err("the database is encrypted; set NOTARY_PASSWORD".to_string()),
+    };
+    Database::open(dir, Some(&password)).map_err(|e| e.to_string())
+}
+
+/// Finds a note by full id, or by an id prefix matching exactly one note
+/// outside the trash.
+fn find_note(db: &Database, id: Option<&String>) -> Result<Note, String> {
+    let id = id.ok_or("missing note id")?;
+    let exact = db.get_note(id).map_err(|e| e.to_string())?;
+    if let Some(note) = exact.filter(|note| note.deleted_at.is_none()) {
+        return Ok(note);
+    }
+
+    let mut matches: Vec<Note> = db
+        .get_all_notes()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|note| note.id.starts_with(id.as_str()))
+        .collect();
+    match matches.len() {
+        0 => Err(format!("no note matches {id}")),
+        1 => Ok(matches.remove(0)),
+        n => Err(format!("{n} notes match {id}; use a longer id")),
+    }
+}
+
+/// Splits `--title <title>` off the arguments.
+fn take_title(args: &[String]) -> Result<(Option<String>, Vec<String>), String> {
+    let mut title = None;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--title" {
+            title = Some(args.next().ok_or("--title needs a value")?.clone());
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    Ok((title, rest))
+}
+
+/// Text from the arguments, or else from piped stdin.
+fn read_text(args: &[String]) -> Result<Option<String>, String> {
+    if !args.is_empty() {
+        return Ok(Some(args.join(" ")));
+    }
+    if std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text).map_err(|e| e.to_string())?;
+    Ok(Some(text))
+}
+
+fn display_title(note: &Note) -> &str {
+    if note.title.trim().is_empty() {
+        "(untitled)"
+    } else {
+        &note.title
+    }
+}
+
+fn list(db: &Database) -> Result<(), String> {
+    for note in db.get_all_notes().map_err(|e| e.to_string())? {
+        let lock = if note.is_locked { "  [locked]" } else { "" };
+        println!("{}  {}{lock}", note.id, display_title(&note));
+    }
+    Ok(())
+}
+
+fn show(db: &Database, args: &[String]) -> Result<(), String> {
+    let note = find_note(db, args.first())?;
+    if note.is_locked {
+        return Err("note is locked; unlock it in the app".to_string());
+    }
+    println!("{}\n", display_title(&note));
+    println!("{}", note.content);
+    Ok(())
+}
+
+fn new(db: &Database, args: &[String]) -> Result<(), String> {
+    let (title, rest) = take_title(args)?;
+    let content = read_text(&rest)?;
+
+    let note = db.create_note(100, 100).map_err(|e| e.to_string())?;
+    db.update_note(&note.id, title.as_deref(), content.as_deref(), None, None, None, None, None, None, None)
+        .map_err(|e| e.to_string())?;
+    println!("{}", note.id);
+    Ok(())
+}
+
+fn append(db: &Database, args: &[String]) -> Result<(), String> {
+    let note = find_note(db, args.first())?;
+    let text = read_text(&args[1..])?.ok_or("nothing to append")?;
+
+    let existing = note.content.trim_end_matches('\n');
+    let content = if existing.is_empty() {
+        text
+    } else {
+        format!("{existing}\n{text}")
+    };
+    db.update_note(&note.id, None, Some(&content), None, None, None, None, None, None, None)
+        .map_err(|e| e.to_string())
+}
+
+fn edit(db: &Database, args: &[String]) -> Result<(), String> {
+    let note = find_note(db, args.first())?;
+    let (title, rest) = take_title(&args[1..])?;
+    if let Some(extra) = rest.first() {
+        return Err(format!("unexpected argument: {extra}"));
+    }
+
+    // With --title only the title changes
+    let content = match title {
+        Some(_) => None,
+        None if !std::io::stdin().is_terminal() => read_text(&[])?,
+        None => edit_in_editor(&note)?,
+    };
+    db.update_note(&note.id, title.as_deref(), content.as_deref(), None, None, None, None, None, None, None)
+        .map_err(|e| e.to_string())
+}
+
+/// Opens the note's content in `$VISUAL` / `$EDITOR` and returns the edited
+/// text, or `None` when it wasn't changed.
+fn edit_in_editor(note: &Note) -> Result<Option<String>, String> {
+    if note.is_locked {
+        return Err("note is locked; unlock it in the app".to_string());
+    }
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or("$EDITOR is empty")?;
+
+    let path = std::env::temp_dir().join(format!("notary-{}.md", Uuid::new_v4()));
+    write_private_file(&path, &note.content).map_err(|e| e.to_string())?;
+    let status = std::process::Command::new(program).args(parts).arg(&path).status();
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    let status = status.map_err(|e| format!("failed to run {program}: {e}"))?;
+    if !status.success() {
+        return Err(format!("{program} exited with {status}; note left unchanged"));
+    }
+    let edited = edited.map_err(|e| e.to_string())?;
+    Ok((edited != note.content).then_some(edited))
+}
+
+/// Creates `path` readable by the current user only, failing rather than
+/// following anything already there, e.g. a planted symlink.
+fn write_private_file(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+fn delete(db: &Database, args: &[String]) -> Result<(), String> {
+    let note = find_note(db, args.first())?;
+    db.delete_note(&note.id).map_err(|e| e.to_string())
+}
+
+fn search(db: &Database, args: &[String]) -> Result<(), String> {
+    if args.is_empty() {
+        return Err("missing search query".to_string());
+    }
+    let strip = |text: &str| text.replace("<mark>", "").replace("</mark>", "");
+
+    for result in db.search_notes(&args.join(" "), 50).map_err(|e| e.to_string())? {
+        let title = if result.title.trim().is_empty() { "(untitled)" } else { &result.title };
+        println!("{}  {}", result.id, title);
+        let snippet = strip(&result.snippet);
+        if !snippet.trim().is_empty() {
+            println!("    {}", snippet.replace('\n', " "));
+        }
+    }
+    Ok(())
+}
+
+fn export(db: &Database, args: &[String]) -> Result<(), String> {
+    let plural = |count: usize| if count == 1 { "" } else { "s" };
+    match args {
+        [flag, file] if flag == "--archive" => {
+            let count = db.export_archive(Path::new(file)).map_err(|e| e.to_string())?;
+            eprintln!("Exported {count} note{}", plural(count));
+        }
+        [dir] => {
+            let report = db.export_markdown(Path::new(dir)).map_err(|e| e.to_string())?;
+            let count = report.exported.len();
+            eprintln!("Exported {count} note{}", plural(count));
+            if !report.skipped.is_empty() {
+                let skipped = report.skipped.len();
+                eprintln!("Skipped {skipped} locked note{}", plural(skipped));
+            }
+        }
+        _ => return Err("usage: notary export <dir> | notary export --archive <file>".to_string()),
+    }
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use tauri::{AppHandle, Emitter, Manager, Window};
-use crate::db::{Database, Note, Settings};
-use crate::note_window::create_note_window;
+use crate::api::{start_server, stop_server};
+use crate::db::{ArchiveImport, ArchiveStrategy, Attachment, Backup, Database, EncryptionStatus, MarkdownExport, MarkdownImport, Note, Notebook, NoteRevision, OutgoingLink, Recurrence, RecurringTodo, Reminder, SearchResult, Settings, SyncReport, Tag, TodoCompletion, TodoRecurrence, Workspace, MAX_ATTACHMENT_BYTES};
+use crate::note_window::{arrange_rects, close_all_note_windows, create_note_window, gather_note_windows, move_note, notify_note_updated, primary_work_area, refresh_note_window, reload_note_windows, show_all_note_windows, work_areas, ArrangeStrategy, Rect};
 
 #[tauri::command]
 pub fn create_note(app: AppHandle, pos_x: Option<i32>, pos_y: Option<i32>) -> Result<Note, String> {
@@ -97,6 +98,31 @@ pub fn delete_note(app: AppHandle, id: String) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+pub fn list_trash(app: AppHandle) -> Result<Vec<Note>, String> {
+    let db = app.state::<Database>();
+    db.purge_expired_trash().map_err(|e| e.to_string())?;
+    db.list_trash().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn restore_note(app: AppHandle, id: String) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    if !db.restore_note(&id).map_err(|e| e.to_string())? {
+        return Err("Note is not in the trash".to_string());
+    }
+
+    db.get_note(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Note not found".to_string())
+}
+
+#[tauri::command]
+pub fn empty_trash(app: AppHandle) -> Result<usize, String> {
+    let db = app.state::<Database>();
+    db.empty_trash().map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn open_note(app: AppHandle, id: String) -> Result<Note, String> {
     let db = app.state::<Database>();
@@ -109,6 +135,10 @@ pub fn open_note(app: AppHandle, id: String) -> Result<Note, String> {
         .map_err(|e| e.to_string())?
         .ok_or_else(|| "Note not found".to_string())?;
 
+    if note.deleted_at.is_some() {
+        return Err("Note is in the trash".to_string());
+    }
+
     // Create the window
     create_note_window(&app, &note)?;
 
@@ -154,6 +184,64 @@ pub fn set_default_opacity(app: AppHandle, opacity: f64) -> Result<(), String> {
     db.set_setting("default_opacity", &opacity.to_string()).map_err(|e| e.to_string())
 }
 
+/// Longest trash retention that can be set, about a hundred years.
+const MAX_TRASH_RETENTION_DAYS: i64 = 36_500;
+
+#[tauri::command]
+pub fn set_trash_retention_days(app: AppHandle, days: i64) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.set_setting("trash_retention_days", &days.clamp(0, MAX_TRASH_RETENTION_DAYS).to_string()).map_err(|e| e.to_string())?;
+    db.purge_expired_trash().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_backup_retention(app: AppHandle, daily: i64, weekly: i64) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.set_setting("backup_keep_daily", &daily.max(1).to_string()).map_err(|e| e.to_string())?;
+    db.set_setting("backup_keep_weekly", &weekly.max(0).to_string()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_sync_dir(app: AppHandle, dir: Option<String>) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.set_setting("sync_dir", dir.as_deref().unwrap_or("")).map_err(|e| e.to_string())
+}
+
+/// Saves the API settings and starts or stops the server to match.
+#[tauri::command]
+pub fn set_api_settings(app: AppHandle, enabled: bool, port: u16) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.set_setting("api_enabled", if enabled { "true" } else { "false" }).map_err(|e| e.to_string())?;
+    db.set_setting("api_port", &port.to_string()).map_err(|e| e.to_string())?;
+
+    stop_server(&app);
+    start_server(&app)
+}
+
+#[tauri::command]
+pub fn get_api_token(app: AppHandle) -> Result<String, String> {
+    let db = app.state::<Database>();
+    db.get_setting("api_token").map_err(|e| e.to_string())
+}
+
+/// Replaces the API token; scripts using the old one are rejected from now on.
+#[tauri::command]
+pub fn regenerate_api_token(app: AppHandle) -> Result<String, String> {
+    let db = app.state::<Database>();
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    db.set_setting("api_token", &token).map_err(|e| e.to_string())?;
+    Ok(token)
+}
+
+#[tauri::command]
+pub fn set_extract_hashtags(app: AppHandle, enabled: bool) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.set_setting("extract_hashtags", if enabled { "true" } else { "false" })
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn minimize_all_notes(app: AppHandle) -> Result<(), String> {
     for (label, window) in app.webview_windows() {
@@ -175,6 +263,41 @@ pub fn show_all_notes(app: AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+#[tauri::command]
+pub fn gather_all_notes(app: AppHandle) -> Result<(), String> {
+    let db = app.state::<Database>();
+    gather_note_windows(&app, &db)
+}
+
+/// Lays out every open note window on a monitor, given as an index into
+/// the connected monitors; the primary one by default.
+#[tauri::command]
+pub fn arrange_notes(app: AppHandle, strategy: ArrangeStrategy, monitor: Option<usize>) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let area = match monitor {
+        Some(index) => work_areas(&app)
+            .get(index)
+            .copied()
+            .ok_or_else(|| format!("No monitor {index}"))?,
+        None => primary_work_area(&app).ok_or_else(|| "No monitor found".to_string())?,
+    };
+
+    let notes: Vec<Note> = db
+        .get_open_notes()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|note| app.get_webview_window(&format!("note-{}", note.id)).is_some())
+        .collect();
+    let rects: Vec<Rect> = notes.iter().map(Rect::of_note).collect();
+
+    for (note, rect) in notes.iter().zip(arrange_rects(strategy, area, &rects)) {
+        let note = move_note(&db, note, rect)?;
+        refresh_note_window(&app, &note);
+    }
+    show_all_note_windows(&app, None);
+    Ok(())
+}
+
 #[tauri::command]
 pub fn set_all_opacity(app: AppHandle, opacity: f64) -> Result<(), String> {
     let opacity = opacity.clamp(0.3, 1.0);
@@ -190,3 +313,522 @@ pub fn set_all_opacity(app: AppHandle, opacity: f64) -> Result<(), String> {
     }
     Ok(())
 }
+
+#[tauri::command]
+pub fn list_note_revisions(app: AppHandle, id: String) -> Result<Vec<NoteRevision>, String> {
+    let db = app.state::<Database>();
+    db.list_note_revisions(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_note_revision(app: AppHandle, revision_id: i64) -> Result<Option<NoteRevision>, String> {
+    let db = app.state::<Database>();
+    db.get_note_revision(revision_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn restore_note_revision(app: AppHandle, revision_id: i64) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    let note = db.restore_note_revision(revision_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Revision not found".to_string())?;
+
+    notify_note_updated(&app, &note);
+
+    Ok(note)
+}
+
+#[tauri::command]
+pub fn search_notes(app: AppHandle, query: String, limit: Option<u32>) -> Result<Vec<SearchResult>, String> {
+    let limit = limit.unwrap_or(50).clamp(1, 500);
+    let db = app.state::<Database>();
+    db.search_notes(&query, limit).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_tags(app: AppHandle) -> Result<Vec<Tag>, String> {
+    let db = app.state::<Database>();
+    db.list_tags().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_note_tags(app: AppHandle, id: String) -> Result<Vec<String>, String> {
+    let db = app.state::<Database>();
+    db.get_note_tags(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_note_tag(app: AppHandle, id: String, tag: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.add_note_tag(&id, &tag).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remove_note_tag(app: AppHandle, id: String, tag: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.remove_note_tag(&id, &tag).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn rename_tag(app: AppHandle, old_name: String, new_name: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let rewritten = db.rename_tag(&old_name, &new_name).map_err(|e| e.to_string())?;
+
+    // Refresh any open notes whose hashtags were rewritten
+    for note_id in rewritten {
+        if let Ok(Some(note)) = db.get_note(&note_id) {
+            notify_note_updated(&app, &note);
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn merge_tags(app: AppHandle, source: String, target: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let rewritten = db.merge_tags(&source, &target).map_err(|e| e.to_string())?;
+
+    // Refresh any open notes whose hashtags were rewritten
+    for note_id in rewritten {
+        if let Ok(Some(note)) = db.get_note(&note_id) {
+            notify_note_updated(&app, &note);
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_tag(app: AppHandle, tag: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.delete_tag(&tag).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_notes_by_tags(app: AppHandle, tags: Vec<String>, match_all: Option<bool>) -> Result<Vec<Note>, String> {
+    let db = app.state::<Database>();
+    db.get_notes_by_tags(&tags, match_all.unwrap_or(true)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_notebooks(app: AppHandle) -> Result<Vec<Notebook>, String> {
+    let db = app.state::<Database>();
+    db.list_notebooks().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_notebook(app: AppHandle, name: String, parent_id: Option<String>) -> Result<Notebook, String> {
+    let db = app.state::<Database>();
+    db.create_notebook(&name, parent_id.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn rename_notebook(app: AppHandle, id: String, name: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.rename_notebook(&id, &name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn move_notebook(app: AppHandle, id: String, parent_id: Option<String>) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.move_notebook(&id, parent_id.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_notebook(app: AppHandle, id: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.delete_notebook(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn move_note_to_notebook(app: AppHandle, id: String, notebook_id: Option<String>) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.move_note_to_notebook(&id, notebook_id.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_notebook_notes(app: AppHandle, notebook_id: String, include_nested: Option<bool>) -> Result<Vec<Note>, String> {
+    let db = app.state::<Database>();
+    db.get_notebook_notes(&notebook_id, include_nested.unwrap_or(true)).map_err(|e| e.to_string())
+}
+
+/// Shows every note in a notebook and the notebooks nested in it, opening
+/// the ones that were closed.
+#[tauri::command]
+pub fn show_notebook(app: AppHandle, notebook_id: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let notes = db.get_notebook_notes(&notebook_id, true).map_err(|e| e.to_string())?;
+    for note in notes.into_iter().filter(|note| !note.is_open) {
+        db.open_note(&note.id).map_err(|e| e.to_string())?;
+        create_note_window(&app, &Note { is_open: true, ..note })?;
+    }
+
+    show_all_note_windows(&app, Some(&notebook_id));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn hide_notebook(app: AppHandle, notebook_id: String) -> Result<(), String> {
+    close_all_note_windows(&app, Some(&notebook_id));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_backlinks(app: AppHandle, id: String) -> Result<Vec<Note>, String> {
+    let db = app.state::<Database>();
+    db.get_backlinks(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_outgoing_links(app: AppHandle, id: String) -> Result<Vec<OutgoingLink>, String> {
+    let db = app.state::<Database>();
+    db.get_outgoing_links(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn rename_note(app: AppHandle, id: String, title: String, rewrite_links: Option<bool>) -> Result<(), String> {
+    let db = app.state::<Database>();
+    let rewritten = db.rename_note(&id, &title, rewrite_links.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+
+    // Refresh any open notes whose links were rewritten
+    for note_id in rewritten {
+        if let Ok(Some(note)) = db.get_note(&note_id) {
+            notify_note_updated(&app, &note);
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn add_attachment(
+    app: AppHandle,
+    id: String,
+    file_name: String,
+    mime_type: Option<String>,
+    data: Vec<u8>,
+) -> Result<Attachment, String> {
+    let db = app.state::<Database>();
+    db.add_attachment(&id, &file_name, mime_type.as_deref(), &data)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn add_attachment_from_path(app: AppHandle, id: String, path: String) -> Result<Attachment, String> {
+    use std::io::Read;
+
+    let read_error = |e: std::io::Error| format!("Failed to read {path}: {e}");
+    let file = std::fs::File::open(&path).map_err(read_error)?;
+
+    // Refuse oversized files before loading them into memory
+    let size = file.metadata().map_err(read_error)?.len();
+    if size > MAX_ATTACHMENT_BYTES as u64 {
+        return Err(format!("Attachment is larger than {} MB", MAX_ATTACHMENT_BYTES / (1024 * 1024)));
+    }
+    // A file that grew since is cut off just past the limit, which add_attachment rejects
+    let mut data = Vec::with_capacity(size as usize);
+    file.take(MAX_ATTACHMENT_BYTES as u64 + 1).read_to_end(&mut data).map_err(read_error)?;
+
+    let db = app.state::<Database>();
+    db.add_attachment(&id, &path, None, &data).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_attachments(app: AppHandle, id: String) -> Result<Vec<Attachment>, String> {
+    let db = app.state::<Database>();
+    db.list_attachments(&id).map_err(|e| e.to_string())
+}
+
+/// Returns the raw bytes, which arrive in JS as an `ArrayBuffer`.
+#[tauri::command]
+pub fn read_attachment(app: AppHandle, attachment_id: String) -> Result<tauri::ipc::Response, String> {
+    let db = app.state::<Database>();
+    let (_, data) = db.read_attachment(&attachment_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Attachment not found".to_string())?;
+    Ok(tauri::ipc::Response::new(data))
+}
+
+#[tauri::command]
+pub fn remove_attachment(app: AppHandle, attachment_id: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.remove_attachment(&attachment_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn lock_note(app: AppHandle, id: String, passphrase: String) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    let note = db.lock_note(&id, &passphrase).map_err(|e| e.to_string())?;
+    notify_note_updated(&app, &note);
+    Ok(note)
+}
+
+/// Returns a locked note's content for reading; the note stays locked.
+#[tauri::command]
+pub fn view_locked_note(app: AppHandle, id: String, passphrase: String) -> Result<String, String> {
+    let db = app.state::<Database>();
+    db.view_locked_note(&id, &passphrase).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn unlock_note(app: AppHandle, id: String, passphrase: String) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    let note = db.unlock_note(&id, &passphrase).map_err(|e| e.to_string())?;
+    notify_note_updated(&app, &note);
+    Ok(note)
+}
+
+/// Opens the encrypted database with the master password entered in the
+/// unlock window, then starts the app as usual.
+#[tauri::command]
+pub fn unlock_database(app: AppHandle, password: String) -> Result<(), String> {
+    if app.try_state::<Database>().is_some() {
+        return Ok(());
+    }
+
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    let db = Database::open(app_data_dir, Some(&password)).map_err(|e| e.to_string())?;
+    crate::start(&app, db);
+
+    if let Some(window) = app.get_webview_window("unlock") {
+        let _ = window.close();
+    }
+    // Launch arguments that arrived while locked, e.g. `--new "text"`
+    crate::instance::run_pending_args(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_encryption_status(app: AppHandle) -> Result<EncryptionStatus, String> {
+    let db = app.state::<Database>();
+    db.encryption_status().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn enable_encryption(app: AppHandle, password: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.enable_encryption(&password).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn change_encryption_password(app: AppHandle, current_password: String, new_password: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.change_encryption_password(&current_password, &new_password).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn disable_encryption(app: AppHandle, password: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.disable_encryption(&password).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_backups(app: AppHandle) -> Result<Vec<Backup>, String> {
+    let db = app.state::<Database>();
+    db.list_backups().map_err(|e| e.to_string())
+}
+
+/// "Back up now": a snapshot of this moment that leaves the daily and
+/// weekly ones alone.
+#[tauri::command]
+pub fn create_backup(app: AppHandle) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.create_manual_backup().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn restore_backup(app: AppHandle, name: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.restore_backup(&name).map_err(|e| e.to_string())?;
+    reload_note_windows(&app, &db)
+}
+
+/// Writes every note to `dir` as a Markdown file. Returns how many were written.
+#[tauri::command]
+pub fn export_markdown(app: AppHandle, dir: String) -> Result<MarkdownExport, String> {
+    let db = app.state::<Database>();
+    db.export_markdown(std::path::Path::new(&dir)).map_err(|e| e.to_string())
+}
+
+/// Creates or updates notes from the Markdown and text files in `dir`.
+#[tauri::command]
+pub fn import_markdown(app: AppHandle, dir: String) -> Result<MarkdownImport, String> {
+    let db = app.state::<Database>();
+    let report = db.import_markdown(std::path::Path::new(&dir)).map_err(|e| e.to_string())?;
+
+    // Imported notes start closed, but updates may hit open ones
+    if !report.updated.is_empty() {
+        for note in db.get_open_notes().map_err(|e| e.to_string())? {
+            notify_note_updated(&app, &note);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Writes all notes and settings to a single JSON file. Returns how many
+/// notes were written.
+#[tauri::command]
+pub fn export_archive(app: AppHandle, path: String) -> Result<usize, String> {
+    let db = app.state::<Database>();
+    db.export_archive(std::path::Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn import_archive(app: AppHandle, path: String, strategy: ArchiveStrategy) -> Result<ArchiveImport, String> {
+    let db = app.state::<Database>();
+    let report = db.import_archive(std::path::Path::new(&path), strategy).map_err(|e| e.to_string())?;
+    reload_note_windows(&app, &db)?;
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn sync_now(app: AppHandle) -> Result<SyncReport, String> {
+    let db = app.state::<Database>();
+    let report = db.sync_now().map_err(|e| e.to_string())?;
+    if report.changed_notes() {
+        reload_note_windows(&app, &db)?;
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn toggle_todo(app: AppHandle, id: String, index: usize) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    let note = db.toggle_todo(&id, index).map_err(|e| e.to_string())?;
+    notify_note_updated(&app, &note);
+    Ok(note)
+}
+
+#[tauri::command]
+pub fn add_todo(app: AppHandle, id: String, text: String, index: Option<usize>) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    let note = db.add_todo(&id, &text, index).map_err(|e| e.to_string())?;
+    notify_note_updated(&app, &note);
+    Ok(note)
+}
+
+#[tauri::command]
+pub fn remove_todo(app: AppHandle, id: String, index: usize) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    let note = db.remove_todo(&id, index).map_err(|e| e.to_string())?;
+    notify_note_updated(&app, &note);
+    Ok(note)
+}
+
+#[tauri::command]
+pub fn move_todo(app: AppHandle, id: String, from: usize, to: usize) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    let note = db.move_todo(&id, from, to).map_err(|e| e.to_string())?;
+    notify_note_updated(&app, &note);
+    Ok(note)
+}
+
+#[tauri::command]
+pub fn clear_completed(app: AppHandle, id: String) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    let note = db.clear_completed(&id).map_err(|e| e.to_string())?;
+    notify_note_updated(&app, &note);
+    Ok(note)
+}
+
+#[tauri::command]
+pub fn save_workspace(app: AppHandle, name: String) -> Result<Workspace, String> {
+    let db = app.state::<Database>();
+    db.save_workspace(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn apply_workspace(app: AppHandle, name: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.apply_workspace(&name).map_err(|e| e.to_string())?;
+    reload_note_windows(&app, &db)?;
+    show_all_note_windows(&app, None);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_workspaces(app: AppHandle) -> Result<Vec<Workspace>, String> {
+    let db = app.state::<Database>();
+    db.list_workspaces().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_workspace(app: AppHandle, name: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.delete_workspace(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_note_template(app: AppHandle, id: String, is_template: bool) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.set_note_template(&id, is_template).map_err(|e| e.to_string())?;
+    if let Some(note) = db.get_note(&id).map_err(|e| e.to_string())? {
+        notify_note_updated(&app, &note);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_templates(app: AppHandle) -> Result<Vec<Note>, String> {
+    let db = app.state::<Database>();
+    db.list_templates().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_note_from_template(app: AppHandle, template_id: String, pos_x: Option<i32>, pos_y: Option<i32>) -> Result<Note, String> {
+    let db = app.state::<Database>();
+    let note = db
+        .create_note_from_template(&template_id, pos_x.unwrap_or(100), pos_y.unwrap_or(100))
+        .map_err(|e| e.to_string())?;
+    create_note_window(&app, &note)?;
+    Ok(note)
+}
+
+#[tauri::command]
+pub fn set_todo_recurrence(app: AppHandle, id: String, index: usize, recurrence: Option<TodoRecurrence>) -> Result<Option<RecurringTodo>, String> {
+    let db = app.state::<Database>();
+    db.set_todo_recurrence(&id, index, recurrence).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_recurring_todos(app: AppHandle, id: String) -> Result<Vec<RecurringTodo>, String> {
+    let db = app.state::<Database>();
+    db.list_recurring_todos(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_todo_history(app: AppHandle, recurring_todo_id: String) -> Result<Vec<TodoCompletion>, String> {
+    let db = app.state::<Database>();
+    db.todo_history(&recurring_todo_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_reminder(app: AppHandle, note_id: String, fire_at: String, recurrence: Option<Recurrence>) -> Result<Reminder, String> {
+    let db = app.state::<Database>();
+    db.set_reminder(&note_id, &fire_at, recurrence).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_reminders(app: AppHandle, note_id: Option<String>) -> Result<Vec<Reminder>, String> {
+    let db = app.state::<Database>();
+    db.list_reminders(note_id.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn snooze_reminder(app: AppHandle, id: String, minutes: i64) -> Result<Reminder, String> {
+    let db = app.state::<Database>();
+    db.snooze_reminder(&id, minutes).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn dismiss_reminder(app: AppHandle, id: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.dismiss_reminder(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn acknowledge_reminder(app: AppHandle, id: String) -> Result<(), String> {
+    let db = app.state::<Database>();
+    db.acknowledge_reminder(&id).map_err(|e| e.to_string())
+}
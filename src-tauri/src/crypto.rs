@@ -0,0 +1,84 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+const SALT_LEN: usize = 16;
+
+/// Argon2id cost parameters, stored next to the ciphertext so they can be
+/// raised later without breaking existing locked notes.
+#[derive(Debug, Clone, Copy)]
+pub struct KdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// A passphrase-encrypted payload: Argon2id-derived key, ChaCha20-Poly1305.
+#[derive(Debug, Clone)]
+pub struct Sealed {
+    pub kdf: KdfParams,
+    pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], kdf: KdfParams) -> Result<Key, String> {
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|e| format!("invalid key derivation parameters: {e}"))?;
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("key derivation failed: {e}"))?;
+    Ok(key)
+}
+
+/// Encrypts `plaintext` under `passphrase`. `aad` is authenticated but not
+/// encrypted; decryption fails unless the same `aad` is supplied.
+pub fn seal(passphrase: &str, plaintext: &[u8], aad: &[u8]) -> Result<Sealed, String> {
+    if passphrase.is_empty() {
+        return Err("passphrase cannot be empty".to_string());
+    }
+
+    let kdf = KdfParams::default();
+    let mut salt = vec![0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt, kdf)?;
+
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|_| "encryption failed".to_string())?;
+
+    Ok(Sealed {
+        kdf,
+        salt,
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+}
+
+/// Decrypts a payload produced by `seal`. A wrong passphrase and tampered
+/// data are indistinguishable and both report an incorrect passphrase.
+pub fn open(passphrase: &str, sealed: &Sealed, aad: &[u8]) -> Result<Vec<u8>, String> {
+    if sealed.nonce.len() != 12 {
+        return Err("corrupt encrypted data".to_string());
+    }
+    let key = derive_key(passphrase, &sealed.salt, sealed.kdf)?;
+    ChaCha20Poly1305::new(&key)
+        .decrypt(
+            Nonce::from_slice(&sealed.nonce),
+            Payload { msg: &sealed.ciphertext, aad },
+        )
+        .map_err(|_| "incorrect passphrase".to_string())
+}
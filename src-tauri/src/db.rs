@@ -1,10 +1,47 @@
 use rusqlite::{Connection, Result as SqlResult};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::time::Duration;
 use uuid::Uuid;
 use chrono::Utc;
 
+mod archive;
+mod attachments;
+mod backups;
+mod encryption;
+mod links;
+mod locks;
+mod markdown;
+mod migrations;
+mod notebooks;
+mod recurring_todos;
+mod reminders;
+mod revisions;
+mod search;
+mod sync;
+mod tags;
+mod templates;
+mod todos;
+mod trash;
+mod workspaces;
+
+pub use archive::{ArchiveImport, ArchiveStrategy};
+pub use attachments::{Attachment, MAX_ATTACHMENT_BYTES};
+pub use backups::Backup;
+pub use encryption::EncryptionStatus;
+pub use links::OutgoingLink;
+pub use markdown::{MarkdownExport, MarkdownImport, SkippedFile, SkippedNote};
+pub use notebooks::Notebook;
+pub use recurring_todos::{RecurringTodo, TodoCompletion, TodoRecurrence};
+pub use reminders::{Recurrence, Reminder};
+pub use revisions::NoteRevision;
+pub use search::SearchResult;
+pub use sync::SyncReport;
+pub use tags::Tag;
+pub use todos::TodoItem;
+pub use workspaces::Workspace;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Note {
     pub id: String,
@@ -21,16 +58,139 @@ pub struct Note {
     pub always_on_top: bool,
     pub created_at: String,
     pub updated_at: String,
+    pub deleted_at: Option<String>,  // set while the note is in the trash
+    pub notebook_id: Option<String>,
+    pub is_locked: bool,  // content is encrypted and blank until unlocked
+    #[serde(default)]
+    pub is_template: bool,  // offered as a starting point for new notes
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
     pub theme: String,
     pub default_opacity: f64,
+    pub trash_retention_days: i64,
+    pub extract_hashtags: bool,
+    pub backup_keep_daily: i64,
+    pub backup_keep_weekly: i64,
+    /// Folder shared with other devices for sync; empty when sync is off
+    pub sync_dir: String,
+    /// Whether the local HTTP API for scripts is served
+    pub api_enabled: bool,
+    pub api_port: u16,
 }
 
 pub struct Database {
     conn: Mutex<Connection>,
+    data_dir: PathBuf,
+    /// SQLCipher key the store is currently opened with, if encrypted
+    key: Mutex<Option<String>>,
+}
+
+/// Smallest size a note window can be resized to.
+pub const MIN_NOTE_WIDTH: i32 = 200;
+pub const MIN_NOTE_HEIGHT: i32 = 150;
+
+/// How long a write waits for another process holding the database lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Column list matching `row_to_note`.
+const NOTE_COLUMNS: &str = "id, title, content, mode, pos_x, pos_y, width, height, opacity,
+    is_open, is_minimized, always_on_top, created_at, updated_at, deleted_at, notebook_id, is_locked, is_template";
+
+/// Stores new content for a note and brings everything derived from it in
+/// step: recurring todo lines, outgoing links and, if on, hashtag tags.
+fn save_content(conn: &Connection, id: &str, content: &str, now: &str, extract_hashtags: bool) -> SqlResult<()> {
+    recurring_todos::follow_todo_edits(conn, id, content)?;
+    conn.execute(
+        "UPDATE notes SET content = ?, updated_at = ? WHERE id = ?",
+        rusqlite::params![content, now, id],
+    )?;
+
+    links::sync_links(conn, id, content)?;
+
+    if extract_hashtags {
+        tags::sync_hashtags(conn, id, content)?;
+    }
+    Ok(())
+}
+
+fn row_to_note(row: &rusqlite::Row) -> SqlResult<Note> {
+    Ok(Note {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        content: row.get(2)?,
+        mode: row.get(3)?,
+        pos_x: row.get(4)?,
+        pos_y: row.get(5)?,
+        width: row.get(6)?,
+        height: row.get(7)?,
+        opacity: row.get(8)?,
+        is_open: row.get::<_, i32>(9)? == 1,
+        is_minimized: row.get::<_, i32>(10)? == 1,
+        always_on_top: row.get::<_, i32>(11)? == 1,
+        created_at: row.get(12)?,
+        updated_at: row.get(13)?,
+        deleted_at: row.get(14)?,
+        notebook_id: row.get(15)?,
+        is_locked: row.get::<_, i32>(16)? == 1,
+        is_template: row.get::<_, i32>(17)? == 1,
+    })
+}
+
+/// Inserts a complete note row, e.g. one read back from an export, and
+/// indexes its links. Locked notes can't be inserted this way since the
+/// row alone doesn't carry their ciphertext.
+fn insert_note(conn: &Connection, note: &Note) -> SqlResult<()> {
+    conn.execute(
+        &format!("INSERT INTO notes ({NOTE_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 0, ?)"),
+        rusqlite::params![
+            note.id,
+            note.title,
+            note.content,
+            note.mode,
+            note.pos_x,
+            note.pos_y,
+            note.width,
+            note.height,
+            note.opacity,
+            note.is_open as i32,
+            note.is_minimized as i32,
+            note.always_on_top as i32,
+            note.created_at,
+            note.updated_at,
+            note.deleted_at,
+            note.notebook_id,
+            note.is_template as i32,
+        ],
+    )?;
+    links::sync_links(conn, &note.id, &note.content)
+}
+
+/// Opens `path`, applying `password` as the SQLCipher key, and checks that
+/// the key actually decrypts the file.
+fn open_connection(path: &Path, password: Option<&str>) -> SqlResult<Connection> {
+    let conn = Connection::open(path)?;
+    if let Some(password) = password {
+        conn.pragma_update(None, "key", password)?;
+    }
+
+    // SQLCipher only notices a wrong key on the first read
+    if let Err(e) = conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())) {
+        return Err(match e.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::NotADatabase) => rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_NOTADB),
+                Some("incorrect database password".to_string()),
+            ),
+            _ => e,
+        });
+    }
+
+    // Child tables (revisions, ...) rely on ON DELETE CASCADE
+    conn.pragma_update(None, "foreign_keys", true)?;
+    // The app and the `notary` CLI may write at the same time
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    Ok(conn)
 }
 
 impl Database {
@@ -44,150 +204,190 @@ impl Database {
     }
 
     pub fn new(app_data_dir: PathBuf) -> SqlResult<Self> {
+        Self::open(app_data_dir, None)
+    }
+
+    /// Opens the store, keyed with the master `password` when it is encrypted
+    /// (see `Database::is_encrypted`).
+    pub fn open(app_data_dir: PathBuf, password: Option<&str>) -> SqlResult<Self> {
         std::fs::create_dir_all(&app_data_dir).ok();
-        let db_path = app_data_dir.join("notary.db");
-        let conn = Connection::open(db_path)?;
+        let mut conn = open_connection(&app_data_dir.join("notary.db"), password)?;
+        migrations::run(&mut conn)?;
 
         let db = Database {
             conn: Mutex::new(conn),
+            data_dir: app_data_dir,
+            key: Mutex::new(password.map(str::to_string)),
         };
-        db.init_tables()?;
+        db.init_settings()?;
         Ok(db)
     }
 
-    fn init_tables(&self) -> SqlResult<()> {
+    fn db_path(&self) -> PathBuf {
+        self.data_dir.join("notary.db")
+    }
+
+    fn key(&self) -> Option<String> {
+        self.key.lock().map(|key| key.clone()).unwrap_or(None)
+    }
+
+    fn set_key(&self, key: Option<&str>) {
+        if let Ok(mut current) = self.key.lock() {
+            *current = key.map(str::to_string);
+        }
+    }
+
+    /// Replaces `notary.db` with `replacement` and reopens it with `password`.
+    ///
+    /// The live file is kept as a backup until the replacement has been opened
+    /// successfully; on any failure it is moved back and reopened with
+    /// `previous_password`, leaving the store exactly as it was.
+    fn swap_database_file(
+        &self,
+        conn: &mut Connection,
+        replacement: &Path,
+        password: Option<&str>,
+        previous_password: Option<&str>,
+    ) -> SqlResult<()> {
+        let db_path = self.db_path();
+        let backup = self.data_dir.join("notary.db.swap-backup");
+        let io_error = |e: std::io::Error| {
+            rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_IOERR),
+                Some(format!("failed to replace database: {e}")),
+            )
+        };
+
+        // Close the live connection so the file can be renamed on every platform
+        let old = std::mem::replace(conn, Connection::open_in_memory()?);
+        old.close().map_err(|(_, e)| e)?;
+
+        let reopen_previous = |conn: &mut Connection| -> SqlResult<()> {
+            *conn = open_connection(&db_path, previous_password)?;
+            Ok(())
+        };
+
+        if let Err(e) = std::fs::rename(&db_path, &backup) {
+            reopen_previous(conn)?;
+            return Err(io_error(e));
+        }
+        if let Err(e) = std::fs::rename(replacement, &db_path) {
+            std::fs::rename(&backup, &db_path).map_err(io_error)?;
+            reopen_previous(conn)?;
+            return Err(io_error(e));
+        }
+
+        match open_connection(&db_path, password) {
+            Ok(new_conn) => {
+                *conn = new_conn;
+                self.set_key(password);
+                let _ = std::fs::remove_file(&backup);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&db_path);
+                std::fs::rename(&backup, &db_path).map_err(io_error)?;
+                reopen_previous(conn)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Changes whenever another connection, e.g. the `notary` CLI, commits
+    /// to the database; writes through this one leave it alone.
+    pub fn data_version(&self) -> SqlResult<i64> {
+        let conn = self.conn()?;
+        conn.pragma_query_value(None, "data_version", |row| row.get(0))
+    }
+
+    /// Current `PRAGMA user_version` of the open database.
+    pub fn schema_version(&self) -> SqlResult<i32> {
+        let conn = self.conn()?;
+        migrations::current_version(&conn)
+    }
+
+    fn init_settings(&self) -> SqlResult<()> {
         let conn = self.conn()?;
 
+        // Initialize default settings if not exist
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS notes (
-                id TEXT PRIMARY KEY,
-                title TEXT NOT NULL DEFAULT '',
-                content TEXT NOT NULL DEFAULT '',
-                pos_x INTEGER NOT NULL,
-                pos_y INTEGER NOT NULL,
-                width INTEGER NOT NULL DEFAULT 300,
-                height INTEGER NOT NULL DEFAULT 200,
-                opacity REAL NOT NULL DEFAULT 0.95,
-                is_open INTEGER NOT NULL DEFAULT 1,
-                is_minimized INTEGER NOT NULL DEFAULT 0,
-                always_on_top INTEGER NOT NULL DEFAULT 1,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )",
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('theme', 'light')",
             [],
         )?;
-
-        // Migrations
-        let _ = conn.execute("ALTER TABLE notes ADD COLUMN title TEXT NOT NULL DEFAULT ''", []);
-        let _ = conn.execute("ALTER TABLE notes ADD COLUMN mode TEXT NOT NULL DEFAULT 'text'", []);
-
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS settings (
-                key TEXT PRIMARY KEY,
-                value TEXT NOT NULL
-            )",
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('default_opacity', '0.95')",
             [],
         )?;
-
-        // Initialize default settings if not exist
         conn.execute(
-            "INSERT OR IGNORE INTO settings (key, value) VALUES ('theme', 'light')",
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('trash_retention_days', '30')",
             [],
         )?;
         conn.execute(
-            "INSERT OR IGNORE INTO settings (key, value) VALUES ('default_opacity', '0.95')",
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('extract_hashtags', 'false')",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('backup_keep_daily', '7')",
             [],
         )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('backup_keep_weekly', '4')",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('sync_dir', '')",
+            [],
+        )?;
+        // Names this device's change files in the sync folder
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('sync_device_id', ?)",
+            [Uuid::new_v4().to_string()],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('api_enabled', 'false')",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('api_port', '47100')",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value) VALUES ('api_token', ?)",
+            [Uuid::new_v4().simple().to_string()],
+        )?;
 
         Ok(())
     }
 
     pub fn get_all_notes(&self) -> SqlResult<Vec<Note>> {
         let conn = self.conn()?;
-        let mut stmt = conn.prepare(
-            "SELECT id, title, content, mode, pos_x, pos_y, width, height, opacity,
-                    is_open, is_minimized, always_on_top, created_at, updated_at
-             FROM notes ORDER BY created_at"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {NOTE_COLUMNS} FROM notes WHERE deleted_at IS NULL ORDER BY created_at"
+        ))?;
 
-        let notes = stmt.query_map([], |row| {
-            Ok(Note {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                content: row.get(2)?,
-                mode: row.get(3)?,
-                pos_x: row.get(4)?,
-                pos_y: row.get(5)?,
-                width: row.get(6)?,
-                height: row.get(7)?,
-                opacity: row.get(8)?,
-                is_open: row.get::<_, i32>(9)? == 1,
-                is_minimized: row.get::<_, i32>(10)? == 1,
-                always_on_top: row.get::<_, i32>(11)? == 1,
-                created_at: row.get(12)?,
-                updated_at: row.get(13)?,
-            })
-        })?.collect::<SqlResult<Vec<_>>>()?;
+        let notes = stmt.query_map([], row_to_note)?.collect::<SqlResult<Vec<_>>>()?;
 
         Ok(notes)
     }
 
     pub fn get_open_notes(&self) -> SqlResult<Vec<Note>> {
         let conn = self.conn()?;
-        let mut stmt = conn.prepare(
-            "SELECT id, title, content, mode, pos_x, pos_y, width, height, opacity,
-                    is_open, is_minimized, always_on_top, created_at, updated_at
-             FROM notes WHERE is_open = 1 ORDER BY created_at"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {NOTE_COLUMNS} FROM notes
+             WHERE is_open = 1 AND deleted_at IS NULL ORDER BY created_at"
+        ))?;
 
-        let notes = stmt.query_map([], |row| {
-            Ok(Note {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                content: row.get(2)?,
-                mode: row.get(3)?,
-                pos_x: row.get(4)?,
-                pos_y: row.get(5)?,
-                width: row.get(6)?,
-                height: row.get(7)?,
-                opacity: row.get(8)?,
-                is_open: row.get::<_, i32>(9)? == 1,
-                is_minimized: row.get::<_, i32>(10)? == 1,
-                always_on_top: row.get::<_, i32>(11)? == 1,
-                created_at: row.get(12)?,
-                updated_at: row.get(13)?,
-            })
-        })?.collect::<SqlResult<Vec<_>>>()?;
+        let notes = stmt.query_map([], row_to_note)?.collect::<SqlResult<Vec<_>>>()?;
 
         Ok(notes)
     }
 
+    /// Looks up a note by id, including notes in the trash.
     pub fn get_note(&self, id: &str) -> SqlResult<Option<Note>> {
         let conn = self.conn()?;
-        let mut stmt = conn.prepare(
-            "SELECT id, title, content, mode, pos_x, pos_y, width, height, opacity,
-                    is_open, is_minimized, always_on_top, created_at, updated_at
-             FROM notes WHERE id = ?"
-        )?;
+        let mut stmt = conn.prepare(&format!("SELECT {NOTE_COLUMNS} FROM notes WHERE id = ?"))?;
 
-        let mut notes = stmt.query_map([id], |row| {
-            Ok(Note {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                content: row.get(2)?,
-                mode: row.get(3)?,
-                pos_x: row.get(4)?,
-                pos_y: row.get(5)?,
-                width: row.get(6)?,
-                height: row.get(7)?,
-                opacity: row.get(8)?,
-                is_open: row.get::<_, i32>(9)? == 1,
-                is_minimized: row.get::<_, i32>(10)? == 1,
-                always_on_top: row.get::<_, i32>(11)? == 1,
-                created_at: row.get(12)?,
-                updated_at: row.get(13)?,
-            })
-        })?;
+        let mut notes = stmt.query_map([id], row_to_note)?;
 
         match notes.next() {
             Some(note) => Ok(Some(note?)),
@@ -226,19 +426,34 @@ impl Database {
             always_on_top: true,
             created_at: now.clone(),
             updated_at: now,
+            deleted_at: None,
+            notebook_id: None,
+            is_locked: false,
+            is_template: false,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn update_note(&self, id: &str, title: Option<&str>, content: Option<&str>,
                        mode: Option<&str>, pos_x: Option<i32>,
                        pos_y: Option<i32>, width: Option<i32>, height: Option<i32>,
                        opacity: Option<f64>, always_on_top: Option<bool>) -> SqlResult<()> {
         let now = Utc::now().to_rfc3339();
+        let extract_hashtags = content.is_some()
+            && self.get_setting("extract_hashtags").map(|v| v == "true").unwrap_or(false);
         let conn = self.conn()?;
 
         conn.execute_batch("BEGIN")?;
 
         let result = (|| -> SqlResult<()> {
+            if content.is_some() && locks::is_locked(&conn, id)? {
+                return Err(locks::locked_error());
+            }
+
+            if title.is_some() || content.is_some() {
+                revisions::snapshot_before_edit(&conn, id, title, content, &now, "edit")?;
+            }
+
             if let Some(title) = title {
                 conn.execute(
                     "UPDATE notes SET title = ?, updated_at = ? WHERE id = ?",
@@ -247,10 +462,7 @@ impl Database {
             }
 
             if let Some(content) = content {
-                conn.execute(
-                    "UPDATE notes SET content = ?, updated_at = ? WHERE id = ?",
-                    rusqlite::params![content, now, id],
-                )?;
+                save_content(&conn, id, content, &now, extract_hashtags)?;
             }
 
             if let Some(mode) = mode {
@@ -307,7 +519,7 @@ impl Database {
         let now = Utc::now().to_rfc3339();
         let conn = self.conn()?;
         conn.execute(
-            "UPDATE notes SET is_open = 1, updated_at = ? WHERE id = ?",
+            "UPDATE notes SET is_open = 1, updated_at = ? WHERE id = ? AND deleted_at IS NULL",
             rusqlite::params![now, id],
         )?;
         Ok(())
@@ -323,9 +535,16 @@ impl Database {
         Ok(())
     }
 
+    /// Moves a note to the trash. It stays restorable until the trash is
+    /// emptied or the retention period passes.
     pub fn delete_note(&self, id: &str) -> SqlResult<()> {
+        let now = Utc::now().to_rfc3339();
         let conn = self.conn()?;
-        conn.execute("DELETE FROM notes WHERE id = ?", [id])?;
+        conn.execute(
+            "UPDATE notes SET deleted_at = ?1, is_open = 0, updated_at = ?1
+             WHERE id = ?2 AND deleted_at IS NULL",
+            rusqlite::params![now, id],
+        )?;
         Ok(())
     }
 
@@ -355,6 +574,67 @@ impl Database {
                 .unwrap_or_else(|_| "0.95".to_string())
                 .parse()
                 .unwrap_or(0.95),
+            trash_retention_days: self.get_setting("trash_retention_days")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            extract_hashtags: self.get_setting("extract_hashtags")
+                .map(|v| v == "true")
+                .unwrap_or(false),
+            backup_keep_daily: self.get_setting("backup_keep_daily")
+                .unwrap_or_else(|_| "7".to_string())
+                .parse()
+                .unwrap_or(7),
+            backup_keep_weekly: self.get_setting("backup_keep_weekly")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()
+                .unwrap_or(4),
+            sync_dir: self.get_setting("sync_dir").unwrap_or_default(),
+            api_enabled: self.get_setting("api_enabled")
+                .map(|v| v == "true")
+                .unwrap_or(false),
+            api_port: self.get_setting("api_port")
+                .unwrap_or_else(|_| "47100".to_string())
+                .parse()
+                .unwrap_or(47100),
         })
     }
 }
+
+#[cfg(test)]
+mod test_support {
+    use std::ops::Deref;
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use super::Database;
+
+    /// A store in its own temporary directory, removed on drop.
+    pub struct TempDatabase {
+        db: Database,
+        dir: PathBuf,
+    }
+
+    impl TempDatabase {
+        pub fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("notary-test-{}", Uuid::new_v4()));
+            let db = Database::new(dir.clone()).expect("open test database");
+            TempDatabase { db, dir }
+        }
+    }
+
+    impl Deref for TempDatabase {
+        type Target = Database;
+
+        fn deref(&self) -> &Database {
+            &self.db
+        }
+    }
+
+    impl Drop for TempDatabase {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
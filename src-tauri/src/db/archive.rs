@@ -0,0 +1,499 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::{insert_note, links, migrations, revisions, row_to_note, tags, Database, Note, NoteRevision, NOTE_COLUMNS};
+
+/// Version of the archive layout itself, bumped on incompatible changes.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Settings that belong to the notes rather than to this machine.
+const PORTABLE_SETTINGS: &str = "key NOT LIKE 'sync\\_%' ESCAPE '\\' AND key NOT LIKE 'api\\_%' ESCAPE '\\'";
+
+/// Encrypted content of a locked note, as stored in `note_locks`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedLock {
+    pub note_id: String,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub locked_at: String,
+    /// Note id the ciphertext is bound to when it was copied from another
+    /// note; `None` when it is bound to `note_id`
+    #[serde(default)]
+    pub sealed_for: Option<String>,
+}
+
+/// A notebook; notes refer to it through `notebook_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedNotebook {
+    pub id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// One tag on one note.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedTag {
+    pub note_id: String,
+    pub name: String,
+    /// "manual" or "hashtag", as in `note_tags`
+    pub source: String,
+}
+
+/// Everything needed to move notes to another machine in one file, apart
+/// from attachment files, which would bloat it; their number is recorded so
+/// an import can say what is missing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    /// `PRAGMA user_version` of the exporting database
+    pub schema_version: i32,
+    pub exported_at: String,
+    /// All notes, including those in the trash
+    pub notes: Vec<Note>,
+    #[serde(default)]
+    pub locks: Vec<ArchivedLock>,
+    #[serde(default)]
+    pub notebooks: Vec<ArchivedNotebook>,
+    #[serde(default)]
+    pub tags: Vec<ArchivedTag>,
+    /// Oldest first
+    #[serde(default)]
+    pub revisions: Vec<NoteRevision>,
+    /// Attachments the exported notes had
+    #[serde(default)]
+    pub attachments_omitted: usize,
+    pub settings: BTreeMap<String, String>,
+}
+
+/// How `import_archive` treats notes that already exist locally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveStrategy {
+    /// Delete all local notes and settings first
+    ReplaceAll,
+    /// Only add notes whose id isn't present yet
+    SkipExisting,
+    /// Overwrite a local note when the archived one has a later `updated_at`
+    KeepNewer,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveImport {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    /// Attachments of the archived notes, which archives don't carry
+    pub attachments_omitted: usize,
+}
+
+const LOCK_COLUMNS: &str = "note_id, m_cost, t_cost, p_cost, salt, nonce, ciphertext, locked_at, sealed_for";
+
+fn row_to_lock(row: &rusqlite::Row) -> SqlResult<ArchivedLock> {
+    Ok(ArchivedLock {
+        note_id: row.get(0)?,
+        m_cost: row.get(1)?,
+        t_cost: row.get(2)?,
+        p_cost: row.get(3)?,
+        salt: row.get(4)?,
+        nonce: row.get(5)?,
+        ciphertext: row.get(6)?,
+        locked_at: row.get(7)?,
+        sealed_for: row.get(8)?,
+    })
+}
+
+fn archive_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_IOERR),
+        Some(message),
+    )
+}
+
+/// Whether timestamp `a` is later than `b`, comparing instants rather than
+/// strings so differing offsets still order correctly.
+pub(super) fn is_newer(a: &str, b: &str) -> bool {
+    match (DateTime::parse_from_rfc3339(a), DateTime::parse_from_rfc3339(b)) {
+        (Ok(a), Ok(b)) => a > b,
+        _ => a > b,
+    }
+}
+
+/// Overwrites every column of an existing note, keeping its revisions,
+/// tags and attachments.
+pub(super) fn overwrite_note(conn: &Connection, note: &Note) -> SqlResult<()> {
+    conn.execute("DELETE FROM note_locks WHERE note_id = ?", [&note.id])?;
+    conn.execute(
+        "UPDATE notes SET title = ?, content = ?, mode = ?, pos_x = ?, pos_y = ?, width = ?,
+                height = ?, opacity = ?, is_open = ?, is_minimized = ?, always_on_top = ?,
+                created_at = ?, updated_at = ?, deleted_at = ?, notebook_id = ?, is_locked = 0,
+                is_template = ?
+         WHERE id = ?",
+        rusqlite::params![
+            note.title,
+            note.content,
+            note.mode,
+            note.pos_x,
+            note.pos_y,
+            note.width,
+            note.height,
+            note.opacity,
+            note.is_open as i32,
+            note.is_minimized as i32,
+            note.always_on_top as i32,
+            note.created_at,
+            note.updated_at,
+            note.deleted_at,
+            note.notebook_id,
+            note.is_template as i32,
+            note.id,
+        ],
+    )?;
+    links::sync_links(conn, &note.id, &note.content)
+}
+
+/// Lock data of a note, if it is locked.
+pub(super) fn read_lock(conn: &Connection, note_id: &str) -> SqlResult<Option<ArchivedLock>> {
+    conn.query_row(
+        &format!("SELECT {LOCK_COLUMNS} FROM note_locks WHERE note_id = ?"),
+        [note_id],
+        row_to_lock,
+    )
+    .optional()
+}
+
+/// Files `note` as unfiled when its notebook doesn't exist in this database.
+pub(super) fn drop_missing_notebook(conn: &Connection, note: &mut Note) -> SqlResult<()> {
+    if let Some(notebook_id) = &note.notebook_id {
+        let exists = conn
+            .query_row("SELECT 1 FROM notebooks WHERE id = ?", [notebook_id], |_| Ok(()))
+            .optional()?
+            .is_some();
+        if !exists {
+            note.notebook_id = None;
+        }
+    }
+    Ok(())
+}
+
+/// Adds the archived notebooks that don't exist locally. Parents are linked
+/// once all are in, since they may come after their children.
+fn import_notebooks(conn: &Connection, notebooks: &[ArchivedNotebook]) -> SqlResult<()> {
+    let mut added = Vec::new();
+    for notebook in notebooks {
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO notebooks (id, name, parent_id, created_at, updated_at)
+             VALUES (?, ?, NULL, ?, ?)",
+            rusqlite::params![notebook.id, notebook.name, notebook.created_at, notebook.updated_at],
+        )?;
+        if inserted > 0 {
+            added.push(notebook);
+        }
+    }
+
+    for notebook in added {
+        conn.execute(
+            "UPDATE notebooks SET parent_id = ?1 WHERE id = ?2 AND EXISTS (SELECT 1 FROM notebooks WHERE id = ?1)",
+            rusqlite::params![notebook.parent_id, notebook.id],
+        )?;
+    }
+    Ok(())
+}
+
+pub(super) fn insert_lock(conn: &Connection, lock: &ArchivedLock) -> SqlResult<()> {
+    conn.execute(
+        &format!("INSERT INTO note_locks ({LOCK_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"),
+        rusqlite::params![
+            lock.note_id,
+            lock.m_cost,
+            lock.t_cost,
+            lock.p_cost,
+            lock.salt,
+            lock.nonce,
+            lock.ciphertext,
+            lock.locked_at,
+            lock.sealed_for,
+        ],
+    )?;
+    conn.execute("UPDATE notes SET is_locked = 1 WHERE id = ?", [&lock.note_id])?;
+    Ok(())
+}
+
+impl Database {
+    /// Writes all notes with their lock data, tags and revisions, the
+    /// notebooks and the settings to a JSON file at `path`. Returns how many
+    /// notes were written.
+    pub fn export_archive(&self, path: &Path) -> SqlResult<usize> {
+        let archive = {
+            let conn = self.conn()?;
+
+            let mut stmt = conn.prepare(&format!("SELECT {NOTE_COLUMNS} FROM notes ORDER BY created_at"))?;
+            let notes = stmt.query_map([], row_to_note)?.collect::<SqlResult<Vec<_>>>()?;
+
+            let mut stmt = conn.prepare(&format!("SELECT {LOCK_COLUMNS} FROM note_locks"))?;
+            let locks = stmt.query_map([], row_to_lock)?.collect::<SqlResult<Vec<_>>>()?;
+
+            let mut stmt = conn.prepare(
+                "SELECT id, name, parent_id, created_at, updated_at FROM notebooks ORDER BY created_at",
+            )?;
+            let notebooks = stmt
+                .query_map([], |row| {
+                    Ok(ArchivedNotebook {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        parent_id: row.get(2)?,
+                        created_at: row.get(3)?,
+                        updated_at: row.get(4)?,
+                    })
+                })?
+                .collect::<SqlResult<Vec<_>>>()?;
+
+            let mut stmt = conn.prepare(
+                "SELECT nt.note_id, t.name, nt.source FROM note_tags nt
+                 JOIN tags t ON t.id = nt.tag_id ORDER BY nt.note_id, t.name",
+            )?;
+            let tags = stmt
+                .query_map([], |row| Ok(ArchivedTag { note_id: row.get(0)?, name: row.get(1)?, source: row.get(2)? }))?
+                .collect::<SqlResult<Vec<_>>>()?;
+
+            let mut stmt = conn.prepare(
+                "SELECT id, note_id, title, content, source, created_at FROM note_revisions ORDER BY id",
+            )?;
+            let revisions = stmt.query_map([], revisions::row_to_revision)?.collect::<SqlResult<Vec<_>>>()?;
+
+            let attachments_omitted: usize = conn.query_row("SELECT count(*) FROM attachments", [], |row| row.get(0))?;
+
+            // Sync settings identify this device and its folders; the API
+            // is opted into per machine and its token is a secret
+            let mut stmt = conn.prepare(&format!("SELECT key, value FROM settings WHERE {PORTABLE_SETTINGS}"))?;
+            let settings = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<SqlResult<BTreeMap<String, String>>>()?;
+
+            Archive {
+                version: ARCHIVE_VERSION,
+                schema_version: migrations::current_version(&conn)?,
+                exported_at: Utc::now().to_rfc3339(),
+                notes,
+                locks,
+                notebooks,
+                tags,
+                revisions,
+                attachments_omitted,
+                settings,
+            }
+        };
+
+        let json = serde_json::to_vec_pretty(&archive)
+            .map_err(|e| archive_error(format!("failed to serialize archive: {e}")))?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .map_err(|e| archive_error(format!("failed to write {}: {e}", path.display())))?;
+
+        Ok(archive.notes.len())
+    }
+
+    /// Loads notes, notebooks and settings from an archive written by
+    /// `export_archive`. Settings and notebooks are overwritten only by
+    /// `ReplaceAll`; the other strategies just add the ones that don't exist
+    /// locally. Imported notes get their archived tags, and archived
+    /// revisions they don't have yet. Notes filed under a notebook that
+    /// exists in neither are imported unfiled.
+    pub fn import_archive(&self, path: &Path, strategy: ArchiveStrategy) -> SqlResult<ArchiveImport> {
+        let data = std::fs::read(path)
+            .map_err(|e| archive_error(format!("failed to read {}: {e}", path.display())))?;
+        let archive: Archive = serde_json::from_slice(&data)
+            .map_err(|e| archive_error(format!("not a valid archive: {e}")))?;
+
+        if archive.version > ARCHIVE_VERSION {
+            return Err(archive_error(format!(
+                "archive version {} is newer than this build supports ({ARCHIVE_VERSION})",
+                archive.version
+            )));
+        }
+        if archive.schema_version > self.schema_version()? {
+            return Err(archive_error(
+                "archive was exported by a newer version of the app".to_string(),
+            ));
+        }
+
+        let extract_hashtags = self.get_settings()?.extract_hashtags;
+        let mut report = ArchiveImport {
+            attachments_omitted: archive.attachments_omitted,
+            ..ArchiveImport::default()
+        };
+
+        {
+            let mut conn = self.conn()?;
+            let tx = conn.transaction()?;
+
+            if strategy == ArchiveStrategy::ReplaceAll {
+                tx.execute("DELETE FROM notes", [])?;
+                tx.execute("DELETE FROM notebooks", [])?;
+                tx.execute("DELETE FROM tags", [])?;
+                tx.execute(&format!("DELETE FROM settings WHERE {PORTABLE_SETTINGS}"), [])?;
+            }
+            for (key, value) in &archive.settings {
+                tx.execute(
+                    "INSERT OR IGNORE INTO settings (key, value) VALUES (?, ?)",
+                    rusqlite::params![key, value],
+                )?;
+            }
+
+            import_notebooks(&tx, &archive.notebooks)?;
+
+            for note in &archive.notes {
+                let lock = archive.locks.iter().find(|lock| lock.note_id == note.id);
+                if note.is_locked && lock.is_none() {
+                    // The content only exists as ciphertext, which is missing
+                    report.skipped += 1;
+                    continue;
+                }
+
+                let mut note = note.clone();
+                drop_missing_notebook(&tx, &mut note)?;
+
+                let local_updated_at: Option<String> = tx
+                    .query_row("SELECT updated_at FROM notes WHERE id = ?", [&note.id], |row| row.get(0))
+                    .optional()?;
+
+                match local_updated_at {
+                    None => {
+                        insert_note(&tx, &note)?;
+                        report.created += 1;
+                    }
+                    Some(local) if strategy == ArchiveStrategy::KeepNewer && is_newer(&note.updated_at, &local) => {
+                        // The local version stays recoverable from the history
+                        let now = Utc::now().to_rfc3339();
+                        revisions::snapshot_before_edit(&tx, &note.id, Some(&note.title), Some(&note.content), &now, "import")?;
+                        overwrite_note(&tx, &note)?;
+                        report.updated += 1;
+                    }
+                    Some(_) => {
+                        report.skipped += 1;
+                        continue;
+                    }
+                }
+
+                for tag in archive.tags.iter().filter(|tag| tag.note_id == note.id) {
+                    if let Some(name) = tags::normalize_tag_name(&tag.name) {
+                        let tag_id = tags::get_or_create_tag(&tx, &name)?;
+                        tx.execute(
+                            "INSERT OR IGNORE INTO note_tags (note_id, tag_id, source) VALUES (?, ?, ?)",
+                            rusqlite::params![note.id, tag_id, tag.source],
+                        )?;
+                    }
+                }
+                for revision in archive.revisions.iter().filter(|revision| revision.note_id == note.id) {
+                    tx.execute(
+                        "INSERT INTO note_revisions (note_id, title, content, source, created_at)
+                         SELECT ?1, ?2, ?3, ?4, ?5 WHERE NOT EXISTS (
+                             SELECT 1 FROM note_revisions WHERE note_id = ?1 AND created_at = ?5 AND content = ?3
+                         )",
+                        rusqlite::params![note.id, revision.title, revision.content, revision.source, revision.created_at],
+                    )?;
+                }
+
+                if let Some(lock) = lock {
+                    insert_lock(&tx, lock)?;
+                } else if extract_hashtags {
+                    tags::sync_hashtags(&tx, &note.id, &note.content)?;
+                }
+            }
+
+            tx.commit()?;
+        }
+
+        // Restore defaults for settings the archive didn't have
+        self.init_settings()?;
+        if strategy == ArchiveStrategy::ReplaceAll {
+            self.remove_orphaned_blobs()?;
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    fn exported() -> (TempDatabase, std::path::PathBuf, String) {
+        let db = TempDatabase::new();
+        let parent = db.create_notebook("Work", None).unwrap();
+        let child = db.create_notebook("Projects", Some(&parent.id)).unwrap();
+        let note = db.create_note(0, 0).unwrap();
+        db.update_note(&note.id, None, Some("first draft"), None, None, None, None, None, None, None).unwrap();
+        db.update_note(&note.id, None, Some("final"), None, None, None, None, None, None, None).unwrap();
+        db.move_note_to_notebook(&note.id, Some(&child.id)).unwrap();
+        db.add_note_tag(&note.id, "urgent").unwrap();
+        db.add_attachment(&note.id, "a.txt", None, b"attached").unwrap();
+
+        let path = db.data_dir.join("archive.json");
+        db.export_archive(&path).unwrap();
+        (db, path, note.id)
+    }
+
+    #[test]
+    fn archive_carries_tags_notebooks_and_revisions() {
+        let (source, path, id) = exported();
+        let target = TempDatabase::new();
+
+        let report = target.import_archive(&path, ArchiveStrategy::SkipExisting).unwrap();
+        assert_eq!((report.created, report.attachments_omitted), (1, 1));
+
+        let note = target.get_note(&id).unwrap().unwrap();
+        let notebooks = target.list_notebooks().unwrap();
+        let filed_in = notebooks.iter().find(|nb| Some(&nb.id) == note.notebook_id.as_ref()).unwrap();
+        assert_eq!(filed_in.name, "Projects");
+        let parent = notebooks.iter().find(|nb| Some(&nb.id) == filed_in.parent_id.as_ref()).unwrap();
+        assert_eq!(parent.name, "Work");
+
+        assert_eq!(target.get_note_tags(&id).unwrap(), ["urgent"]);
+        let contents = |db: &TempDatabase| -> Vec<String> {
+            db.list_note_revisions(&id).unwrap().into_iter().map(|r| r.content).collect()
+        };
+        assert!(!contents(&source).is_empty());
+        assert_eq!(contents(&target), contents(&source));
+        assert!(target.list_attachments(&id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reimporting_does_not_duplicate_revisions() {
+        let (source, path, id) = exported();
+        let before = source.list_note_revisions(&id).unwrap().len();
+
+        source.conn().unwrap().execute("UPDATE notes SET updated_at = '2000-01-01T00:00:00+00:00'", []).unwrap();
+        let report = source.import_archive(&path, ArchiveStrategy::KeepNewer).unwrap();
+        assert_eq!(report.updated, 1);
+        assert_eq!(source.list_note_revisions(&id).unwrap().len(), before);
+        assert_eq!(source.get_note_tags(&id).unwrap(), ["urgent"]);
+
+        source.import_archive(&path, ArchiveStrategy::ReplaceAll).unwrap();
+        assert_eq!(source.list_note_revisions(&id).unwrap().len(), before);
+        assert_eq!(source.list_notebooks().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn newer_archive_keeps_the_local_version_as_a_revision() {
+        let (db, path, id) = exported();
+        db.update_note(&id, None, Some("local edit"), None, None, None, None, None, None, None).unwrap();
+        db.conn().unwrap().execute("UPDATE notes SET updated_at = '2000-01-01T00:00:00+00:00'", []).unwrap();
+
+        let report = db.import_archive(&path, ArchiveStrategy::KeepNewer).unwrap();
+        assert_eq!(report.updated, 1);
+        assert_eq!(db.get_note(&id).unwrap().unwrap().content, "final");
+        let latest = db.list_note_revisions(&id).unwrap().into_iter().next().unwrap();
+        assert_eq!((latest.content.as_str(), latest.source.as_str()), ("local edit", "import"));
+    }
+}
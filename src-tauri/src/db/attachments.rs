@@ -0,0 +1,305 @@
+use chrono::Utc;
+use rusqlite::{OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use super::Database;
+
+/// Largest file accepted as an attachment.
+pub const MAX_ATTACHMENT_BYTES: usize = 50 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub note_id: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: i64,
+    /// SHA-256 of the content; identical files share one blob on disk
+    pub hash: String,
+    pub created_at: String,
+}
+
+fn attachment_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_IOERR),
+        Some(message),
+    )
+}
+
+/// Best-effort MIME type from a file name, for callers that don't supply one.
+pub fn guess_mime_type(file_name: &str) -> &'static str {
+    let ext = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "bmp" => "image/bmp",
+        "pdf" => "application/pdf",
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+fn row_to_attachment(row: &rusqlite::Row) -> SqlResult<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        note_id: row.get(1)?,
+        file_name: row.get(2)?,
+        mime_type: row.get(3)?,
+        size: row.get(4)?,
+        hash: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+impl Database {
+    fn attachments_dir(&self) -> PathBuf {
+        self.data_dir.join("attachments")
+    }
+
+    /// Blobs are sharded by the first two hex digits of their hash.
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.attachments_dir().join(&hash[..2]).join(hash)
+    }
+
+    /// Stores `data` as an attachment of `note_id`. The bytes are written once
+    /// per distinct content; re-attaching the same file only adds a row.
+    pub fn add_attachment(
+        &self,
+        note_id: &str,
+        file_name: &str,
+        mime_type: Option<&str>,
+        data: &[u8],
+    ) -> SqlResult<Attachment> {
+        if data.len() > MAX_ATTACHMENT_BYTES {
+            return Err(attachment_error(format!(
+                "attachment is larger than {} MB",
+                MAX_ATTACHMENT_BYTES / (1024 * 1024)
+            )));
+        }
+        if self.get_note(note_id)?.is_none() {
+            return Err(attachment_error(format!("note not found: {note_id}")));
+        }
+
+        let hash = format!("{:x}", Sha256::digest(data));
+
+        let file_name = Path::new(file_name.trim())
+            .file_name()
+            .and_then(|n| n.to_str())
+            .filter(|n| !n.is_empty())
+            .unwrap_or("attachment")
+            .to_string();
+        let attachment = Attachment {
+            id: Uuid::new_v4().to_string(),
+            note_id: note_id.to_string(),
+            mime_type: mime_type
+                .filter(|m| !m.is_empty())
+                .unwrap_or_else(|| guess_mime_type(&file_name))
+                .to_string(),
+            file_name,
+            size: data.len() as i64,
+            hash,
+            created_at: Utc::now().to_rfc3339(),
+        };
+
+        // Holding the lock while writing keeps `remove_orphaned_blobs` from
+        // deleting the blob before its row exists
+        let conn = self.conn()?;
+        let path = self.blob_path(&attachment.hash);
+        if !path.exists() {
+            write_blob(&path, data)
+                .map_err(|e| attachment_error(format!("failed to store attachment: {e}")))?;
+        }
+        conn.execute(
+            "INSERT INTO attachments (id, note_id, file_name, mime_type, size, hash, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![
+                attachment.id,
+                attachment.note_id,
+                attachment.file_name,
+                attachment.mime_type,
+                attachment.size,
+                attachment.hash,
+                attachment.created_at,
+            ],
+        )?;
+
+        Ok(attachment)
+    }
+
+    pub fn list_attachments(&self, note_id: &str) -> SqlResult<Vec<Attachment>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, note_id, file_name, mime_type, size, hash, created_at
+             FROM attachments WHERE note_id = ? ORDER BY created_at",
+        )?;
+        let attachments = stmt
+            .query_map([note_id], row_to_attachment)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(attachments)
+    }
+
+    pub fn get_attachment(&self, id: &str) -> SqlResult<Option<Attachment>> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT id, note_id, file_name, mime_type, size, hash, created_at
+             FROM attachments WHERE id = ?",
+            [id],
+            row_to_attachment,
+        )
+        .optional()
+    }
+
+    /// Reads an attachment's bytes from its blob.
+    pub fn read_attachment(&self, id: &str) -> SqlResult<Option<(Attachment, Vec<u8>)>> {
+        let Some(attachment) = self.get_attachment(id)? else {
+            return Ok(None);
+        };
+        let data = std::fs::read(self.blob_path(&attachment.hash))
+            .map_err(|e| attachment_error(format!("failed to read attachment: {e}")))?;
+        Ok(Some((attachment, data)))
+    }
+
+    pub fn remove_attachment(&self, id: &str) -> SqlResult<()> {
+        {
+            let conn = self.conn()?;
+            conn.execute("DELETE FROM attachments WHERE id = ?", [id])?;
+        }
+        self.remove_orphaned_blobs()?;
+        Ok(())
+    }
+
+    /// Deletes blob files no attachment row refers to any more, e.g. after
+    /// their notes were purged from the trash. Returns how many were removed.
+    pub fn remove_orphaned_blobs(&self) -> SqlResult<usize> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT DISTINCT hash FROM attachments")?;
+        let referenced = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<SqlResult<HashSet<_>>>()?;
+
+        let Ok(shards) = std::fs::read_dir(self.attachments_dir()) else {
+            return Ok(0);
+        };
+
+        let mut removed = 0;
+        for shard in shards.flatten() {
+            let Ok(blobs) = std::fs::read_dir(shard.path()) else {
+                continue;
+            };
+            for blob in blobs.flatten() {
+                let name = blob.file_name().to_string_lossy().to_string();
+                if !referenced.contains(&name) && std::fs::remove_file(blob.path()).is_ok() {
+                    removed += 1;
+                }
+            }
+            // Only succeeds once the shard is empty
+            let _ = std::fs::remove_dir(shard.path());
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Writes through a temporary file so a crash never leaves a truncated blob
+/// under its final, content-addressed name.
+fn write_blob(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    fn blob_exists(db: &TempDatabase, attachment: &Attachment) -> bool {
+        db.blob_path(&attachment.hash).exists()
+    }
+
+    #[test]
+    fn identical_files_share_one_blob() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        let first = db.add_attachment(&note.id, "a.png", None, b"same bytes").unwrap();
+        let second = db.add_attachment(&note.id, "dir/b.png", None, b"same bytes").unwrap();
+
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.hash, second.hash);
+        assert_eq!(second.file_name, "b.png");
+        assert_eq!(second.mime_type, "image/png");
+        assert_eq!(std::fs::read_dir(db.blob_path(&first.hash).parent().unwrap()).unwrap().count(), 1);
+
+        // The blob stays while any attachment still refers to it
+        db.remove_attachment(&first.id).unwrap();
+        assert!(blob_exists(&db, &second));
+        let (_, data) = db.read_attachment(&second.id).unwrap().unwrap();
+        assert_eq!(data, b"same bytes");
+
+        db.remove_attachment(&second.id).unwrap();
+        assert!(!blob_exists(&db, &second));
+    }
+
+    #[test]
+    fn rejects_oversized_files_and_missing_notes() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+
+        let too_big = vec![0u8; MAX_ATTACHMENT_BYTES + 1];
+        assert!(db.add_attachment(&note.id, "big.bin", None, &too_big).is_err());
+        assert!(db.add_attachment("missing", "a.txt", None, b"x").is_err());
+        assert!(db.list_attachments(&note.id).unwrap().is_empty());
+        assert!(!db.attachments_dir().exists());
+    }
+
+    #[test]
+    fn emptying_the_trash_removes_orphaned_blobs() {
+        let db = TempDatabase::new();
+        let kept = db.create_note(0, 0).unwrap();
+        let trashed = db.create_note(0, 0).unwrap();
+        let shared = db.add_attachment(&kept.id, "shared.txt", None, b"shared").unwrap();
+        db.add_attachment(&trashed.id, "shared.txt", None, b"shared").unwrap();
+        let only = db.add_attachment(&trashed.id, "only.txt", None, b"only").unwrap();
+
+        db.delete_note(&trashed.id).unwrap();
+        assert!(blob_exists(&db, &only));
+        db.empty_trash().unwrap();
+
+        assert!(!blob_exists(&db, &only));
+        assert!(blob_exists(&db, &shared));
+    }
+
+    #[test]
+    fn purging_expired_trash_removes_orphaned_blobs() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        let attachment = db.add_attachment(&note.id, "a.txt", None, b"old").unwrap();
+        db.delete_note(&note.id).unwrap();
+        db.conn()
+            .unwrap()
+            .execute("UPDATE notes SET deleted_at = '2000-01-01T00:00:00+00:00' WHERE id = ?", [&note.id])
+            .unwrap();
+
+        db.set_setting("trash_retention_days", "30").unwrap();
+        assert_eq!(db.purge_expired_trash().unwrap(), 1);
+        assert!(!blob_exists(&db, &attachment));
+    }
+}
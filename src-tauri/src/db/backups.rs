@@ -0,0 +1,298 @@
+use chrono::{DateTime, Local, Utc};
+use rusqlite::{Connection, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::encryption::{export_to, is_encrypted_file};
+use super::{migrations, open_connection, Database};
+
+/// Pages copied per backup step. The connection lock is held throughout, so
+/// nothing writes in between and there is no need to pause.
+const PAGES_PER_STEP: std::os::raw::c_int = 256;
+
+/// Snapshots taken right before a restore, so the restore can be undone.
+const MAX_RESTORE_SNAPSHOTS: usize = 5;
+
+/// Snapshots taken on request with `create_manual_backup`.
+const MAX_MANUAL_SNAPSHOTS: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    pub name: String,
+    /// "daily", "weekly", "manual" or "restore" (taken before restoring
+    /// another backup)
+    pub kind: String,
+    pub created_at: String,
+    pub size: u64,
+}
+
+fn backup_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_IOERR),
+        Some(message),
+    )
+}
+
+/// Kind of a backup file from its name, e.g. `notary-daily-2024-05-01.db`.
+fn backup_kind(name: &str) -> Option<&str> {
+    let stem = name.strip_prefix("notary-")?.strip_suffix(".db")?;
+    let (kind, _) = stem.split_once('-')?;
+    matches!(kind, "daily" | "weekly" | "manual" | "restore").then_some(kind)
+}
+
+/// Copies `conn` page by page into `target` with SQLite's online backup API.
+/// The copy is keyed with `key` so backups of an encrypted store stay
+/// encrypted, and only appears under `target` once complete.
+fn snapshot(conn: &Connection, target: &Path, key: Option<&str>) -> SqlResult<()> {
+    let tmp = target.with_extension("db.tmp");
+    let _ = std::fs::remove_file(&tmp);
+
+    let mut dest = Connection::open(&tmp)?;
+    if let Some(key) = key {
+        dest.pragma_update(None, "key", key)?;
+    }
+    rusqlite::backup::Backup::new(conn, &mut dest)?.run_to_completion(PAGES_PER_STEP, Duration::ZERO, None)?;
+    dest.close().map_err(|(_, e)| e)?;
+
+    std::fs::rename(&tmp, target)
+        .map_err(|e| backup_error(format!("failed to write backup: {e}")))
+}
+
+/// Overwrites a file with zeros before deleting it. Best effort: file
+/// systems that copy on write may still keep the old blocks around.
+fn shred_file(path: &Path) -> std::io::Result<()> {
+    let len = std::fs::metadata(path)?.len();
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    std::io::copy(&mut std::io::repeat(0).take(len), &mut file)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::remove_file(path)
+}
+
+impl Database {
+    fn backups_dir(&self) -> PathBuf {
+        self.data_dir.join("backups")
+    }
+
+    fn create_backups_dir(&self) -> SqlResult<PathBuf> {
+        let dir = self.backups_dir();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| backup_error(format!("failed to create backup directory: {e}")))?;
+        Ok(dir)
+    }
+
+    /// Takes today's daily snapshot and this week's weekly one unless they
+    /// were already taken, so each holds the state from the start of its day
+    /// or week, then prunes old backups beyond the `backup_keep_daily` /
+    /// `backup_keep_weekly` settings. Attachment files are not part of
+    /// backups.
+    pub fn create_backup(&self) -> SqlResult<()> {
+        let dir = self.create_backups_dir()?;
+        let now = Local::now();
+        let daily = dir.join(format!("notary-daily-{}.db", now.format("%Y-%m-%d")));
+        let weekly = dir.join(format!("notary-weekly-{}.db", now.format("%G-W%V")));
+
+        if !daily.exists() {
+            let conn = self.conn()?;
+            snapshot(&conn, &daily, self.key().as_deref())?;
+        }
+
+        if !weekly.exists() {
+            let tmp = weekly.with_extension("db.tmp");
+            std::fs::copy(&daily, &tmp)
+                .and_then(|_| std::fs::rename(&tmp, &weekly))
+                .map_err(|e| backup_error(format!("failed to write backup: {e}")))?;
+        }
+
+        self.rotate_backups()
+    }
+
+    /// Takes a snapshot now, kept apart from the daily and weekly rotation.
+    pub fn create_manual_backup(&self) -> SqlResult<()> {
+        let dir = self.create_backups_dir()?;
+        let target = dir.join(format!("notary-manual-{}.db", Local::now().format("%Y-%m-%d-%H%M%S")));
+        {
+            let conn = self.conn()?;
+            snapshot(&conn, &target, self.key().as_deref())?;
+        }
+        self.rotate_backups()
+    }
+
+    fn rotate_backups(&self) -> SqlResult<()> {
+        let settings = self.get_settings()?;
+        let backups = self.list_backups()?;
+
+        for (kind, keep) in [
+            ("daily", settings.backup_keep_daily.max(1) as usize),
+            ("weekly", settings.backup_keep_weekly.max(0) as usize),
+            ("manual", MAX_MANUAL_SNAPSHOTS),
+            ("restore", MAX_RESTORE_SNAPSHOTS),
+        ] {
+            // Names sort chronologically within a kind
+            let mut names: Vec<&str> = backups
+                .iter()
+                .filter(|backup| backup.kind == kind)
+                .map(|backup| backup.name.as_str())
+                .collect();
+            names.sort_unstable_by(|a, b| b.cmp(a));
+
+            for name in names.into_iter().skip(keep) {
+                std::fs::remove_file(self.backups_dir().join(name))
+                    .map_err(|e| backup_error(format!("failed to remove old backup {name}: {e}")))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rewrites every backup under `key`, or as plaintext when `None`, after
+    /// the store's encryption changed, so no backup stays readable under the
+    /// old setting. A backup that can't be opened with `previous_key` is
+    /// deleted instead.
+    pub(super) fn rekey_backups(&self, previous_key: Option<&str>, key: Option<&str>) -> SqlResult<()> {
+        for backup in self.list_backups()? {
+            let path = self.backups_dir().join(&backup.name);
+            let tmp = path.with_extension("db.tmp");
+            let encrypted = is_encrypted_file(&path);
+            // The modification time is the backup's creation time
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+
+            let rewritten = open_connection(&path, if encrypted { previous_key } else { None })
+                .and_then(|source| export_to(&source, &tmp, key.unwrap_or("")));
+
+            // Plaintext is overwritten rather than just unlinked
+            let removed = if encrypted { std::fs::remove_file(&path) } else { shred_file(&path) };
+            removed.map_err(|e| backup_error(format!("failed to remove backup {}: {e}", backup.name)))?;
+
+            match rewritten {
+                Ok(()) => {
+                    std::fs::rename(&tmp, &path)
+                        .map_err(|e| backup_error(format!("failed to write backup {}: {e}", backup.name)))?;
+                    if let Some(modified) = modified {
+                        let _ = std::fs::File::options().write(true).open(&path).and_then(|f| f.set_modified(modified));
+                    }
+                }
+                Err(_) => {
+                    let _ = std::fs::remove_file(&tmp);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Available backups, newest first.
+    pub fn list_backups(&self) -> SqlResult<Vec<Backup>> {
+        let Ok(entries) = std::fs::read_dir(self.backups_dir()) else {
+            return Ok(Vec::new());
+        };
+
+        let mut backups: Vec<Backup> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let kind = backup_kind(&name)?.to_string();
+                let metadata = entry.metadata().ok()?;
+                let created_at: DateTime<Utc> = metadata.modified().ok()?.into();
+                Some(Backup {
+                    name,
+                    kind,
+                    created_at: created_at.to_rfc3339(),
+                    size: metadata.len(),
+                })
+            })
+            .collect();
+
+        backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(backups)
+    }
+
+    /// Replaces the live database with the backup called `name` and reopens
+    /// the connection, without restarting the app. The current state is
+    /// snapshotted first so the restore can be undone.
+    ///
+    /// The restored store keeps the current encryption setting and password.
+    /// An encrypted backup can only be read with the password it was taken
+    /// under, so it has to match the current one, and a plaintext backup is
+    /// refused by an encrypted store.
+    pub fn restore_backup(&self, name: &str) -> SqlResult<()> {
+        if !self.list_backups()?.iter().any(|backup| backup.name == name) {
+            return Err(backup_error(format!("backup not found: {name}")));
+        }
+        let source_path = self.backups_dir().join(name);
+        let key = self.key();
+
+        {
+            let mut conn = self.conn()?;
+
+            let source_encrypted = is_encrypted_file(&source_path);
+            if source_encrypted && key.is_none() {
+                return Err(backup_error(
+                    "backup is encrypted but the database is not; enable encryption with its password first"
+                        .to_string(),
+                ));
+            }
+            if !source_encrypted && key.is_some() {
+                return Err(backup_error(
+                    "backup is not encrypted but the database is; it cannot be restored into an encrypted store"
+                        .to_string(),
+                ));
+            }
+            let source = open_connection(&source_path, key.as_deref())?;
+
+            let undo = self.backups_dir().join(format!(
+                "notary-restore-{}.db",
+                Local::now().format("%Y%m%d-%H%M%S")
+            ));
+            snapshot(&conn, &undo, key.as_deref())?;
+
+            let tmp = self.data_dir.join("notary.db.restoring");
+            let _ = std::fs::remove_file(&tmp);
+            snapshot(&source, &tmp, key.as_deref())?;
+            drop(source);
+
+            // Bring backups from older versions up to the current schema
+            // before they go live
+            {
+                let mut restored = open_connection(&tmp, key.as_deref())?;
+                migrations::run(&mut restored)?;
+            }
+
+            self.swap_database_file(&mut conn, &tmp, key.as_deref(), key.as_deref())?;
+        }
+
+        self.init_settings()?;
+        self.rotate_backups()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+
+    fn backup_contents(db: &TempDatabase, kind: &str) -> Vec<String> {
+        let path = db.backups_dir().join(&db.list_backups().unwrap().iter().find(|b| b.kind == kind).unwrap().name);
+        let conn = rusqlite::Connection::open(path).unwrap();
+        let mut stmt = conn.prepare("SELECT content FROM notes ORDER BY created_at").unwrap();
+        let contents = stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<_, _>>().unwrap();
+        contents
+    }
+
+    #[test]
+    fn periodic_backups_keep_the_first_snapshot_of_the_period() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        db.update_note(&note.id, None, Some("morning"), None, None, None, None, None, None, None).unwrap();
+        db.create_backup().unwrap();
+
+        db.update_note(&note.id, None, Some("evening"), None, None, None, None, None, None, None).unwrap();
+        db.create_backup().unwrap();
+
+        assert_eq!(backup_contents(&db, "daily"), ["morning"]);
+        assert_eq!(backup_contents(&db, "weekly"), ["morning"]);
+
+        db.create_manual_backup().unwrap();
+        assert_eq!(backup_contents(&db, "manual"), ["evening"]);
+    }
+}
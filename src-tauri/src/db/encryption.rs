@@ -0,0 +1,260 @@
+use rusqlite::{Connection, DatabaseName, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+use super::{migrations, open_connection, Database};
+
+/// Every plaintext SQLite file starts with this header; SQLCipher files are
+/// indistinguishable from random bytes.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionStatus {
+    /// Whether this build links SQLCipher at all
+    pub supported: bool,
+    pub enabled: bool,
+}
+
+fn encryption_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_AUTH),
+        Some(message),
+    )
+}
+
+/// Whether the SQLite library this build links against is SQLCipher.
+fn cipher_supported(conn: &Connection) -> SqlResult<bool> {
+    conn.query_row("PRAGMA cipher_version", [], |row| row.get::<_, String>(0))
+        .optional()
+        .map(|version| version.is_some())
+}
+
+/// Copies the open database into a new file at `target`, encrypted with
+/// `key`, or plaintext when `key` is empty.
+pub(super) fn export_to(conn: &Connection, target: &Path, key: &str) -> SqlResult<()> {
+    if target.exists() {
+        std::fs::remove_file(target)
+            .map_err(|e| encryption_error(format!("failed to remove {}: {e}", target.display())))?;
+    }
+
+    let version = migrations::current_version(conn)?;
+    conn.execute(
+        "ATTACH DATABASE ?1 AS export KEY ?2",
+        rusqlite::params![target.to_string_lossy(), key],
+    )?;
+    let result = conn
+        .query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()))
+        .and_then(|()| conn.pragma_update(Some(DatabaseName::Attached("export")), "user_version", version));
+    conn.execute_batch("DETACH DATABASE export")?;
+    result
+}
+
+/// Whether the database file at `path` is encrypted.
+pub(super) fn is_encrypted_file(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match std::fs::File::open(path) {
+        Ok(mut file) => match file.read_exact(&mut header) {
+            Ok(()) => header != SQLITE_HEADER,
+            // An empty file is a database that was never written
+            Err(_) => false,
+        },
+        Err(_) => false,
+    }
+}
+
+impl Database {
+    /// Whether `notary.db` under `app_data_dir` is encrypted and has to be
+    /// opened with `Database::open` and the master password.
+    pub fn is_encrypted(app_data_dir: &Path) -> bool {
+        is_encrypted_file(&app_data_dir.join("notary.db"))
+    }
+
+    pub fn encryption_status(&self) -> SqlResult<EncryptionStatus> {
+        let conn = self.conn()?;
+        Ok(EncryptionStatus {
+            supported: cipher_supported(&conn)?,
+            enabled: Database::is_encrypted(&self.data_dir),
+        })
+    }
+
+    pub(super) fn require_cipher(&self, conn: &Connection) -> SqlResult<()> {
+        if cipher_supported(conn)? {
+            Ok(())
+        } else {
+            Err(encryption_error(
+                "this build does not include database encryption support".to_string(),
+            ))
+        }
+    }
+
+    /// Fails unless `password` opens the database file.
+    fn verify_password(&self, password: &str) -> SqlResult<()> {
+        open_connection(&self.db_path(), Some(password)).map(|_| ())
+    }
+
+    /// Encrypts the whole store with `password`, existing backups included.
+    pub fn enable_encryption(&self, password: &str) -> SqlResult<()> {
+        if password.is_empty() {
+            return Err(encryption_error("password cannot be empty".to_string()));
+        }
+        if Database::is_encrypted(&self.data_dir) {
+            return Err(encryption_error("database is already encrypted".to_string()));
+        }
+
+        let mut conn = self.conn()?;
+        self.require_cipher(&conn)?;
+
+        let tmp = self.data_dir.join("notary.db.encrypting");
+        export_to(&conn, &tmp, password)?;
+        self.swap_database_file(&mut conn, &tmp, Some(password), None)?;
+        self.rekey_backups(None, Some(password))
+    }
+
+    /// Re-encrypts the store and its backups under a new password.
+    pub fn change_encryption_password(&self, current: &str, new: &str) -> SqlResult<()> {
+        if new.is_empty() {
+            return Err(encryption_error("password cannot be empty".to_string()));
+        }
+        if !Database::is_encrypted(&self.data_dir) {
+            return Err(encryption_error("database is not encrypted".to_string()));
+        }
+
+        let conn = self.conn()?;
+        self.require_cipher(&conn)?;
+        self.verify_password(current)?;
+        conn.pragma_update(None, "rekey", new)?;
+        self.set_key(Some(new));
+        self.rekey_backups(Some(current), Some(new))
+    }
+
+    /// Decrypts the store, and its backups, back to plaintext.
+    pub fn disable_encryption(&self, password: &str) -> SqlResult<()> {
+        if !Database::is_encrypted(&self.data_dir) {
+            return Err(encryption_error("database is not encrypted".to_string()));
+        }
+
+        let mut conn = self.conn()?;
+        self.require_cipher(&conn)?;
+        self.verify_password(password)?;
+
+        let tmp = self.data_dir.join("notary.db.decrypting");
+        export_to(&conn, &tmp, "")?;
+        self.swap_database_file(&mut conn, &tmp, None, Some(password))?;
+        self.rekey_backups(Some(password), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    fn set_content(db: &TempDatabase, id: &str, content: &str) {
+        db.update_note(id, None, Some(content), None, None, None, None, None, None, None).unwrap();
+    }
+
+    fn content(db: &TempDatabase, id: &str) -> String {
+        db.get_note(id).unwrap().unwrap().content
+    }
+
+    /// The only backup, opened with `password`.
+    #[cfg(feature = "sqlcipher")]
+    fn open_backup(db: &TempDatabase, password: Option<&str>) -> SqlResult<Connection> {
+        let backups = db.list_backups().unwrap();
+        assert_eq!(backups.len(), 1);
+        open_connection(&db.data_dir.join("backups").join(&backups[0].name), password)
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn round_trip_takes_backups_along() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        set_content(&db, &note.id, "secret");
+        db.create_manual_backup().unwrap();
+
+        assert!(db.enable_encryption("").is_err());
+        db.enable_encryption("first").unwrap();
+        assert!(db.encryption_status().unwrap().enabled);
+        assert_eq!(content(&db, &note.id), "secret");
+        assert!(open_backup(&db, None).is_err());
+        let backup = open_backup(&db, Some("first")).unwrap();
+        let backed_up: String = backup.query_row("SELECT content FROM notes", [], |row| row.get(0)).unwrap();
+        assert_eq!(backed_up, "secret");
+        drop(backup);
+
+        db.change_encryption_password("first", "second").unwrap();
+        assert_eq!(content(&db, &note.id), "secret");
+        assert!(open_backup(&db, Some("first")).is_err());
+        assert!(open_backup(&db, Some("second")).is_ok());
+
+        // New backups are taken under the current password
+        set_content(&db, &note.id, "newer");
+        db.create_backup().unwrap();
+        for backup in db.list_backups().unwrap() {
+            let path = db.data_dir.join("backups").join(&backup.name);
+            assert!(open_connection(&path, Some("second")).is_ok());
+        }
+        for backup in db.list_backups().unwrap().into_iter().filter(|b| b.kind != "manual") {
+            std::fs::remove_file(db.data_dir.join("backups").join(backup.name)).unwrap();
+        }
+
+        db.disable_encryption("second").unwrap();
+        assert!(!db.encryption_status().unwrap().enabled);
+        assert_eq!(content(&db, &note.id), "newer");
+        assert!(open_backup(&db, None).is_ok());
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn wrong_password_changes_nothing() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        set_content(&db, &note.id, "secret");
+        db.enable_encryption("right").unwrap();
+        assert!(db.enable_encryption("again").is_err());
+
+        assert!(db.change_encryption_password("wrong", "new").is_err());
+        assert!(db.disable_encryption("wrong").is_err());
+        assert!(db.encryption_status().unwrap().enabled);
+        assert_eq!(content(&db, &note.id), "secret");
+        assert!(open_connection(&db.db_path(), Some("right")).is_ok());
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    #[test]
+    fn encrypted_store_refuses_plaintext_backups() {
+        let db = TempDatabase::new();
+        db.enable_encryption("pw").unwrap();
+
+        let backups = db.data_dir.join("backups");
+        std::fs::create_dir_all(&backups).unwrap();
+        let plain = Connection::open(backups.join("notary-manual-2000-01-01-000000.db")).unwrap();
+        plain.execute_batch("CREATE TABLE notes (id TEXT)").unwrap();
+        drop(plain);
+
+        assert!(db.restore_backup("notary-manual-2000-01-01-000000.db").is_err());
+        assert!(db.encryption_status().unwrap().enabled);
+    }
+
+    #[test]
+    fn failed_swap_leaves_the_original_in_place() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        set_content(&db, &note.id, "keep");
+
+        let replacement = db.data_dir.join("notary.db.broken");
+        std::fs::write(&replacement, vec![0xA5u8; 4096]).unwrap();
+        {
+            let mut conn = db.conn().unwrap();
+            assert!(db.swap_database_file(&mut conn, &replacement, Some("pw"), None).is_err());
+        }
+
+        assert!(!Database::is_encrypted(&db.data_dir));
+        assert!(!db.data_dir.join("notary.db.swap-backup").exists());
+        assert_eq!(content(&db, &note.id), "keep");
+        set_content(&db, &note.id, "still writable");
+        assert_eq!(content(&db, &note.id), "still writable");
+    }
+}
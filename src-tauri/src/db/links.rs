@@ -0,0 +1,309 @@
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+
+use super::{revisions, row_to_note, Database, Note, NOTE_COLUMNS};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutgoingLink {
+    /// Link text as written, i.e. `Title` for `[[Title]]` or `[[Title|alias]]`
+    pub target: String,
+    /// The note the link resolves to, if any
+    pub note_id: Option<String>,
+    pub note_title: Option<String>,
+}
+
+/// Calls `f` with the byte range and inner text of every `[[...]]` link.
+fn for_each_link(content: &str, mut f: impl FnMut(std::ops::Range<usize>, &str)) {
+    let mut pos = 0;
+    while let Some(open) = content[pos..].find("[[") {
+        let start = pos + open;
+        let inner_start = start + 2;
+        let Some(close) = content[inner_start..].find("]]") else {
+            break;
+        };
+        let inner_end = inner_start + close;
+        let inner = &content[inner_start..inner_end];
+
+        // A link never spans lines; restart the scan inside it if it seems to
+        if let Some(newline) = inner.find('\n') {
+            pos = inner_start + newline;
+            continue;
+        }
+
+        f(start..inner_end + 2, inner);
+        pos = inner_end + 2;
+    }
+}
+
+/// The part of a link's inner text naming the target (before any `|alias`).
+fn link_target(inner: &str) -> &str {
+    inner.split('|').next().unwrap_or("").trim()
+}
+
+/// Extracts `[[Note Title]]`, `[[note-id]]` and `[[Title|alias]]` link
+/// targets from note content, without duplicates (compared case-insensitively).
+pub fn parse_links(content: &str) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    for_each_link(content, |_, inner| {
+        let target = link_target(inner);
+        if !target.is_empty() && !targets.iter().any(|t| t.to_lowercase() == target.to_lowercase()) {
+            targets.push(target.to_string());
+        }
+    });
+    targets
+}
+
+/// Rewrites links whose target is `old_target` (case-insensitively) to point
+/// at `new_target`, keeping any alias. Returns `None` when nothing changed.
+pub fn retarget_links(content: &str, old_target: &str, new_target: &str) -> Option<String> {
+    let old_target = old_target.to_lowercase();
+    let mut replacements = Vec::new();
+
+    for_each_link(content, |range, inner| {
+        if link_target(inner).to_lowercase() == old_target {
+            let replacement = match inner.split_once('|') {
+                Some((_, alias)) => format!("[[{new_target}|{alias}]]"),
+                None => format!("[[{new_target}]]"),
+            };
+            replacements.push((range, replacement));
+        }
+    });
+
+    if replacements.is_empty() {
+        return None;
+    }
+
+    let mut result = content.to_string();
+    for (range, replacement) in replacements.into_iter().rev() {
+        result.replace_range(range, &replacement);
+    }
+    Some(result)
+}
+
+/// Replaces the stored outgoing links of a note with those found in `content`.
+pub(super) fn sync_links(conn: &Connection, note_id: &str, content: &str) -> SqlResult<()> {
+    conn.execute("DELETE FROM note_links WHERE source_id = ?", [note_id])?;
+    for target in parse_links(content) {
+        conn.execute(
+            "INSERT OR IGNORE INTO note_links (source_id, target) VALUES (?, ?)",
+            rusqlite::params![note_id, target],
+        )?;
+    }
+    Ok(())
+}
+
+impl Database {
+    /// Notes whose content links to `id`, either by id or by its current title.
+    pub fn get_backlinks(&self, id: &str) -> SqlResult<Vec<Note>> {
+        let conn = self.conn()?;
+        let title: Option<String> = conn
+            .query_row("SELECT title FROM notes WHERE id = ?", [id], |row| row.get(0))
+            .optional()?;
+        let Some(title) = title else {
+            return Ok(Vec::new());
+        };
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {NOTE_COLUMNS} FROM notes
+             WHERE deleted_at IS NULL AND id != ?1 AND id IN (
+                 SELECT source_id FROM note_links
+                 WHERE target = ?1 OR (?2 != '' AND target = ?2)
+             )
+             ORDER BY title COLLATE NOCASE"
+        ))?;
+        let notes = stmt
+            .query_map(rusqlite::params![id, title.trim()], row_to_note)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(notes)
+    }
+
+    /// Links found in a note's content, each resolved to a note by id first
+    /// and otherwise by title. Links to missing or trashed notes come back
+    /// unresolved.
+    pub fn get_outgoing_links(&self, id: &str) -> SqlResult<Vec<OutgoingLink>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT l.target, n.id, n.title
+             FROM note_links l
+             LEFT JOIN notes n ON n.id = COALESCE(
+                 (SELECT id FROM notes WHERE id = l.target AND deleted_at IS NULL),
+                 (SELECT id FROM notes
+                  WHERE title = l.target COLLATE NOCASE AND deleted_at IS NULL
+                  ORDER BY created_at LIMIT 1)
+             )
+             WHERE l.source_id = ?
+             ORDER BY l.rowid",
+        )?;
+        let links = stmt
+            .query_map([id], |row| {
+                Ok(OutgoingLink {
+                    target: row.get(0)?,
+                    note_id: row.get(1)?,
+                    note_title: row.get(2)?,
+                })
+            })?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(links)
+    }
+
+    /// Changes a note's title. With `rewrite_links`, `[[Old Title]]` links in
+    /// other notes are updated to the new title (or to the note id when the new
+    /// title cannot be written as a link). Returns the ids of the other notes
+    /// whose content was rewritten.
+    pub fn rename_note(&self, id: &str, title: &str, rewrite_links: bool) -> SqlResult<Vec<String>> {
+        let now = Utc::now().to_rfc3339();
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let old_title: Option<String> = tx
+            .query_row("SELECT title FROM notes WHERE id = ?", [id], |row| row.get(0))
+            .optional()?;
+        let Some(old_title) = old_title else {
+            return Ok(Vec::new());
+        };
+
+        revisions::snapshot_before_edit(&tx, id, Some(title), None, &now, "edit")?;
+        tx.execute(
+            "UPDATE notes SET title = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![title, now, id],
+        )?;
+
+        let old_target = old_title.trim();
+        let mut rewritten = Vec::new();
+
+        if rewrite_links && !old_target.is_empty() && old_target != title.trim() {
+            let new_target = title.trim();
+            let new_target = if new_target.is_empty()
+                || new_target.contains("]]")
+                || new_target.contains('|')
+                || new_target.contains('\n')
+            {
+                id
+            } else {
+                new_target
+            };
+
+            let mut stmt = tx.prepare(
+                "SELECT id, content FROM notes
+                 WHERE id != ?1 AND id IN (SELECT source_id FROM note_links WHERE target = ?2)",
+            )?;
+            let sources = stmt
+                .query_map(rusqlite::params![id, old_target], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<SqlResult<Vec<_>>>()?;
+            drop(stmt);
+
+            for (source_id, content) in sources {
+                let Some(new_content) = retarget_links(&content, old_target, new_target) else {
+                    continue;
+                };
+                revisions::snapshot_before_edit(&tx, &source_id, None, Some(&new_content), &now, "edit")?;
+                tx.execute(
+                    "UPDATE notes SET content = ?, updated_at = ? WHERE id = ?",
+                    rusqlite::params![new_content, now, source_id],
+                )?;
+                sync_links(&tx, &source_id, &new_content)?;
+                rewritten.push(source_id);
+            }
+        }
+
+        tx.commit()?;
+        Ok(rewritten)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    fn set_note(db: &TempDatabase, id: &str, title: &str, content: &str) {
+        db.update_note(id, Some(title), Some(content), None, None, None, None, None, None, None).unwrap();
+    }
+
+    fn ids(notes: &[Note]) -> Vec<&str> {
+        notes.iter().map(|note| note.id.as_str()).collect()
+    }
+
+    #[test]
+    fn finds_links_with_aliases_once_each() {
+        let content = "see [[Groceries]], [[groceries|the list]] and [[ Recipes | dinner ]] [[]]";
+        assert_eq!(parse_links(content), ["Groceries", "Recipes"]);
+    }
+
+    #[test]
+    fn links_do_not_span_lines() {
+        // The unclosed `[[` must not swallow the next line's link
+        assert_eq!(parse_links("a [[broken\nthen [[Real]]"), ["Real"]);
+
+        let mut found = Vec::new();
+        for_each_link("x [[a\n[[b]] [[c]]", |range, inner| found.push((range, inner.to_string())));
+        assert_eq!(found, [(6..11, "b".to_string()), (12..17, "c".to_string())]);
+    }
+
+    #[test]
+    fn retargets_matching_links_and_keeps_aliases() {
+        let content = "[[Old]] and [[old|alias]] but not [[Older]]";
+        assert_eq!(
+            retarget_links(content, "Old", "New").as_deref(),
+            Some("[[New]] and [[New|alias]] but not [[Older]]")
+        );
+        assert_eq!(retarget_links(content, "Missing", "New"), None);
+    }
+
+    #[test]
+    fn backlinks_follow_title_and_id() {
+        let db = TempDatabase::new();
+        let target = db.create_note(0, 0).unwrap();
+        set_note(&db, &target.id, "Target", "");
+        let by_title = db.create_note(0, 0).unwrap();
+        set_note(&db, &by_title.id, "A", "see [[Target]]");
+        let by_id = db.create_note(0, 0).unwrap();
+        set_note(&db, &by_id.id, "B", &format!("see [[{}|there]]", target.id));
+        let trashed = db.create_note(0, 0).unwrap();
+        set_note(&db, &trashed.id, "C", "see [[Target]]");
+        db.delete_note(&trashed.id).unwrap();
+
+        assert_eq!(ids(&db.get_backlinks(&target.id).unwrap()), [by_title.id.as_str(), by_id.id.as_str()]);
+
+        let outgoing = db.get_outgoing_links(&by_title.id).unwrap();
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].note_id.as_deref(), Some(target.id.as_str()));
+    }
+
+    #[test]
+    fn renaming_rewrites_links_in_other_notes() {
+        let db = TempDatabase::new();
+        let target = db.create_note(0, 0).unwrap();
+        set_note(&db, &target.id, "Plans", "");
+        let source = db.create_note(0, 0).unwrap();
+        set_note(&db, &source.id, "Diary", "see [[Plans|the plans]] and [[Plans]]");
+
+        let rewritten = db.rename_note(&target.id, "Roadmap", true).unwrap();
+        assert_eq!(rewritten, [source.id.as_str()]);
+        assert_eq!(
+            db.get_note(&source.id).unwrap().unwrap().content,
+            "see [[Roadmap|the plans]] and [[Roadmap]]"
+        );
+        assert_eq!(ids(&db.get_backlinks(&target.id).unwrap()), [source.id.as_str()]);
+
+        // Without rewriting, the old links are left dangling
+        assert!(db.rename_note(&target.id, "Elsewhere", false).unwrap().is_empty());
+        assert!(db.get_backlinks(&target.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn renaming_to_an_unlinkable_title_links_by_id() {
+        let db = TempDatabase::new();
+        let target = db.create_note(0, 0).unwrap();
+        set_note(&db, &target.id, "Plans", "");
+        let source = db.create_note(0, 0).unwrap();
+        set_note(&db, &source.id, "Diary", "see [[Plans]]");
+
+        db.rename_note(&target.id, "a|b", true).unwrap();
+        assert_eq!(db.get_note(&source.id).unwrap().unwrap().content, format!("see [[{}]]", target.id));
+        assert_eq!(ids(&db.get_backlinks(&target.id).unwrap()), [source.id.as_str()]);
+    }
+}
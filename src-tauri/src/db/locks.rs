@@ -0,0 +1,189 @@
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
+
+use super::{links, Database, Note};
+use crate::crypto::{self, KdfParams, Sealed};
+
+fn lock_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_AUTH),
+        Some(message),
+    )
+}
+
+pub(super) fn is_locked(conn: &Connection, note_id: &str) -> SqlResult<bool> {
+    conn.query_row("SELECT is_locked FROM notes WHERE id = ?", [note_id], |row| row.get::<_, i32>(0))
+        .optional()
+        .map(|locked| locked == Some(1))
+}
+
+/// Error returned when something tries to change the content of a locked note.
+pub(super) fn locked_error() -> rusqlite::Error {
+    lock_error("note is locked".to_string())
+}
+
+/// Decrypts the stored content of a locked note. A lock copied from another
+/// note is opened with that note's id, which its ciphertext is bound to.
+fn decrypt_content(conn: &Connection, id: &str, passphrase: &str) -> SqlResult<String> {
+    let sealed: Option<(Sealed, Option<String>)> = conn
+        .query_row(
+            "SELECT m_cost, t_cost, p_cost, salt, nonce, ciphertext, sealed_for
+             FROM note_locks WHERE note_id = ?",
+            [id],
+            |row| {
+                let sealed = Sealed {
+                    kdf: KdfParams {
+                        m_cost: row.get(0)?,
+                        t_cost: row.get(1)?,
+                        p_cost: row.get(2)?,
+                    },
+                    salt: row.get(3)?,
+                    nonce: row.get(4)?,
+                    ciphertext: row.get(5)?,
+                };
+                Ok((sealed, row.get(6)?))
+            },
+        )
+        .optional()?;
+    let Some((sealed, sealed_for)) = sealed else {
+        return Err(lock_error("note is not locked".to_string()));
+    };
+
+    let associated = sealed_for.as_deref().unwrap_or(id);
+    let plaintext = crypto::open(passphrase, &sealed, associated.as_bytes()).map_err(lock_error)?;
+    String::from_utf8(plaintext).map_err(|_| lock_error("decrypted content is not valid text".to_string()))
+}
+
+impl Database {
+    /// Encrypts a note's content under `passphrase` and clears the plaintext,
+    /// including from revision history and the search index. The title stays
+    /// readable so the note can still be found.
+    ///
+    /// Secure delete is switched on first so SQLite overwrites the freed
+    /// plaintext rather than leaving it in unused pages of the file.
+    pub fn lock_note(&self, id: &str, passphrase: &str) -> SqlResult<Note> {
+        let now = Utc::now().to_rfc3339();
+        {
+            let mut conn = self.conn()?;
+            conn.pragma_update(None, "secure_delete", true)?;
+            let tx = conn.transaction()?;
+            // FTS5 otherwise keeps deleted terms in its index segments until they merge
+            tx.execute("INSERT INTO notes_fts (notes_fts, rank) VALUES ('secure-delete', 1)", [])?;
+
+            let content: Option<String> = tx
+                .query_row("SELECT content FROM notes WHERE id = ?", [id], |row| row.get(0))
+                .optional()?;
+            let Some(content) = content else {
+                return Err(lock_error(format!("note not found: {id}")));
+            };
+            if is_locked(&tx, id)? {
+                return Err(lock_error("note is already locked".to_string()));
+            }
+
+            let sealed = crypto::seal(passphrase, content.as_bytes(), id.as_bytes()).map_err(lock_error)?;
+
+            tx.execute(
+                "INSERT INTO note_locks (note_id, m_cost, t_cost, p_cost, salt, nonce, ciphertext, locked_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                rusqlite::params![
+                    id,
+                    sealed.kdf.m_cost,
+                    sealed.kdf.t_cost,
+                    sealed.kdf.p_cost,
+                    sealed.salt,
+                    sealed.nonce,
+                    sealed.ciphertext,
+                    now,
+                ],
+            )?;
+            tx.execute(
+                "UPDATE notes SET content = '', is_locked = 1, updated_at = ? WHERE id = ?",
+                rusqlite::params![now, id],
+            )?;
+            tx.execute("DELETE FROM note_revisions WHERE note_id = ?", [id])?;
+            links::sync_links(&tx, id, "")?;
+
+            tx.commit()?;
+        }
+
+        self.get_note(id)?.ok_or_else(|| lock_error(format!("note not found: {id}")))
+    }
+
+    /// Decrypts a locked note's content with `passphrase` for reading. Nothing
+    /// is written; the note stays locked.
+    pub fn view_locked_note(&self, id: &str, passphrase: &str) -> SqlResult<String> {
+        let conn = self.conn()?;
+        decrypt_content(&conn, id, passphrase)
+    }
+
+    /// Removes a note's lock for good: decrypts it with `passphrase` and
+    /// stores its content as plaintext again.
+    pub fn unlock_note(&self, id: &str, passphrase: &str) -> SqlResult<Note> {
+        let now = Utc::now().to_rfc3339();
+        {
+            let mut conn = self.conn()?;
+            let tx = conn.transaction()?;
+
+            let content = decrypt_content(&tx, id, passphrase)?;
+            tx.execute(
+                "UPDATE notes SET content = ?, is_locked = 0, updated_at = ? WHERE id = ?",
+                rusqlite::params![content, now, id],
+            )?;
+            tx.execute("DELETE FROM note_locks WHERE note_id = ?", [id])?;
+            links::sync_links(&tx, id, &content)?;
+
+            tx.commit()?;
+        }
+
+        self.get_note(id)?.ok_or_else(|| lock_error(format!("note not found: {id}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+
+    const SECRET: &str = "correct horse battery staple";
+
+    /// A note locked after two saves, long enough to span several pages.
+    fn locked_note(db: &TempDatabase) -> (String, String) {
+        let content = format!("{SECRET}\n").repeat(500);
+        let note = db.create_note(0, 0).unwrap();
+        db.update_note(&note.id, None, Some(SECRET), None, None, None, None, None, None, None).unwrap();
+        db.update_note(&note.id, None, Some(&content), None, None, None, None, None, None, None).unwrap();
+        db.lock_note(&note.id, "pass").unwrap();
+        (note.id, content)
+    }
+
+    #[test]
+    fn viewing_does_not_unlock() {
+        let db = TempDatabase::new();
+        let (id, content) = locked_note(&db);
+
+        assert_eq!(db.view_locked_note(&id, "pass").unwrap(), content);
+        assert!(db.view_locked_note(&id, "wrong").is_err());
+        let note = db.get_note(&id).unwrap().unwrap();
+        assert!(note.is_locked);
+        assert_eq!(note.content, "");
+    }
+
+    #[test]
+    fn removing_the_lock_restores_plaintext() {
+        let db = TempDatabase::new();
+        let (id, content) = locked_note(&db);
+
+        let note = db.unlock_note(&id, "pass").unwrap();
+        assert!(!note.is_locked);
+        assert_eq!(note.content, content);
+        assert!(db.view_locked_note(&id, "pass").is_err());
+    }
+
+    #[test]
+    fn locking_leaves_no_plaintext_in_the_file() {
+        let db = TempDatabase::new();
+        let (id, _) = locked_note(&db);
+        let bytes = std::fs::read(db.db_path()).unwrap();
+        assert!(!bytes.windows(SECRET.len()).any(|window| window == SECRET.as_bytes()));
+        assert!(db.search_notes("horse", 10).unwrap().iter().all(|result| result.id != id));
+    }
+}
@@ -0,0 +1,491 @@
+use chrono::{DateTime, Utc};
+use rusqlite::Result as SqlResult;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use uuid::Uuid;
+
+use super::{insert_note, tags, Database, Note, MIN_NOTE_HEIGHT, MIN_NOTE_WIDTH};
+
+/// Longest file name stem written on export, in characters.
+const MAX_SLUG_LEN: usize = 80;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedFile {
+    pub file: String,
+    pub reason: String,
+}
+
+/// A note `export_markdown` left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedNote {
+    pub id: String,
+    pub title: String,
+    pub reason: String,
+}
+
+/// Outcome of `export_markdown`: the files written and the notes left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarkdownExport {
+    pub exported: Vec<String>,
+    pub skipped: Vec<SkippedNote>,
+}
+
+/// Outcome of `import_markdown`, by file name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarkdownImport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+fn markdown_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_IOERR),
+        Some(message),
+    )
+}
+
+/// File name stem for a note title: lowercase letters and digits joined by
+/// single dashes, so it is safe on every platform.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= MAX_SLUG_LEN {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-').to_string();
+
+    if slug.is_empty() {
+        return "untitled".to_string();
+    }
+
+    // Device names Windows refuses to use as file names
+    let reserved = matches!(slug.as_str(), "con" | "prn" | "aux" | "nul")
+        || (slug.len() == 4
+            && (slug.starts_with("com") || slug.starts_with("lpt"))
+            && slug.ends_with(|c: char| c.is_ascii_digit()));
+    if reserved {
+        format!("{slug}-note")
+    } else {
+        slug
+    }
+}
+
+/// A YAML double-quoted scalar; JSON string escaping is valid YAML.
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+/// A note as Markdown with YAML front matter. The body is the note content
+/// verbatim, so todo lines keep their `- [ ]` / `- [x]` syntax.
+pub fn note_to_markdown(note: &Note) -> String {
+    format!(
+        "---\n\
+         id: {}\n\
+         title: {}\n\
+         mode: {}\n\
+         pos_x: {}\n\
+         pos_y: {}\n\
+         width: {}\n\
+         height: {}\n\
+         opacity: {}\n\
+         always_on_top: {}\n\
+         created_at: {}\n\
+         updated_at: {}\n\
+         ---\n\
+         {}",
+        yaml_string(&note.id),
+        yaml_string(&note.title),
+        yaml_string(&note.mode),
+        note.pos_x,
+        note.pos_y,
+        note.width,
+        note.height,
+        note.opacity,
+        note.always_on_top,
+        yaml_string(&note.created_at),
+        yaml_string(&note.updated_at),
+        note.content,
+    )
+}
+
+/// Parses one YAML scalar as written by `note_to_markdown` or by hand:
+/// double-quoted, single-quoted or plain.
+fn yaml_value(raw: &str) -> String {
+    let raw = raw.trim();
+    if raw.len() >= 2 && raw.starts_with('"') && raw.ends_with('"') {
+        return serde_json::from_str(raw).unwrap_or_else(|_| raw[1..raw.len() - 1].to_string());
+    }
+    if raw.len() >= 2 && raw.starts_with('\'') && raw.ends_with('\'') {
+        return raw[1..raw.len() - 1].replace("''", "'");
+    }
+    raw.to_string()
+}
+
+/// Splits leading `---` front matter off `text`. Only flat `key: value`
+/// pairs are understood; anything else in the block is ignored.
+fn split_front_matter(text: &str) -> (HashMap<String, String>, &str) {
+    let mut fields = HashMap::new();
+    let Some(rest) = text.strip_prefix("---\n") else {
+        return (fields, text);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end_matches('\n');
+        if line.trim_end() == "---" {
+            return (fields, &rest[offset..]);
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            if !key.starts_with(char::is_whitespace) {
+                fields.insert(key.trim().to_string(), yaml_value(value));
+            }
+        }
+    }
+
+    // Never closed, so it wasn't front matter
+    (HashMap::new(), text)
+}
+
+/// A note is a todo list when every non-blank line is a checkbox item, the
+/// same shape the todo view writes.
+fn looks_like_todo(content: &str) -> bool {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty()).peekable();
+    lines.peek().is_some() && lines.all(|line| line.starts_with("- [ ] ") || line.starts_with("- [x] "))
+}
+
+/// Builds a note from a Markdown or text file. Fields missing from the front
+/// matter, and timestamps that aren't RFC 3339, fall back to `defaults`;
+/// without a title the file name is used.
+/// Size and opacity are brought within what a note window allows.
+pub fn markdown_to_note(text: &str, file_stem: &str, defaults: &Note) -> Note {
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let (fields, body) = split_front_matter(&text);
+
+    let string = |key: &str| fields.get(key).filter(|v| !v.is_empty()).cloned();
+    // Stored timestamps are compared as strings, so bring them to UTC
+    let timestamp = |key: &str| {
+        fields
+            .get(key)
+            .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+            .map(|t| t.with_timezone(&Utc).to_rfc3339())
+    };
+    let int = |key: &str, default: i32| fields.get(key).and_then(|v| v.parse().ok()).unwrap_or(default);
+    let mode = match fields.get("mode").map(String::as_str) {
+        Some(mode @ ("text" | "todo")) => mode.to_string(),
+        _ if looks_like_todo(body) => "todo".to_string(),
+        _ => "text".to_string(),
+    };
+
+    Note {
+        id: string("id").unwrap_or_else(|| defaults.id.clone()),
+        title: fields.get("title").cloned().unwrap_or_else(|| file_stem.to_string()),
+        content: body.to_string(),
+        mode,
+        pos_x: int("pos_x", defaults.pos_x),
+        pos_y: int("pos_y", defaults.pos_y),
+        width: int("width", defaults.width).max(MIN_NOTE_WIDTH),
+        height: int("height", defaults.height).max(MIN_NOTE_HEIGHT),
+        opacity: fields
+            .get("opacity")
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|opacity| opacity.is_finite())
+            .unwrap_or(defaults.opacity)
+            .clamp(0.3, 1.0),
+        always_on_top: fields
+            .get("always_on_top")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.always_on_top),
+        created_at: timestamp("created_at").unwrap_or_else(|| defaults.created_at.clone()),
+        updated_at: timestamp("updated_at").unwrap_or_else(|| defaults.updated_at.clone()),
+        ..defaults.clone()
+    }
+}
+
+impl Database {
+    /// Writes every note outside the trash to `dir` as `<slugified title>.md`.
+    /// Notes with the same slug get `-2`, `-3`, ... suffixes in creation order,
+    /// so re-exporting into the same folder overwrites the same files.
+    /// Locked notes are skipped since only their ciphertext is stored; the
+    /// report lists them next to the files written.
+    pub fn export_markdown(&self, dir: &Path) -> SqlResult<MarkdownExport> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| markdown_error(format!("failed to create {}: {e}", dir.display())))?;
+
+        let mut used = HashSet::new();
+        let mut report = MarkdownExport::default();
+
+        for note in self.get_all_notes()? {
+            if note.is_locked {
+                report.skipped.push(SkippedNote {
+                    id: note.id,
+                    title: note.title,
+                    reason: "note is locked".to_string(),
+                });
+                continue;
+            }
+
+            let slug = slugify(&note.title);
+            let mut stem = slug.clone();
+            let mut n = 2;
+            while !used.insert(stem.clone()) {
+                stem = format!("{slug}-{n}");
+                n += 1;
+            }
+
+            let file = format!("{stem}.md");
+            let path = dir.join(&file);
+            std::fs::write(&path, note_to_markdown(&note))
+                .map_err(|e| markdown_error(format!("failed to write {}: {e}", path.display())))?;
+            report.exported.push(file);
+        }
+
+        Ok(report)
+    }
+
+    /// Creates or updates notes from the `.md` and `.txt` files in `dir`.
+    ///
+    /// A front matter `id` that matches an existing note updates that note;
+    /// otherwise a new note is created (keeping the id when it is a valid
+    /// UUID). New notes start closed. Files identical to their note, locked
+    /// notes and notes in the trash are skipped.
+    pub fn import_markdown(&self, dir: &Path) -> SqlResult<MarkdownImport> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| markdown_error(format!("failed to read {}: {e}", dir.display())))?;
+        let mut paths: Vec<_> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .is_some_and(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("txt"))
+            })
+            .collect();
+        paths.sort();
+
+        let settings = self.get_settings()?;
+        let mut report = MarkdownImport::default();
+
+        for path in paths {
+            let file = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let skip = |report: &mut MarkdownImport, reason: &str| {
+                report.skipped.push(SkippedFile { file: file.clone(), reason: reason.to_string() });
+            };
+
+            let Ok(text) = std::fs::read_to_string(&path) else {
+                skip(&mut report, "not a UTF-8 text file");
+                continue;
+            };
+
+            let now = Utc::now().to_rfc3339();
+            let defaults = Note {
+                id: Uuid::new_v4().to_string(),
+                title: String::new(),
+                content: String::new(),
+                mode: "text".to_string(),
+                pos_x: 100,
+                pos_y: 100,
+                width: 300,
+                height: 200,
+                opacity: settings.default_opacity,
+                is_open: false,
+                is_minimized: false,
+                always_on_top: true,
+                created_at: now.clone(),
+                updated_at: now,
+                deleted_at: None,
+                notebook_id: None,
+                is_locked: false,
+                is_template: false,
+            };
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let mut note = markdown_to_note(&text, &stem, &defaults);
+
+            let Some(existing) = self.get_note(&note.id)? else {
+                if Uuid::parse_str(&note.id).is_err() {
+                    note.id = defaults.id.clone();
+                }
+                let mut conn = self.conn()?;
+                let tx = conn.transaction()?;
+                insert_note(&tx, &note)?;
+                if settings.extract_hashtags {
+                    tags::sync_hashtags(&tx, &note.id, &note.content)?;
+                }
+                tx.commit()?;
+                report.created.push(file);
+                continue;
+            };
+
+            if existing.is_locked {
+                skip(&mut report, "note is locked");
+                continue;
+            }
+            if existing.deleted_at.is_some() {
+                skip(&mut report, "note is in the trash");
+                continue;
+            }
+
+            let title = (note.title != existing.title).then_some(note.title.as_str());
+            let content = (note.content != existing.content).then_some(note.content.as_str());
+            let mode = (note.mode != existing.mode).then_some(note.mode.as_str());
+            let moved = note.pos_x != existing.pos_x || note.pos_y != existing.pos_y;
+            let resized = note.width != existing.width || note.height != existing.height;
+            let opacity = (note.opacity != existing.opacity).then_some(note.opacity);
+            let on_top = (note.always_on_top != existing.always_on_top).then_some(note.always_on_top);
+
+            if title.is_none() && content.is_none() && mode.is_none() && !moved && !resized
+                && opacity.is_none() && on_top.is_none()
+            {
+                skip(&mut report, "unchanged");
+                continue;
+            }
+
+            self.update_note(
+                &note.id,
+                title,
+                content,
+                mode,
+                moved.then_some(note.pos_x),
+                moved.then_some(note.pos_y),
+                resized.then_some(note.width),
+                resized.then_some(note.height),
+                opacity,
+                on_top,
+            )?;
+            report.updated.push(file);
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    fn defaults() -> Note {
+        Note {
+            id: "default".to_string(),
+            title: String::new(),
+            content: String::new(),
+            mode: "text".to_string(),
+            pos_x: 0,
+            pos_y: 0,
+            width: 300,
+            height: 200,
+            opacity: 0.95,
+            is_open: false,
+            is_minimized: false,
+            always_on_top: true,
+            created_at: String::new(),
+            updated_at: String::new(),
+            deleted_at: None,
+            notebook_id: None,
+            is_locked: false,
+            is_template: false,
+        }
+    }
+
+    #[test]
+    fn front_matter_geometry_is_clamped() {
+        let note = markdown_to_note("---\nwidth: 10\nheight: -5\nopacity: 0\n---\nbody", "tiny", &defaults());
+        assert_eq!((note.width, note.height, note.opacity), (MIN_NOTE_WIDTH, MIN_NOTE_HEIGHT, 0.3));
+
+        let note = markdown_to_note("---\nopacity: 7\n---\n", "opaque", &defaults());
+        assert_eq!(note.opacity, 1.0);
+
+        let note = markdown_to_note("---\nopacity: NaN\nwidth: 640\n---\n", "nan", &defaults());
+        assert_eq!((note.width, note.opacity), (640, 0.95));
+    }
+
+    #[test]
+    fn export_reports_locked_notes() {
+        let db = TempDatabase::new();
+        let plain = db.create_note(0, 0).unwrap();
+        db.update_note(&plain.id, Some("Shopping"), Some("milk"), None, None, None, None, None, None, None).unwrap();
+        let locked = db.create_note(0, 0).unwrap();
+        db.update_note(&locked.id, Some("Secrets"), Some("hidden"), None, None, None, None, None, None, None).unwrap();
+        db.lock_note(&locked.id, "pass").unwrap();
+
+        let dir = db.data_dir.join("export");
+        let report = db.export_markdown(&dir).unwrap();
+        assert_eq!(report.exported, ["shopping.md"]);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!((report.skipped[0].id.as_str(), report.skipped[0].title.as_str()), (locked.id.as_str(), "Secrets"));
+        assert!(!dir.join("secrets.md").exists());
+    }
+
+    #[test]
+    fn front_matter_timestamps_must_be_rfc3339() {
+        let defaults = Note { created_at: "now".to_string(), updated_at: "now".to_string(), ..defaults() };
+        let note = markdown_to_note(
+            "---\ncreated_at: 2020-01-02T03:04:05+02:00\nupdated_at: last tuesday\n---\n",
+            "dated",
+            &defaults,
+        );
+        assert_eq!(note.created_at, "2020-01-02T01:04:05+00:00");
+        assert_eq!(note.updated_at, "now");
+    }
+
+    fn write(dir: &Path, name: &str, text: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(name), text).unwrap();
+    }
+
+    #[test]
+    fn import_creates_updates_and_skips() {
+        let db = TempDatabase::new();
+        let existing = db.create_note(0, 0).unwrap();
+        let locked = db.create_note(0, 0).unwrap();
+        db.lock_note(&locked.id, "pass").unwrap();
+        let trashed = db.create_note(0, 0).unwrap();
+        db.delete_note(&trashed.id).unwrap();
+        let kept_id = Uuid::new_v4().to_string();
+
+        let dir = db.data_dir.join("import");
+        write(&dir, "a-update.md", &format!("---\nid: {}\ntitle: Renamed\n---\nnew body", existing.id));
+        write(&dir, "b-locked.md", &format!("---\nid: {}\n---\nclobber", locked.id));
+        write(&dir, "c-trashed.md", &format!("---\nid: {}\n---\nclobber", trashed.id));
+        write(&dir, "d-kept-id.md", &format!("---\nid: {kept_id}\n---\nhello"));
+        write(&dir, "e-todo.txt", "- [ ] milk\n- [x] eggs\n");
+        write(&dir, "f-bad-id.md", "---\nid: not-a-uuid\ncreated_at: soon\n---\nplain");
+        write(&dir, "ignored.png", "not text");
+
+        let report = db.import_markdown(&dir).unwrap();
+        assert_eq!(report.created, ["d-kept-id.md", "e-todo.txt", "f-bad-id.md"]);
+        assert_eq!(report.updated, ["a-update.md"]);
+        let skipped: Vec<_> = report.skipped.iter().map(|s| (s.file.as_str(), s.reason.as_str())).collect();
+        assert_eq!(skipped, [("b-locked.md", "note is locked"), ("c-trashed.md", "note is in the trash")]);
+
+        let updated = db.get_note(&existing.id).unwrap().unwrap();
+        assert_eq!((updated.title.as_str(), updated.content.as_str()), ("Renamed", "new body"));
+
+        let kept = db.get_note(&kept_id).unwrap().unwrap();
+        assert_eq!(kept.content, "hello");
+        assert!(!kept.is_open);
+
+        let notes = db.get_all_notes().unwrap();
+        let todo = notes.iter().find(|n| n.title == "e-todo").unwrap();
+        assert_eq!(todo.mode, "todo");
+        assert!(!todo.is_open);
+        let bad_id = notes.iter().find(|n| n.title == "f-bad-id").unwrap();
+        assert!(Uuid::parse_str(&bad_id.id).is_ok());
+        assert!(DateTime::parse_from_rfc3339(&bad_id.created_at).is_ok());
+
+        // Importing the same files again changes nothing
+        let again = db.import_markdown(&dir).unwrap();
+        assert!(again.updated.is_empty());
+        assert!(again.skipped.iter().any(|s| s.file == "a-update.md" && s.reason == "unchanged"));
+    }
+}
@@ -0,0 +1,537 @@
+use rusqlite::{Connection, Result as SqlResult, Transaction};
+
+/// A single schema step. `version` is written to `PRAGMA user_version` in the
+/// same transaction as `up`, so a migration is either fully applied or not at all.
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> SqlResult<()>,
+}
+
+/// Ordered list of every schema migration. Append only — never edit or
+/// renumber an entry that has shipped.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline notes and settings tables",
+        up: baseline,
+    },
+    Migration {
+        version: 2,
+        description: "note revision history",
+        up: note_revisions,
+    },
+    Migration {
+        version: 3,
+        description: "full-text search index",
+        up: notes_fts,
+    },
+    Migration {
+        version: 4,
+        description: "soft delete for notes",
+        up: notes_deleted_at,
+    },
+    Migration {
+        version: 5,
+        description: "tags",
+        up: tags,
+    },
+    Migration {
+        version: 6,
+        description: "notebooks",
+        up: notebooks,
+    },
+    Migration {
+        version: 7,
+        description: "wiki-style links between notes",
+        up: note_links,
+    },
+    Migration {
+        version: 8,
+        description: "note attachments",
+        up: attachments,
+    },
+    Migration {
+        version: 9,
+        description: "per-note encryption",
+        up: note_locks,
+    },
+    Migration {
+        version: 10,
+        description: "folder sync state",
+        up: sync_state,
+    },
+    Migration {
+        version: 11,
+        description: "note reminders",
+        up: reminders,
+    },
+    Migration {
+        version: 12,
+        description: "recurring todo items",
+        up: recurring_todos,
+    },
+    Migration {
+        version: 13,
+        description: "note templates",
+        up: note_templates,
+    },
+    Migration {
+        version: 14,
+        description: "workspaces",
+        up: workspaces,
+    },
+    Migration {
+        version: 15,
+        description: "lock copies bound to another note id",
+        up: note_locks_sealed_for,
+    },
+];
+
+pub fn current_version(conn: &Connection) -> SqlResult<i32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Brings the database up to the latest schema version.
+pub fn run(conn: &mut Connection) -> SqlResult<()> {
+    apply(conn, MIGRATIONS)
+}
+
+/// Applies every migration newer than the database's `user_version`, in order,
+/// each inside its own transaction. Stops at the first failure.
+pub fn apply(conn: &mut Connection, migrations: &[Migration]) -> SqlResult<()> {
+    let current = current_version(conn)?;
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+
+    if current > latest {
+        return Err(failure(
+            rusqlite::ffi::SQLITE_ERROR,
+            format!("database schema version {current} is newer than this build supports ({latest})"),
+        ));
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        let result = (migration.up)(&tx)
+            .and_then(|()| tx.pragma_update(None, "user_version", migration.version));
+
+        match result {
+            Ok(()) => tx.commit().map_err(|e| migration_error(migration, e))?,
+            // Dropping `tx` without committing rolls the migration back
+            Err(e) => return Err(migration_error(migration, e)),
+        }
+    }
+
+    Ok(())
+}
+
+fn migration_error(migration: &Migration, err: rusqlite::Error) -> rusqlite::Error {
+    let code = match &err {
+        rusqlite::Error::SqliteFailure(e, _) => e.extended_code,
+        _ => rusqlite::ffi::SQLITE_ERROR,
+    };
+    failure(
+        code,
+        format!("migration {} ({}) failed: {err}", migration.version, migration.description),
+    )
+}
+
+fn failure(code: i32, message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(code), Some(message))
+}
+
+pub fn has_column(conn: &Connection, table: &str, column: &str) -> SqlResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<SqlResult<Vec<_>>>()?;
+    Ok(names.iter().any(|name| name == column))
+}
+
+// Databases created before versioned migrations have user_version 0 but may
+// already contain `notes`, with or without the `title`/`mode` columns that used
+// to be added by unchecked ALTERs. The baseline reconciles all of those shapes.
+fn baseline(tx: &Transaction) -> SqlResult<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS notes (
+            id TEXT PRIMARY KEY,
+            title TEXT NOT NULL DEFAULT '',
+            content TEXT NOT NULL DEFAULT '',
+            mode TEXT NOT NULL DEFAULT 'text',
+            pos_x INTEGER NOT NULL,
+            pos_y INTEGER NOT NULL,
+            width INTEGER NOT NULL DEFAULT 300,
+            height INTEGER NOT NULL DEFAULT 200,
+            opacity REAL NOT NULL DEFAULT 0.95,
+            is_open INTEGER NOT NULL DEFAULT 1,
+            is_minimized INTEGER NOT NULL DEFAULT 0,
+            always_on_top INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    if !has_column(tx, "notes", "title")? {
+        tx.execute("ALTER TABLE notes ADD COLUMN title TEXT NOT NULL DEFAULT ''", [])?;
+    }
+    if !has_column(tx, "notes", "mode")? {
+        tx.execute("ALTER TABLE notes ADD COLUMN mode TEXT NOT NULL DEFAULT 'text'", [])?;
+    }
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn note_revisions(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE note_revisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            title TEXT NOT NULL,
+            content TEXT NOT NULL,
+            source TEXT NOT NULL DEFAULT 'edit',
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX idx_note_revisions_note ON note_revisions(note_id, id);",
+    )
+}
+
+// A standalone FTS table keyed by note id rather than an external-content
+// table: `notes` has a TEXT primary key, so its implicit rowid is not stable
+// across VACUUM.
+fn notes_fts(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE notes_fts USING fts5(
+            note_id UNINDEXED,
+            title,
+            content,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        INSERT INTO notes_fts (note_id, title, content)
+            SELECT id, title, content FROM notes;
+
+        CREATE TRIGGER notes_fts_insert AFTER INSERT ON notes BEGIN
+            INSERT INTO notes_fts (note_id, title, content)
+                VALUES (new.id, new.title, new.content);
+        END;
+
+        CREATE TRIGGER notes_fts_update AFTER UPDATE OF title, content ON notes BEGIN
+            DELETE FROM notes_fts WHERE note_id = old.id;
+            INSERT INTO notes_fts (note_id, title, content)
+                VALUES (new.id, new.title, new.content);
+        END;
+
+        CREATE TRIGGER notes_fts_delete AFTER DELETE ON notes BEGIN
+            DELETE FROM notes_fts WHERE note_id = old.id;
+        END;",
+    )
+}
+
+fn notes_deleted_at(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "ALTER TABLE notes ADD COLUMN deleted_at TEXT;
+        CREATE INDEX idx_notes_deleted_at ON notes(deleted_at);",
+    )
+}
+
+fn tags(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE TABLE note_tags (
+            note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            source TEXT NOT NULL DEFAULT 'manual',
+            PRIMARY KEY (note_id, tag_id)
+        );
+        CREATE INDEX idx_note_tags_tag ON note_tags(tag_id);",
+    )
+}
+
+fn notebooks(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE notebooks (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            parent_id TEXT REFERENCES notebooks(id) ON DELETE SET NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+        CREATE INDEX idx_notebooks_parent ON notebooks(parent_id);
+
+        ALTER TABLE notes ADD COLUMN notebook_id TEXT REFERENCES notebooks(id) ON DELETE SET NULL;
+        CREATE INDEX idx_notes_notebook ON notes(notebook_id);",
+    )
+}
+
+fn note_links(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE note_links (
+            source_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            target TEXT NOT NULL COLLATE NOCASE,
+            PRIMARY KEY (source_id, target)
+        );
+        CREATE INDEX idx_note_links_target ON note_links(target);",
+    )?;
+
+    // Index links already present in existing notes
+    let mut stmt = tx.prepare("SELECT id, content FROM notes")?;
+    let notes = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<SqlResult<Vec<_>>>()?;
+    for (id, content) in notes {
+        super::links::sync_links(tx, &id, &content)?;
+    }
+
+    Ok(())
+}
+
+fn attachments(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE attachments (
+            id TEXT PRIMARY KEY,
+            note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            file_name TEXT NOT NULL,
+            mime_type TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            hash TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX idx_attachments_note ON attachments(note_id);
+        CREATE INDEX idx_attachments_hash ON attachments(hash);",
+    )
+}
+
+fn note_locks(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "ALTER TABLE notes ADD COLUMN is_locked INTEGER NOT NULL DEFAULT 0;
+
+        CREATE TABLE note_locks (
+            note_id TEXT PRIMARY KEY REFERENCES notes(id) ON DELETE CASCADE,
+            m_cost INTEGER NOT NULL,
+            t_cost INTEGER NOT NULL,
+            p_cost INTEGER NOT NULL,
+            salt BLOB NOT NULL,
+            nonce BLOB NOT NULL,
+            ciphertext BLOB NOT NULL,
+            locked_at TEXT NOT NULL
+        );",
+    )
+}
+
+// No foreign key: the row has to outlive a purged note so the deletion can
+// still be reconciled with other devices.
+fn sync_state(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE sync_state (
+            note_id TEXT PRIMARY KEY,
+            synced_updated_at TEXT NOT NULL,
+            superseded TEXT NOT NULL DEFAULT ''
+        );",
+    )
+}
+
+fn reminders(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE reminders (
+            id TEXT PRIMARY KEY,
+            note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            fire_at TEXT NOT NULL,
+            recurrence TEXT,
+            fired_at TEXT,
+            snoozed_until TEXT,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE INDEX idx_reminders_note ON reminders(note_id);",
+    )
+}
+
+fn recurring_todos(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE recurring_todos (
+            id TEXT PRIMARY KEY,
+            note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            text TEXT NOT NULL,
+            recurrence TEXT NOT NULL,
+            occurrence TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            UNIQUE (note_id, text)
+        );
+
+        CREATE TABLE todo_completions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            recurring_todo_id TEXT NOT NULL REFERENCES recurring_todos(id) ON DELETE CASCADE,
+            occurrence TEXT NOT NULL,
+            completed INTEGER NOT NULL,
+            recorded_at TEXT NOT NULL,
+            UNIQUE (recurring_todo_id, occurrence)
+        );",
+    )
+}
+
+fn note_templates(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch("ALTER TABLE notes ADD COLUMN is_template INTEGER NOT NULL DEFAULT 0;")
+}
+
+fn workspaces(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch(
+        "CREATE TABLE workspaces (
+            name TEXT PRIMARY KEY,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+
+        CREATE TABLE workspace_notes (
+            workspace TEXT NOT NULL REFERENCES workspaces(name) ON DELETE CASCADE,
+            note_id TEXT NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+            pos_x INTEGER NOT NULL,
+            pos_y INTEGER NOT NULL,
+            width INTEGER NOT NULL,
+            height INTEGER NOT NULL,
+            opacity REAL NOT NULL,
+            PRIMARY KEY (workspace, note_id)
+        );",
+    )
+}
+
+// Conflict copies of a locked note carry its ciphertext, which can't be
+// re-sealed without the passphrase and stays bound to the original id.
+fn note_locks_sealed_for(tx: &Transaction) -> SqlResult<()> {
+    tx.execute_batch("ALTER TABLE note_locks ADD COLUMN sealed_for TEXT;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(conn: &Connection) -> Vec<(String, String)> {
+        let mut stmt = conn
+            .prepare("SELECT name, coalesce(sql, '') FROM sqlite_master ORDER BY name")
+            .unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.collect::<SqlResult<Vec<_>>>().unwrap()
+    }
+
+    fn migrated_to(version: i32) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let index = MIGRATIONS.iter().position(|m| m.version == version).map_or(0, |i| i + 1);
+        apply(&mut conn, &MIGRATIONS[..index]).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), version);
+        conn
+    }
+
+    #[test]
+    fn each_migration_applies_from_every_earlier_version() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            let earlier = std::iter::once(0).chain(MIGRATIONS[..index].iter().map(|m| m.version));
+            for start in earlier {
+                let mut conn = migrated_to(start);
+                if start > 0 {
+                    conn.execute(
+                        "INSERT INTO notes (id, content, pos_x, pos_y, created_at, updated_at)
+                         VALUES ('n1', 'kept', 1, 2, 'now', 'now')",
+                        [],
+                    )
+                    .unwrap();
+                }
+
+                apply(&mut conn, &MIGRATIONS[..=index])
+                    .unwrap_or_else(|e| panic!("{} -> {}: {e}", start, migration.version));
+                assert_eq!(current_version(&conn).unwrap(), migration.version);
+                if start > 0 {
+                    let content: String = conn
+                        .query_row("SELECT content FROM notes WHERE id = 'n1'", [], |row| row.get(0))
+                        .unwrap();
+                    assert_eq!(content, "kept");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stepwise_and_fresh_databases_end_up_identical() {
+        let mut fresh = Connection::open_in_memory().unwrap();
+        run(&mut fresh).unwrap();
+
+        let mut stepwise = Connection::open_in_memory().unwrap();
+        for index in 0..MIGRATIONS.len() {
+            apply(&mut stepwise, &MIGRATIONS[..=index]).unwrap();
+        }
+        assert_eq!(schema(&stepwise), schema(&fresh));
+    }
+
+    #[test]
+    fn baseline_adopts_legacy_notes_table() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE notes (
+                id TEXT PRIMARY KEY,
+                content TEXT NOT NULL DEFAULT '',
+                pos_x INTEGER NOT NULL,
+                pos_y INTEGER NOT NULL,
+                width INTEGER NOT NULL DEFAULT 300,
+                height INTEGER NOT NULL DEFAULT 200,
+                opacity REAL NOT NULL DEFAULT 0.95,
+                is_open INTEGER NOT NULL DEFAULT 1,
+                is_minimized INTEGER NOT NULL DEFAULT 0,
+                always_on_top INTEGER NOT NULL DEFAULT 1,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            INSERT INTO notes (id, content, pos_x, pos_y, created_at, updated_at)
+                VALUES ('old', 'legacy', 0, 0, 'now', 'now');",
+        )
+        .unwrap();
+
+        run(&mut conn).unwrap();
+        assert!(has_column(&conn, "notes", "title").unwrap());
+        assert!(has_column(&conn, "notes", "mode").unwrap());
+        let hits: i64 = conn
+            .query_row("SELECT count(*) FROM notes_fts WHERE notes_fts MATCH 'legacy'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(hits, 1);
+    }
+
+    #[test]
+    fn refuses_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let newer = MIGRATIONS.last().unwrap().version + 1;
+        conn.pragma_update(None, "user_version", newer).unwrap();
+
+        let err = run(&mut conn).unwrap_err();
+        assert!(err.to_string().contains("newer"), "{err}");
+        assert_eq!(current_version(&conn).unwrap(), newer);
+    }
+
+    fn broken(tx: &Transaction) -> SqlResult<()> {
+        tx.execute_batch("CREATE TABLE half_done (id INTEGER); INSERT INTO missing_table VALUES (1);")
+    }
+
+    #[test]
+    fn failing_migration_rolls_back() {
+        let migrations = [
+            Migration { version: 1, description: "baseline", up: baseline },
+            Migration { version: 2, description: "broken", up: broken },
+        ];
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        let err = apply(&mut conn, &migrations).unwrap_err();
+        assert!(err.to_string().contains("migration 2 (broken) failed"), "{err}");
+        assert_eq!(current_version(&conn).unwrap(), 1);
+        assert!(!schema(&conn).iter().any(|(name, _)| name == "half_done"));
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{row_to_note, Database, Note, NOTE_COLUMNS};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notebook {
+    pub id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    /// Number of non-trashed notes directly in this notebook
+    pub note_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Ids of `notebook_id` and every notebook nested below it.
+const SUBTREE_CTE: &str = "WITH RECURSIVE subtree(id) AS (
+        SELECT ?1
+        UNION
+        SELECT nb.id FROM notebooks nb JOIN subtree s ON nb.parent_id = s.id
+    )";
+
+fn notebook_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT),
+        Some(message),
+    )
+}
+
+fn notebook_exists(conn: &Connection, id: &str) -> SqlResult<bool> {
+    conn.query_row("SELECT 1 FROM notebooks WHERE id = ?", [id], |_| Ok(()))
+        .optional()
+        .map(|found| found.is_some())
+}
+
+fn validate_name(name: &str) -> SqlResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(notebook_error("notebook name cannot be empty".to_string()));
+    }
+    Ok(name.to_string())
+}
+
+impl Database {
+    /// Every notebook as a flat list ordered by name; nesting is expressed
+    /// through `parent_id`.
+    pub fn list_notebooks(&self) -> SqlResult<Vec<Notebook>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT nb.id, nb.name, nb.parent_id,
+                    (SELECT COUNT(*) FROM notes n
+                     WHERE n.notebook_id = nb.id AND n.deleted_at IS NULL),
+                    nb.created_at, nb.updated_at
+             FROM notebooks nb
+             ORDER BY nb.name COLLATE NOCASE",
+        )?;
+        let notebooks = stmt
+            .query_map([], |row| {
+                Ok(Notebook {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    parent_id: row.get(2)?,
+                    note_count: row.get(3)?,
+                    created_at: row.get(4)?,
+                    updated_at: row.get(5)?,
+                })
+            })?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(notebooks)
+    }
+
+    pub fn create_notebook(&self, name: &str, parent_id: Option<&str>) -> SqlResult<Notebook> {
+        let name = validate_name(name)?;
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().to_rfc3339();
+
+        let conn = self.conn()?;
+        if let Some(parent_id) = parent_id {
+            if !notebook_exists(&conn, parent_id)? {
+                return Err(notebook_error(format!("notebook not found: {parent_id}")));
+            }
+        }
+        conn.execute(
+            "INSERT INTO notebooks (id, name, parent_id, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?)",
+            rusqlite::params![id, name, parent_id, now, now],
+        )?;
+
+        Ok(Notebook {
+            id,
+            name,
+            parent_id: parent_id.map(str::to_string),
+            note_count: 0,
+            created_at: now.clone(),
+            updated_at: now,
+        })
+    }
+
+    pub fn rename_notebook(&self, id: &str, name: &str) -> SqlResult<()> {
+        let name = validate_name(name)?;
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE notebooks SET name = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![name, now, id],
+        )?;
+        Ok(())
+    }
+
+    /// Re-parents a notebook. `None` moves it to the top level. Moving a
+    /// notebook underneath itself or one of its descendants is rejected.
+    pub fn move_notebook(&self, id: &str, parent_id: Option<&str>) -> SqlResult<()> {
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn()?;
+
+        if let Some(parent_id) = parent_id {
+            if !notebook_exists(&conn, parent_id)? {
+                return Err(notebook_error(format!("notebook not found: {parent_id}")));
+            }
+            let creates_cycle: bool = conn.query_row(
+                &format!("{SUBTREE_CTE} SELECT EXISTS (SELECT 1 FROM subtree WHERE id = ?2)"),
+                rusqlite::params![id, parent_id],
+                |row| row.get(0),
+            )?;
+            if creates_cycle {
+                return Err(notebook_error(
+                    "cannot move a notebook into itself or one of its children".to_string(),
+                ));
+            }
+        }
+
+        conn.execute(
+            "UPDATE notebooks SET parent_id = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![parent_id, now, id],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes a notebook. Its notes and child notebooks move up to the
+    /// deleted notebook's parent rather than being lost.
+    pub fn delete_notebook(&self, id: &str) -> SqlResult<()> {
+        let now = Utc::now().to_rfc3339();
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let parent_id: Option<Option<String>> = tx
+            .query_row("SELECT parent_id FROM notebooks WHERE id = ?", [id], |row| row.get(0))
+            .optional()?;
+        let Some(parent_id) = parent_id else {
+            return Ok(());
+        };
+
+        tx.execute(
+            "UPDATE notes SET notebook_id = ?1, updated_at = ?2 WHERE notebook_id = ?3",
+            rusqlite::params![parent_id, now, id],
+        )?;
+        tx.execute(
+            "UPDATE notebooks SET parent_id = ?1, updated_at = ?2 WHERE parent_id = ?3",
+            rusqlite::params![parent_id, now, id],
+        )?;
+        tx.execute("DELETE FROM notebooks WHERE id = ?", [id])?;
+
+        tx.commit()
+    }
+
+    /// Files a note under a notebook, or removes it from any notebook when
+    /// `notebook_id` is `None`.
+    pub fn move_note_to_notebook(&self, note_id: &str, notebook_id: Option<&str>) -> SqlResult<()> {
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn()?;
+        if let Some(notebook_id) = notebook_id {
+            if !notebook_exists(&conn, notebook_id)? {
+                return Err(notebook_error(format!("notebook not found: {notebook_id}")));
+            }
+        }
+        conn.execute(
+            "UPDATE notes SET notebook_id = ?, updated_at = ? WHERE id = ?",
+            rusqlite::params![notebook_id, now, note_id],
+        )?;
+        Ok(())
+    }
+
+    /// Non-trashed notes in a notebook, optionally including all notebooks
+    /// nested below it.
+    pub fn get_notebook_notes(&self, notebook_id: &str, include_nested: bool) -> SqlResult<Vec<Note>> {
+        let conn = self.conn()?;
+        let sql = if include_nested {
+            format!(
+                "{SUBTREE_CTE} SELECT {NOTE_COLUMNS} FROM notes
+                 WHERE notebook_id IN (SELECT id FROM subtree) AND deleted_at IS NULL
+                 ORDER BY created_at"
+            )
+        } else {
+            format!(
+                "SELECT {NOTE_COLUMNS} FROM notes
+                 WHERE notebook_id = ?1 AND deleted_at IS NULL
+                 ORDER BY created_at"
+            )
+        };
+        let mut stmt = conn.prepare(&sql)?;
+        let notes = stmt
+            .query_map([notebook_id], row_to_note)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(notes)
+    }
+}
@@ -0,0 +1,402 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Local, NaiveDate, Utc, Weekday};
+use rusqlite::{Connection, Result as SqlResult, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::todos::{editable_todo_content, index_error, is_blank_list, parse_todos, save_todos, todo_error};
+use super::{locks, Database, Note, TodoItem};
+
+/// Schedule of a recurring todo item. Serialized as "daily", "weekdays" or
+/// "weekly:<day>", e.g. "weekly:mon".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TodoRecurrence {
+    Daily,
+    /// Monday to Friday
+    Weekdays,
+    Weekly(Weekday),
+}
+
+impl TryFrom<String> for TodoRecurrence {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "daily" => Ok(TodoRecurrence::Daily),
+            "weekdays" => Ok(TodoRecurrence::Weekdays),
+            _ => value
+                .strip_prefix("weekly:")
+                .and_then(|day| day.parse::<Weekday>().ok())
+                .map(TodoRecurrence::Weekly)
+                .ok_or_else(|| format!("unknown recurrence: {value}")),
+        }
+    }
+}
+
+impl From<TodoRecurrence> for String {
+    fn from(value: TodoRecurrence) -> Self {
+        match value {
+            TodoRecurrence::Daily => "daily".to_string(),
+            TodoRecurrence::Weekdays => "weekdays".to_string(),
+            TodoRecurrence::Weekly(day) => format!("weekly:{}", day.to_string().to_lowercase()),
+        }
+    }
+}
+
+impl TodoRecurrence {
+    /// First day of the period that `today` falls in.
+    fn occurrence(self, today: NaiveDate) -> NaiveDate {
+        let back = |days: u32| today - chrono::Duration::days(days as i64);
+        match self {
+            TodoRecurrence::Daily => today,
+            // A weekend still belongs to Friday's period
+            TodoRecurrence::Weekdays => match today.weekday() {
+                Weekday::Sat => back(1),
+                Weekday::Sun => back(2),
+                _ => today,
+            },
+            TodoRecurrence::Weekly(day) => {
+                let since = (7 + today.weekday().num_days_from_monday() - day.num_days_from_monday()) % 7;
+                back(since)
+            }
+        }
+    }
+
+    /// First day of the period after the one starting on `occurrence`.
+    fn following(self, occurrence: NaiveDate) -> NaiveDate {
+        let mut day = occurrence;
+        loop {
+            day = day.succ_opt().unwrap_or(NaiveDate::MAX);
+            let next = self.occurrence(day);
+            if next != occurrence || day == NaiveDate::MAX {
+                return next;
+            }
+        }
+    }
+}
+
+/// A line of a todo-mode note that comes back unchecked every period. The
+/// line is matched by its text, which follows edits made to the line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringTodo {
+    pub id: String,
+    pub note_id: String,
+    pub text: String,
+    pub recurrence: TodoRecurrence,
+    /// First day (local date) of the period being tracked
+    pub occurrence: String,
+    pub created_at: String,
+}
+
+/// Whether a recurring item was done in one of its periods.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TodoCompletion {
+    pub occurrence: String,
+    pub completed: bool,
+    pub recorded_at: String,
+}
+
+const RECURRING_COLUMNS: &str = "id, note_id, text, recurrence, occurrence, created_at";
+
+fn row_to_recurring(row: &rusqlite::Row) -> SqlResult<RecurringTodo> {
+    let recurrence: String = row.get(3)?;
+    Ok(RecurringTodo {
+        id: row.get(0)?,
+        note_id: row.get(1)?,
+        text: row.get(2)?,
+        recurrence: TodoRecurrence::try_from(recurrence).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, e.into())
+        })?,
+        occurrence: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// Moves recurring items of note `note_id` along when its content is about
+/// to become `content`: a recurring line whose text is gone, at a position
+/// where a line with new text appeared, was edited and keeps recurring under
+/// the new text. Lines that were only added, removed or moved keep their text.
+pub(super) fn follow_todo_edits(conn: &Connection, note_id: &str, content: &str) -> SqlResult<()> {
+    let recurring: i64 = conn.query_row(
+        "SELECT count(*) FROM recurring_todos WHERE note_id = ?",
+        [note_id],
+        |row| row.get(0),
+    )?;
+    if recurring == 0 {
+        return Ok(());
+    }
+
+    let current: String = conn.query_row("SELECT content FROM notes WHERE id = ?", [note_id], |row| row.get(0))?;
+    let before = parse_todos(&current);
+    let after = parse_todos(content);
+    let has_text = |items: &[TodoItem], text: &str| items.iter().any(|item| item.text == text);
+
+    for (old, new) in before.iter().zip(&after) {
+        if old.text != new.text && !has_text(&after, &old.text) && !has_text(&before, &new.text) {
+            conn.execute(
+                "UPDATE OR IGNORE recurring_todos SET text = ? WHERE note_id = ? AND text = ?",
+                rusqlite::params![new.text, note_id, old.text],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+impl Database {
+    /// Makes the item at `index` of a todo-mode note recur on `recurrence`,
+    /// or stop recurring when `None`.
+    pub fn set_todo_recurrence(
+        &self,
+        note_id: &str,
+        index: usize,
+        recurrence: Option<TodoRecurrence>,
+    ) -> SqlResult<Option<RecurringTodo>> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        let content = editable_todo_content(&tx, note_id)?;
+        let item = parse_todos(&content).into_iter().nth(index).ok_or_else(|| index_error(index))?;
+
+        let Some(recurrence) = recurrence else {
+            tx.execute(
+                "DELETE FROM recurring_todos WHERE note_id = ? AND text = ?",
+                rusqlite::params![note_id, item.text],
+            )?;
+            tx.commit()?;
+            return Ok(None);
+        };
+        if item.text.trim().is_empty() {
+            return Err(todo_error(
+                rusqlite::ffi::SQLITE_CONSTRAINT,
+                "an empty todo item can't recur".to_string(),
+            ));
+        }
+
+        tx.execute(
+            &format!(
+                "INSERT INTO recurring_todos ({RECURRING_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?)
+                 ON CONFLICT (note_id, text) DO UPDATE SET
+                     recurrence = excluded.recurrence, occurrence = excluded.occurrence"
+            ),
+            rusqlite::params![
+                Uuid::new_v4().to_string(),
+                note_id,
+                item.text,
+                String::from(recurrence),
+                recurrence.occurrence(today()).to_string(),
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        let recurring = tx.query_row(
+            &format!("SELECT {RECURRING_COLUMNS} FROM recurring_todos WHERE note_id = ? AND text = ?"),
+            rusqlite::params![note_id, item.text],
+            row_to_recurring,
+        )?;
+        tx.commit()?;
+        Ok(Some(recurring))
+    }
+
+    pub fn list_recurring_todos(&self, note_id: &str) -> SqlResult<Vec<RecurringTodo>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {RECURRING_COLUMNS} FROM recurring_todos WHERE note_id = ? ORDER BY created_at"
+        ))?;
+        let todos = stmt.query_map([note_id], row_to_recurring)?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(todos)
+    }
+
+    /// Past periods of a recurring item, newest first.
+    pub fn todo_history(&self, recurring_todo_id: &str) -> SqlResult<Vec<TodoCompletion>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT occurrence, completed, recorded_at FROM todo_completions
+             WHERE recurring_todo_id = ? ORDER BY occurrence DESC",
+        )?;
+        let history = stmt
+            .query_map([recurring_todo_id], |row| {
+                Ok(TodoCompletion {
+                    occurrence: row.get(0)?,
+                    completed: row.get(1)?,
+                    recorded_at: row.get(2)?,
+                })
+            })?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(history)
+    }
+
+    /// Starts a new period for every recurring item whose period has rolled
+    /// over: records whether it was done, and periods that passed while the
+    /// app wasn't running as not done, then unchecks it, or adds it back at
+    /// the end if it was deleted. Notes that are locked, in the trash or
+    /// no longer todo lists are left alone until that changes. Returns the
+    /// notes that were changed.
+    pub fn roll_over_recurring_todos(&self) -> SqlResult<Vec<Note>> {
+        let now = Utc::now().to_rfc3339();
+        let today = today();
+        let extract_hashtags = self.get_settings()?.extract_hashtags;
+
+        let mut changed = Vec::new();
+        {
+            let mut conn = self.conn()?;
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+            let mut due: BTreeMap<String, Vec<RecurringTodo>> = BTreeMap::new();
+            {
+                let mut stmt = tx.prepare(&format!(
+                    "SELECT {RECURRING_COLUMNS} FROM recurring_todos
+                     WHERE note_id IN (SELECT id FROM notes WHERE deleted_at IS NULL AND mode = 'todo')
+                     ORDER BY created_at"
+                ))?;
+                for todo in stmt.query_map([], row_to_recurring)? {
+                    let todo = todo?;
+                    if todo.recurrence.occurrence(today).to_string() != todo.occurrence {
+                        due.entry(todo.note_id.clone()).or_default().push(todo);
+                    }
+                }
+            }
+
+            for (note_id, todos) in due {
+                if locks::is_locked(&tx, &note_id)? {
+                    continue;
+                }
+                let content: String = tx.query_row("SELECT content FROM notes WHERE id = ?", [&note_id], |row| row.get(0))?;
+                let mut items = parse_todos(&content);
+
+                for todo in todos {
+                    let completed = match items.iter_mut().find(|item| item.text == todo.text) {
+                        Some(item) => std::mem::replace(&mut item.checked, false),
+                        None => {
+                            if is_blank_list(&items) {
+                                items.clear();
+                            }
+                            items.push(TodoItem { checked: false, text: todo.text.clone() });
+                            false
+                        }
+                    };
+                    tx.execute(
+                        "INSERT OR REPLACE INTO todo_completions (recurring_todo_id, occurrence, completed, recorded_at)
+                         VALUES (?, ?, ?, ?)",
+                        rusqlite::params![todo.id, todo.occurrence, completed, now],
+                    )?;
+
+                    let current = todo.recurrence.occurrence(today);
+                    if let Ok(tracked) = NaiveDate::parse_from_str(&todo.occurrence, "%Y-%m-%d") {
+                        let mut skipped = todo.recurrence.following(tracked);
+                        while skipped < current {
+                            tx.execute(
+                                "INSERT OR IGNORE INTO todo_completions (recurring_todo_id, occurrence, completed, recorded_at)
+                                 VALUES (?, ?, 0, ?)",
+                                rusqlite::params![todo.id, skipped.to_string(), now],
+                            )?;
+                            skipped = todo.recurrence.following(skipped);
+                        }
+                    }
+
+                    tx.execute(
+                        "UPDATE recurring_todos SET occurrence = ? WHERE id = ?",
+                        rusqlite::params![current.to_string(), todo.id],
+                    )?;
+                }
+
+                if save_todos(&tx, &note_id, &content, &items, &now, extract_hashtags, "rollover")? {
+                    changed.push(note_id);
+                }
+            }
+
+            tx.commit()?;
+        }
+
+        changed
+            .iter()
+            .filter_map(|id| self.get_note(id).transpose())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        // 2026-01-05 is a Monday
+        NaiveDate::from_ymd_opt(2026, 1, day).unwrap()
+    }
+
+    fn todo_note(db: &TempDatabase, content: &str) -> String {
+        let note = db.create_note(0, 0).unwrap();
+        db.update_note(&note.id, None, Some(content), Some("todo"), None, None, None, None, None, None)
+            .unwrap();
+        note.id
+    }
+
+    fn set_content(db: &TempDatabase, id: &str, content: &str) {
+        db.update_note(id, None, Some(content), None, None, None, None, None, None, None).unwrap();
+    }
+
+    #[test]
+    fn weekend_belongs_to_fridays_period() {
+        assert_eq!(TodoRecurrence::Weekdays.occurrence(date(9)), date(9));
+        assert_eq!(TodoRecurrence::Weekdays.occurrence(date(10)), date(9));
+        assert_eq!(TodoRecurrence::Weekdays.occurrence(date(11)), date(9));
+        assert_eq!(TodoRecurrence::Weekdays.occurrence(date(12)), date(12));
+        assert_eq!(TodoRecurrence::Weekdays.following(date(9)), date(12));
+    }
+
+    #[test]
+    fn weekly_period_starts_on_its_day() {
+        let wednesday = TodoRecurrence::Weekly(Weekday::Wed);
+        assert_eq!(wednesday.occurrence(date(7)), date(7));
+        assert_eq!(wednesday.occurrence(date(13)), date(7));
+        assert_eq!(wednesday.occurrence(date(6)), NaiveDate::from_ymd_opt(2025, 12, 31).unwrap());
+        assert_eq!(wednesday.following(date(7)), date(14));
+        assert_eq!(TodoRecurrence::Daily.following(date(31)), NaiveDate::from_ymd_opt(2026, 2, 1).unwrap());
+    }
+
+    #[test]
+    fn editing_a_recurring_line_keeps_it_recurring() {
+        let db = TempDatabase::new();
+        let id = todo_note(&db, "- [ ] water plants\n- [ ] stretch");
+        db.set_todo_recurrence(&id, 0, Some(TodoRecurrence::Daily)).unwrap();
+
+        set_content(&db, &id, "- [ ] water the plants\n- [ ] stretch");
+        assert_eq!(db.list_recurring_todos(&id).unwrap()[0].text, "water the plants");
+
+        // Moving lines around changes no text
+        set_content(&db, &id, "- [ ] stretch\n- [ ] water the plants");
+        db.move_todo(&id, 0, 1).unwrap();
+        assert_eq!(db.list_recurring_todos(&id).unwrap()[0].text, "water the plants");
+    }
+
+    #[test]
+    fn skipped_periods_are_recorded_as_not_done() {
+        let db = TempDatabase::new();
+        let id = todo_note(&db, "- [x] journal");
+        let todo = db.set_todo_recurrence(&id, 0, Some(TodoRecurrence::Daily)).unwrap().unwrap();
+        let three_days_ago = today() - chrono::Duration::days(3);
+        db.conn()
+            .unwrap()
+            .execute(
+                "UPDATE recurring_todos SET occurrence = ? WHERE id = ?",
+                [three_days_ago.to_string(), todo.id.clone()],
+            )
+            .unwrap();
+
+        let changed = db.roll_over_recurring_todos().unwrap();
+        assert_eq!(changed[0].content, "- [ ] journal");
+        let history: Vec<_> = db
+            .todo_history(&todo.id)
+            .unwrap()
+            .into_iter()
+            .map(|completion| (completion.occurrence, completion.completed))
+            .collect();
+        let day = |back: i64| (today() - chrono::Duration::days(back)).to_string();
+        assert_eq!(history, [(day(1), false), (day(2), false), (day(3), true)]);
+    }
+}
@@ -0,0 +1,350 @@
+use chrono::{DateTime, Datelike, Days, Local, Utc, Weekday};
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{row_to_note, Database, Note, NOTE_COLUMNS};
+
+/// How often a recurring reminder comes back, in local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Recurrence {
+    Daily,
+    /// Monday to Friday
+    Weekdays,
+    Weekly,
+}
+
+impl Recurrence {
+    fn as_str(self) -> &'static str {
+        match self {
+            Recurrence::Daily => "daily",
+            Recurrence::Weekdays => "weekdays",
+            Recurrence::Weekly => "weekly",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "daily" => Some(Recurrence::Daily),
+            "weekdays" => Some(Recurrence::Weekdays),
+            "weekly" => Some(Recurrence::Weekly),
+            _ => None,
+        }
+    }
+
+    /// First occurrence after `after`, stepping from `from`.
+    fn next_after(self, from: DateTime<Utc>, after: DateTime<Utc>) -> DateTime<Utc> {
+        let step = |at: DateTime<Local>, days: u64| {
+            // Keeps the wall-clock time across DST changes where possible
+            at.checked_add_days(Days::new(days))
+                .unwrap_or(at + chrono::Duration::days(days as i64))
+        };
+
+        let mut next = from.with_timezone(&Local);
+        loop {
+            next = match self {
+                Recurrence::Daily => step(next, 1),
+                Recurrence::Weekly => step(next, 7),
+                Recurrence::Weekdays => {
+                    let mut day = step(next, 1);
+                    while matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+                        day = step(day, 1);
+                    }
+                    day
+                }
+            };
+            if next.with_timezone(&Utc) > after {
+                return next.with_timezone(&Utc);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reminder {
+    pub id: String,
+    pub note_id: String,
+    /// Next scheduled time
+    pub fire_at: String,
+    pub recurrence: Option<Recurrence>,
+    /// When it went off, kept until it is snoozed or acknowledged; the note
+    /// shows the reminder as ringing meanwhile
+    pub fired_at: Option<String>,
+    /// Set while snoozed; takes precedence over `fire_at`
+    pub snoozed_until: Option<String>,
+    pub created_at: String,
+}
+
+impl Reminder {
+    /// Whether the reminder should go off at `now`: its time, or the end of
+    /// its snooze, has come. A one-off reminder that already went off only
+    /// goes off again once snoozed.
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        if self.snoozed_until.is_none() && self.recurrence.is_none() && self.fired_at.is_some() {
+            return false;
+        }
+        let at = self.snoozed_until.as_deref().unwrap_or(&self.fire_at);
+        DateTime::parse_from_rfc3339(at).is_ok_and(|at| at <= now)
+    }
+}
+
+const REMINDER_COLUMNS: &str = "id, note_id, fire_at, recurrence, fired_at, snoozed_until, created_at";
+
+fn row_to_reminder(row: &rusqlite::Row) -> SqlResult<Reminder> {
+    let recurrence: Option<String> = row.get(3)?;
+    Ok(Reminder {
+        id: row.get(0)?,
+        note_id: row.get(1)?,
+        fire_at: row.get(2)?,
+        recurrence: recurrence.as_deref().and_then(Recurrence::parse),
+        fired_at: row.get(4)?,
+        snoozed_until: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+fn reminder_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT),
+        Some(message),
+    )
+}
+
+fn get_reminder(conn: &Connection, id: &str) -> SqlResult<Option<Reminder>> {
+    conn.query_row(
+        &format!("SELECT {REMINDER_COLUMNS} FROM reminders WHERE id = ?"),
+        [id],
+        row_to_reminder,
+    )
+    .optional()
+}
+
+impl Database {
+    /// Schedules a reminder for a note at `fire_at` (RFC 3339), repeating
+    /// per `recurrence` if given.
+    pub fn set_reminder(&self, note_id: &str, fire_at: &str, recurrence: Option<Recurrence>) -> SqlResult<Reminder> {
+        let fire_at = DateTime::parse_from_rfc3339(fire_at)
+            .map_err(|e| reminder_error(format!("invalid reminder time: {e}")))?
+            .with_timezone(&Utc);
+
+        let conn = self.conn()?;
+        let in_trash: Option<bool> = conn
+            .query_row("SELECT deleted_at IS NOT NULL FROM notes WHERE id = ?", [note_id], |row| row.get(0))
+            .optional()?;
+        match in_trash {
+            None => return Err(rusqlite::Error::QueryReturnedNoRows),
+            Some(true) => return Err(reminder_error("note is in the trash".to_string())),
+            Some(false) => {}
+        }
+
+        let reminder = Reminder {
+            id: Uuid::new_v4().to_string(),
+            note_id: note_id.to_string(),
+            fire_at: fire_at.to_rfc3339(),
+            recurrence,
+            fired_at: None,
+            snoozed_until: None,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        conn.execute(
+            &format!("INSERT INTO reminders ({REMINDER_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?)"),
+            rusqlite::params![
+                reminder.id,
+                reminder.note_id,
+                reminder.fire_at,
+                reminder.recurrence.map(Recurrence::as_str),
+                reminder.fired_at,
+                reminder.snoozed_until,
+                reminder.created_at,
+            ],
+        )?;
+        Ok(reminder)
+    }
+
+    /// Reminders of one note, or of all notes, soonest first. Those that went
+    /// off and weren't acknowledged yet have `fired_at` set.
+    pub fn list_reminders(&self, note_id: Option<&str>) -> SqlResult<Vec<Reminder>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {REMINDER_COLUMNS} FROM reminders
+             WHERE ?1 IS NULL OR note_id = ?1
+             ORDER BY coalesce(snoozed_until, fire_at)"
+        ))?;
+        let reminders = stmt.query_map([note_id], row_to_reminder)?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(reminders)
+    }
+
+    /// Stops the reminder ringing and makes it go off again `minutes` from
+    /// now. A recurring reminder keeps its schedule afterwards.
+    pub fn snooze_reminder(&self, id: &str, minutes: i64) -> SqlResult<Reminder> {
+        let until = chrono::Duration::try_minutes(minutes.max(1))
+            .and_then(|snooze| Utc::now().checked_add_signed(snooze))
+            .ok_or_else(|| reminder_error(format!("can't snooze for {minutes} minutes")))?;
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE reminders SET snoozed_until = ?, fired_at = NULL WHERE id = ?",
+            rusqlite::params![until.to_rfc3339(), id],
+        )?;
+        get_reminder(&conn, id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+    }
+
+    /// Removes a reminder, ending its recurrence if it has one.
+    pub fn dismiss_reminder(&self, id: &str) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM reminders WHERE id = ?", [id])?;
+        Ok(())
+    }
+
+    /// Stops a reminder ringing. A one-off reminder is done and removed; a
+    /// recurring one waits for its next occurrence.
+    pub fn acknowledge_reminder(&self, id: &str) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM reminders WHERE id = ? AND recurrence IS NULL", [id])?;
+        conn.execute("UPDATE reminders SET fired_at = NULL WHERE id = ?", [id])?;
+        Ok(())
+    }
+
+    /// Takes every reminder that is due, marks it as fired and returns them
+    /// with their notes. Recurring reminders move on to their next occurrence
+    /// after now, so reminders missed while the app wasn't running go off
+    /// once. Reminders of notes in the trash wait until the note is restored.
+    pub fn take_due_reminders(&self) -> SqlResult<Vec<(Reminder, Note)>> {
+        let now = Utc::now();
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let candidates = {
+            let mut stmt = tx.prepare(&format!("SELECT {REMINDER_COLUMNS} FROM reminders"))?;
+            let reminders = stmt.query_map([], row_to_reminder)?.collect::<SqlResult<Vec<_>>>()?;
+            reminders
+        };
+
+        let mut due = Vec::new();
+        for mut reminder in candidates.into_iter().filter(|reminder| reminder.is_due(now)) {
+            let note = tx
+                .query_row(
+                    &format!("SELECT {NOTE_COLUMNS} FROM notes WHERE id = ? AND deleted_at IS NULL"),
+                    [&reminder.note_id],
+                    row_to_note,
+                )
+                .optional()?;
+            let Some(note) = note else {
+                continue;
+            };
+
+            reminder.fired_at = Some(now.to_rfc3339());
+            reminder.snoozed_until = None;
+
+            if let Some(recurrence) = reminder.recurrence {
+                let scheduled = DateTime::parse_from_rfc3339(&reminder.fire_at)
+                    .map(|at| at.with_timezone(&Utc))
+                    .unwrap_or(now);
+                if scheduled <= now {
+                    reminder.fire_at = recurrence.next_after(scheduled, now).to_rfc3339();
+                }
+            }
+            tx.execute(
+                "UPDATE reminders SET fire_at = ?, fired_at = ?, snoozed_until = NULL WHERE id = ?",
+                rusqlite::params![reminder.fire_at, reminder.fired_at, reminder.id],
+            )?;
+            due.push((reminder, note));
+        }
+
+        tx.commit()?;
+        Ok(due)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    /// 2026-01-09 is a Friday; January keeps clear of DST changes.
+    fn local(day: u32, hour: u32) -> DateTime<Utc> {
+        Local.with_ymd_and_hms(2026, 1, day, hour, 0, 0).unwrap().with_timezone(&Utc)
+    }
+
+    fn reminder(fire_at: DateTime<Utc>, snoozed_until: Option<DateTime<Utc>>) -> Reminder {
+        Reminder {
+            id: String::new(),
+            note_id: String::new(),
+            fire_at: fire_at.to_rfc3339(),
+            recurrence: None,
+            fired_at: None,
+            snoozed_until: snoozed_until.map(|at| at.to_rfc3339()),
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn next_after_steps_past_the_given_time() {
+        let friday = local(9, 9);
+        assert_eq!(Recurrence::Daily.next_after(friday, friday), local(10, 9));
+        assert_eq!(Recurrence::Weekly.next_after(friday, friday), local(16, 9));
+        assert_eq!(Recurrence::Weekdays.next_after(friday, friday), local(12, 9));
+        // Missed occurrences are skipped, not replayed
+        assert_eq!(Recurrence::Daily.next_after(friday, local(12, 10)), local(13, 9));
+        assert_eq!(Recurrence::Weekdays.next_after(local(5, 9), local(10, 12)), local(12, 9));
+    }
+
+    #[test]
+    fn is_due_follows_the_snooze() {
+        let now = local(9, 12);
+        assert!(reminder(local(9, 9), None).is_due(now));
+        assert!(!reminder(local(9, 13), None).is_due(now));
+        assert!(!reminder(local(9, 9), Some(local(9, 13))).is_due(now));
+        assert!(reminder(local(9, 13), Some(local(9, 11))).is_due(now));
+
+        let fired = Reminder { fired_at: Some(local(9, 9).to_rfc3339()), ..reminder(local(9, 9), None) };
+        assert!(!fired.is_due(now));
+        assert!(Reminder { snoozed_until: Some(local(9, 11).to_rfc3339()), ..fired }.is_due(now));
+    }
+
+    #[test]
+    fn fired_reminders_ring_until_acknowledged() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        let past = (Utc::now() - chrono::Duration::minutes(5)).to_rfc3339();
+        let one_off = db.set_reminder(&note.id, &past, None).unwrap();
+        let recurring = db.set_reminder(&note.id, &past, Some(Recurrence::Daily)).unwrap();
+
+        assert_eq!(db.take_due_reminders().unwrap().len(), 2);
+        // Still there for a note window opened later, but not fired twice
+        let ringing = db.list_reminders(Some(&note.id)).unwrap();
+        assert!(ringing.iter().all(|r| r.fired_at.is_some() && r.snoozed_until.is_none()));
+        assert!(db.take_due_reminders().unwrap().is_empty());
+
+        // Snoozing stops the ringing until the snooze ends
+        let snoozed = db.snooze_reminder(&one_off.id, 10).unwrap();
+        assert!(snoozed.fired_at.is_none());
+        assert!(db.take_due_reminders().unwrap().is_empty());
+        db.conn()
+            .unwrap()
+            .execute("UPDATE reminders SET snoozed_until = ? WHERE id = ?", [past.as_str(), one_off.id.as_str()])
+            .unwrap();
+        let due = db.take_due_reminders().unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0.id, one_off.id);
+
+        db.acknowledge_reminder(&one_off.id).unwrap();
+        db.acknowledge_reminder(&recurring.id).unwrap();
+        let left = db.list_reminders(Some(&note.id)).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].id, recurring.id);
+        assert!(left[0].fired_at.is_none());
+        assert!(DateTime::parse_from_rfc3339(&left[0].fire_at).unwrap() > Utc::now());
+    }
+
+    #[test]
+    fn huge_snooze_is_rejected() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        let reminder = db.set_reminder(&note.id, &Utc::now().to_rfc3339(), None).unwrap();
+        assert!(db.snooze_reminder(&reminder.id, i64::MAX).is_err());
+        assert!(db.snooze_reminder(&reminder.id, 10).unwrap().snoozed_until.is_some());
+    }
+}
@@ -0,0 +1,255 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+
+use super::{locks, save_content, Database, Note};
+
+/// Saves within this many seconds of a revision being started add no new one,
+/// so debounced autosave yields one revision per editing burst.
+const COALESCE_WINDOW_SECS: i64 = 120;
+
+/// Oldest revisions beyond this count are pruned per note.
+const MAX_REVISIONS_PER_NOTE: i64 = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteRevision {
+    pub id: i64,
+    pub note_id: String,
+    pub title: String,
+    pub content: String,
+    /// "edit" for autosave snapshots; "restore", "sync", "import" or
+    /// "rollover" for the state replaced by that operation
+    pub source: String,
+    pub created_at: String,
+}
+
+/// Records the note's current title/content before it is overwritten with
+/// `title`/`content`.
+///
+/// Saves inside the coalesce window of the newest "edit" revision add
+/// nothing, so that revision keeps the state from the start of the editing
+/// burst — a select-all-delete followed by typing can still be undone.
+/// `source` other than "edit" always starts a new revision that is never
+/// coalesced into.
+pub(super) fn snapshot_before_edit(
+    conn: &Connection,
+    note_id: &str,
+    title: Option<&str>,
+    content: Option<&str>,
+    now: &str,
+    source: &str,
+) -> SqlResult<()> {
+    let current: Option<(String, String)> = conn
+        .query_row(
+            "SELECT title, content FROM notes WHERE id = ?",
+            [note_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    let Some((cur_title, cur_content)) = current else {
+        return Ok(());
+    };
+
+    let changes_title = title.is_some_and(|t| t != cur_title);
+    let changes_content = content.is_some_and(|c| c != cur_content);
+    if !changes_title && !changes_content {
+        return Ok(());
+    }
+
+    let latest: Option<(String, String, String, String)> = conn
+        .query_row(
+            "SELECT title, content, source, created_at FROM note_revisions
+             WHERE note_id = ? ORDER BY id DESC LIMIT 1",
+            [note_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+
+    if let Some((rev_title, rev_content, rev_source, rev_created_at)) = latest {
+        if rev_title == cur_title && rev_content == cur_content {
+            return Ok(());
+        }
+        if source == "edit" && rev_source == "edit" && within_window(&rev_created_at, now) {
+            return Ok(());
+        }
+    }
+
+    conn.execute(
+        "INSERT INTO note_revisions (note_id, title, content, source, created_at)
+         VALUES (?, ?, ?, ?, ?)",
+        rusqlite::params![note_id, cur_title, cur_content, source, now],
+    )?;
+
+    conn.execute(
+        "DELETE FROM note_revisions WHERE note_id = ?1 AND id NOT IN (
+             SELECT id FROM note_revisions WHERE note_id = ?1 ORDER BY id DESC LIMIT ?2
+         )",
+        rusqlite::params![note_id, MAX_REVISIONS_PER_NOTE],
+    )?;
+
+    Ok(())
+}
+
+fn within_window(earlier: &str, now: &str) -> bool {
+    match (
+        DateTime::parse_from_rfc3339(earlier),
+        DateTime::parse_from_rfc3339(now),
+    ) {
+        (Ok(earlier), Ok(now)) => (now - earlier).num_seconds() < COALESCE_WINDOW_SECS,
+        _ => false,
+    }
+}
+
+pub(super) fn row_to_revision(row: &rusqlite::Row) -> SqlResult<NoteRevision> {
+    Ok(NoteRevision {
+        id: row.get(0)?,
+        note_id: row.get(1)?,
+        title: row.get(2)?,
+        content: row.get(3)?,
+        source: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+impl Database {
+    /// Revisions of a note, newest first.
+    pub fn list_note_revisions(&self, note_id: &str) -> SqlResult<Vec<NoteRevision>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, note_id, title, content, source, created_at
+             FROM note_revisions WHERE note_id = ? ORDER BY id DESC",
+        )?;
+        let revisions = stmt
+            .query_map([note_id], row_to_revision)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(revisions)
+    }
+
+    pub fn get_note_revision(&self, revision_id: i64) -> SqlResult<Option<NoteRevision>> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT id, note_id, title, content, source, created_at
+             FROM note_revisions WHERE id = ?",
+            [revision_id],
+            row_to_revision,
+        )
+        .optional()
+    }
+
+    /// Replaces the note's title and content with those of `revision_id`. The
+    /// state being replaced is saved as a new revision first, so a restore can
+    /// itself be undone. Links, hashtags and recurring todo lines follow the
+    /// restored content as they do for an edit.
+    pub fn restore_note_revision(&self, revision_id: i64) -> SqlResult<Option<Note>> {
+        let Some(revision) = self.get_note_revision(revision_id)? else {
+            return Ok(None);
+        };
+
+        let now = Utc::now().to_rfc3339();
+        let extract_hashtags = self.get_settings()?.extract_hashtags;
+        {
+            let mut conn = self.conn()?;
+            let tx = conn.transaction()?;
+            if locks::is_locked(&tx, &revision.note_id)? {
+                return Err(locks::locked_error());
+            }
+            snapshot_before_edit(
+                &tx,
+                &revision.note_id,
+                Some(&revision.title),
+                Some(&revision.content),
+                &now,
+                "restore",
+            )?;
+            tx.execute(
+                "UPDATE notes SET title = ?, updated_at = ? WHERE id = ?",
+                rusqlite::params![revision.title, now, revision.note_id],
+            )?;
+            save_content(&tx, &revision.note_id, &revision.content, &now, extract_hashtags)?;
+            tx.commit()?;
+        }
+
+        self.get_note(&revision.note_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+
+    fn set_content(db: &TempDatabase, id: &str, content: &str) {
+        db.update_note(id, None, Some(content), None, None, None, None, None, None, None).unwrap();
+    }
+
+    /// Moves every revision of the note out of the coalesce window.
+    fn age_revisions(db: &TempDatabase, id: &str) {
+        let old = (chrono::Utc::now() - chrono::Duration::minutes(10)).to_rfc3339();
+        db.conn()
+            .unwrap()
+            .execute("UPDATE note_revisions SET created_at = ? WHERE note_id = ?", [old.as_str(), id])
+            .unwrap();
+    }
+
+    #[test]
+    fn burst_keeps_state_from_before_it_started() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        set_content(&db, &note.id, "important");
+        age_revisions(&db, &note.id);
+
+        // Select-all-delete, then typing over it within one burst
+        set_content(&db, &note.id, "");
+        for typed in ["o", "oo", "oops"] {
+            set_content(&db, &note.id, typed);
+        }
+
+        let revisions = db.list_note_revisions(&note.id).unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].content, "important");
+
+        let restored = db.restore_note_revision(revisions[0].id).unwrap().unwrap();
+        assert_eq!(restored.content, "important");
+        let revisions = db.list_note_revisions(&note.id).unwrap();
+        assert_eq!((revisions[0].source.as_str(), revisions[0].content.as_str()), ("restore", "oops"));
+    }
+
+    #[test]
+    fn saves_after_the_window_start_a_new_revision() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        set_content(&db, &note.id, "one");
+        age_revisions(&db, &note.id);
+        set_content(&db, &note.id, "two");
+        age_revisions(&db, &note.id);
+        set_content(&db, &note.id, "three");
+
+        let contents: Vec<_> = db
+            .list_note_revisions(&note.id)
+            .unwrap()
+            .into_iter()
+            .map(|revision| revision.content)
+            .collect();
+        assert_eq!(contents, ["two", "one", ""]);
+    }
+
+    #[test]
+    fn restore_brings_links_and_hashtags_along() {
+        let db = TempDatabase::new();
+        db.set_setting("extract_hashtags", "true").unwrap();
+        let target = db.create_note(0, 0).unwrap();
+        db.update_note(&target.id, Some("Target"), None, None, None, None, None, None, None, None).unwrap();
+        let note = db.create_note(0, 0).unwrap();
+        set_content(&db, &note.id, "see [[Target]] #work");
+        age_revisions(&db, &note.id);
+        set_content(&db, &note.id, "nothing here");
+        assert!(db.get_backlinks(&target.id).unwrap().is_empty());
+
+        let revision = db.list_note_revisions(&note.id).unwrap().remove(0);
+        assert_eq!(revision.content, "see [[Target]] #work");
+        db.restore_note_revision(revision.id).unwrap();
+
+        assert_eq!(db.get_backlinks(&target.id).unwrap().len(), 1);
+        assert_eq!(db.get_note_tags(&note.id).unwrap(), ["work"]);
+    }
+}
@@ -0,0 +1,88 @@
+use rusqlite::Result as SqlResult;
+use serde::{Deserialize, Serialize};
+
+use super::Database;
+
+const HIGHLIGHT_START: &str = "<mark>";
+const HIGHLIGHT_END: &str = "</mark>";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub id: String,
+    pub title: String,
+    /// Title with matched terms wrapped in `<mark>`…`</mark>`
+    pub title_highlight: String,
+    /// Excerpt of the content around the best match, highlighted the same way
+    pub snippet: String,
+    /// bm25 score; lower is a better match
+    pub rank: f64,
+}
+
+/// Turns free-form user input into an FTS5 MATCH expression.
+///
+/// Double-quoted runs become phrase queries and every other word becomes a
+/// prefix query, all ANDed together. Everything is re-quoted so FTS5 operators
+/// and punctuation in the input can never produce a syntax error.
+pub fn build_match_query(input: &str) -> Option<String> {
+    let mut terms = Vec::new();
+
+    for (i, part) in input.split('"').enumerate() {
+        let in_phrase = i % 2 == 1;
+        if in_phrase {
+            let phrase = part.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !phrase.is_empty() {
+                terms.push(format!("\"{phrase}\""));
+            }
+        } else {
+            for word in part.split_whitespace() {
+                terms.push(format!("\"{word}\"*"));
+            }
+        }
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+impl Database {
+    /// Ranked full-text search over note titles and content, excluding the
+    /// trash. Title matches weigh more than content matches.
+    pub fn search_notes(&self, query: &str, limit: u32) -> SqlResult<Vec<SearchResult>> {
+        let Some(match_query) = build_match_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT n.id, n.title,
+                    highlight(notes_fts, 1, ?2, ?3),
+                    snippet(notes_fts, 2, ?2, ?3, '…', 16),
+                    bm25(notes_fts, 0.0, 10.0, 1.0) AS score
+             FROM notes_fts
+             JOIN notes n ON n.id = notes_fts.note_id
+             WHERE notes_fts MATCH ?1 AND n.deleted_at IS NULL
+             ORDER BY score
+             LIMIT ?4",
+        )?;
+
+        let results = stmt
+            .query_map(
+                rusqlite::params![match_query, HIGHLIGHT_START, HIGHLIGHT_END, limit],
+                |row| {
+                    Ok(SearchResult {
+                        id: row.get(0)?,
+                        title: row.get(1)?,
+                        title_highlight: row.get(2)?,
+                        snippet: row.get(3)?,
+                        rank: row.get(4)?,
+                    })
+                },
+            )?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        Ok(results)
+    }
+}
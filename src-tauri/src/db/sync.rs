@@ -0,0 +1,576 @@
+use chrono::{Local, Utc};
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use super::archive::{drop_missing_notebook, insert_lock, is_newer, overwrite_note, read_lock, ArchivedLock};
+use super::{insert_note, revisions, row_to_note, tags, Database, Note, NOTE_COLUMNS};
+
+/// One device's current version of one note, stored in the sync folder as
+/// `notes/<note id>/<device id>.json`. Every file has a single writer, so
+/// the folder can be replicated by any file sync tool without write clashes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChangeFile {
+    device_id: String,
+    note_id: String,
+    written_at: String,
+    /// `None` once the note was permanently deleted on that device
+    note: Option<Note>,
+    #[serde(default)]
+    lock: Option<ArchivedLock>,
+    /// `updated_at` of other devices' versions that this version already
+    /// accounts for, so they aren't treated as conflicting with it again
+    #[serde(default)]
+    supersedes: Vec<String>,
+    /// For deletions, the `updated_at` of the version that was deleted
+    #[serde(default)]
+    deleted_version: Option<String>,
+}
+
+/// What a sync run changed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    /// Notes created or overwritten from other devices' versions
+    pub imported: usize,
+    /// Change files written for this device
+    pub exported: usize,
+    /// Conflict copies created for edits that lost
+    pub conflicts: usize,
+    /// Notes permanently deleted because another device deleted them
+    pub deleted: usize,
+}
+
+impl SyncReport {
+    /// Whether any note in this database was created, changed or removed.
+    pub fn changed_notes(&self) -> bool {
+        self.imported > 0 || self.conflicts > 0 || self.deleted > 0
+    }
+}
+
+/// Last version of a note taken over from another device.
+struct SyncState {
+    synced_updated_at: String,
+    superseded: Vec<String>,
+}
+
+fn sync_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_IOERR),
+        Some(message),
+    )
+}
+
+fn read_change_file(path: &Path) -> Option<ChangeFile> {
+    let data = std::fs::read(path).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+fn write_change_file(path: &Path, file: &ChangeFile) -> SqlResult<()> {
+    let json = serde_json::to_vec_pretty(file)
+        .map_err(|e| sync_error(format!("failed to serialize change: {e}")))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json)
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .map_err(|e| sync_error(format!("failed to write {}: {e}", path.display())))
+}
+
+/// Whether two change files describe the same version, ignoring when they
+/// were written.
+fn same_change(a: &ChangeFile, b: &ChangeFile) -> bool {
+    let mut b = b.clone();
+    b.written_at = a.written_at.clone();
+    serde_json::to_value(a).ok() == serde_json::to_value(&b).ok()
+}
+
+/// Whether two versions of a note have the same title and content.
+fn same_text(a: &Note, a_lock: Option<&ArchivedLock>, b: &Note, b_lock: Option<&ArchivedLock>) -> bool {
+    a.title == b.title
+        && a.content == b.content
+        && a_lock.map(|l| &l.ciphertext) == b_lock.map(|l| &l.ciphertext)
+}
+
+fn get_state(conn: &Connection, note_id: &str) -> SqlResult<Option<SyncState>> {
+    conn.query_row(
+        "SELECT synced_updated_at, superseded FROM sync_state WHERE note_id = ?",
+        [note_id],
+        |row| {
+            let superseded: String = row.get(1)?;
+            Ok(SyncState {
+                synced_updated_at: row.get(0)?,
+                superseded: superseded.lines().map(str::to_string).collect(),
+            })
+        },
+    )
+    .optional()
+}
+
+fn set_state(conn: &Connection, note_id: &str, synced_updated_at: &str, superseded: &[String]) -> SqlResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_state (note_id, synced_updated_at, superseded) VALUES (?, ?, ?)",
+        rusqlite::params![note_id, synced_updated_at, superseded.join("\n")],
+    )?;
+    Ok(())
+}
+
+fn get_note_row(conn: &Connection, id: &str) -> SqlResult<Option<Note>> {
+    conn.query_row(&format!("SELECT {NOTE_COLUMNS} FROM notes WHERE id = ?"), [id], row_to_note)
+        .optional()
+}
+
+/// Takes over another device's version of a note, keeping the replaced
+/// text as a "sync" revision so it can be brought back. Window geometry and
+/// open state stay as they are on this device.
+fn apply_remote(conn: &Connection, note: &Note, lock: Option<&ArchivedLock>, extract_hashtags: bool) -> SqlResult<()> {
+    let mut note = note.clone();
+    drop_missing_notebook(conn, &mut note)?;
+
+    if let Some(local) = get_note_row(conn, &note.id)? {
+        note.pos_x = local.pos_x;
+        note.pos_y = local.pos_y;
+        note.width = local.width;
+        note.height = local.height;
+        note.is_open = local.is_open;
+        note.is_minimized = local.is_minimized;
+        let now = Utc::now().to_rfc3339();
+        revisions::snapshot_before_edit(conn, &note.id, Some(&note.title), Some(&note.content), &now, "sync")?;
+        overwrite_note(conn, &note)?;
+    } else {
+        insert_note(conn, &note)?;
+    }
+
+    if let Some(lock) = lock {
+        insert_lock(conn, lock)?;
+    } else if extract_hashtags {
+        tags::sync_hashtags(conn, &note.id, &note.content)?;
+    }
+    Ok(())
+}
+
+/// Saves the text of an edit that lost a conflict as a new, open note. A
+/// locked loser's copy gets its lock, still bound to the original note id
+/// since it can't be re-sealed without the passphrase.
+fn insert_conflict_copy(
+    conn: &Connection,
+    loser: &Note,
+    lock: Option<&ArchivedLock>,
+    extract_hashtags: bool,
+) -> SqlResult<()> {
+    let now = Utc::now().to_rfc3339();
+    let title = if loser.title.trim().is_empty() { "Untitled" } else { loser.title.trim() };
+    let mut copy = Note {
+        id: Uuid::new_v4().to_string(),
+        title: format!("{title} (conflict copy {})", Local::now().format("%Y-%m-%d %H:%M")),
+        pos_x: loser.pos_x + 30,
+        pos_y: loser.pos_y + 30,
+        is_open: true,
+        is_minimized: false,
+        created_at: now.clone(),
+        updated_at: now,
+        deleted_at: None,
+        is_locked: false,
+        ..loser.clone()
+    };
+    drop_missing_notebook(conn, &mut copy)?;
+
+    insert_note(conn, &copy)?;
+    if let Some(lock) = lock {
+        insert_lock(
+            conn,
+            &ArchivedLock {
+                note_id: copy.id.clone(),
+                sealed_for: Some(lock.sealed_for.clone().unwrap_or_else(|| lock.note_id.clone())),
+                ..lock.clone()
+            },
+        )?;
+    } else if extract_hashtags {
+        tags::sync_hashtags(conn, &copy.id, &copy.content)?;
+    }
+    Ok(())
+}
+
+impl Database {
+    fn device_id(&self) -> SqlResult<String> {
+        self.get_setting("sync_device_id")
+    }
+
+    /// Syncs with the folder from the `sync_dir` setting.
+    pub fn sync_now(&self) -> SqlResult<SyncReport> {
+        let dir = self.get_settings()?.sync_dir;
+        if dir.is_empty() {
+            return Err(sync_error("no sync folder is set".to_string()));
+        }
+        self.sync_with(Path::new(&dir))
+    }
+
+    /// Takes over newer versions of notes written to `dir` by other devices,
+    /// then writes this device's versions of every note there.
+    ///
+    /// When a note was edited on both sides since they last synced, the newer
+    /// version (by `updated_at`) wins and the other one's text is kept as a
+    /// conflict copy note, locked like it if it was.
+    pub fn sync_with(&self, dir: &Path) -> SqlResult<SyncReport> {
+        let notes_dir = dir.join("notes");
+        std::fs::create_dir_all(&notes_dir)
+            .map_err(|e| sync_error(format!("failed to create {}: {e}", notes_dir.display())))?;
+
+        let device_id = self.device_id()?;
+        let extract_hashtags = self.get_settings()?.extract_hashtags;
+        let mut report = SyncReport::default();
+
+        let mut note_dirs: Vec<PathBuf> = std::fs::read_dir(&notes_dir)
+            .map_err(|e| sync_error(format!("failed to read {}: {e}", notes_dir.display())))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect();
+        note_dirs.sort();
+
+        for note_dir in &note_dirs {
+            let Some(note_id) = note_dir.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if Uuid::parse_str(note_id).is_err() {
+                continue;
+            }
+
+            let own = read_change_file(&note_dir.join(format!("{device_id}.json")));
+            let mut remotes: Vec<ChangeFile> = std::fs::read_dir(note_dir)
+                .map_err(|e| sync_error(format!("failed to read {}: {e}", note_dir.display())))?
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|e| e == "json"))
+                .filter_map(|path| read_change_file(&path))
+                .filter(|file| file.device_id != device_id && file.note_id == note_id)
+                .collect();
+            // Oldest first, so the newest version is the last one applied
+            remotes.sort_by(|a, b| {
+                let updated = |file: &ChangeFile| file.note.as_ref().map(|n| n.updated_at.clone()).unwrap_or_default();
+                updated(a).cmp(&updated(b))
+            });
+
+            for remote in &remotes {
+                self.ingest_change(remote, own.as_ref(), extract_hashtags, &mut report)?;
+            }
+        }
+
+        self.publish_changes(&notes_dir, &device_id, &mut report)?;
+
+        if report.deleted > 0 {
+            self.remove_orphaned_blobs()?;
+        }
+        Ok(report)
+    }
+
+    fn ingest_change(
+        &self,
+        remote: &ChangeFile,
+        own: Option<&ChangeFile>,
+        extract_hashtags: bool,
+        report: &mut SyncReport,
+    ) -> SqlResult<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let id = remote.note_id.as_str();
+
+        let local = get_note_row(&tx, id)?;
+        let state = get_state(&tx, id)?;
+
+        match (&remote.note, local) {
+            (None, None) => {}
+
+            // Deleted over there; follow unless it changed here since
+            (None, Some(local)) => {
+                let deleted_version = remote.deleted_version.as_deref().unwrap_or_default();
+                if !is_newer(&local.updated_at, deleted_version) {
+                    tx.execute("DELETE FROM notes WHERE id = ?", [id])?;
+                    tx.execute("DELETE FROM sync_state WHERE note_id = ?", [id])?;
+                    report.deleted += 1;
+                }
+            }
+
+            (Some(remote_note), None) => {
+                // Deleted here if this device wrote a file for it before; it
+                // only comes back if edited over there after that version
+                let deleted_version = own.and_then(|own| match &own.note {
+                    Some(note) => Some(note.updated_at.as_str()),
+                    None => own.deleted_version.as_deref(),
+                });
+                if deleted_version.is_none_or(|version| is_newer(&remote_note.updated_at, version)) {
+                    apply_remote(&tx, remote_note, remote.lock.as_ref(), extract_hashtags)?;
+                    set_state(&tx, id, &remote_note.updated_at, &[])?;
+                    report.imported += 1;
+                }
+            }
+
+            (Some(remote_note), Some(local)) => {
+                let superseded = state.as_ref().map(|s| s.superseded.clone()).unwrap_or_default();
+
+                if remote_note.updated_at == local.updated_at {
+                    set_state(&tx, id, &remote_note.updated_at, &superseded)?;
+                } else if state
+                    .as_ref()
+                    .is_some_and(|s| !is_newer(&remote_note.updated_at, &s.synced_updated_at))
+                {
+                    // Already seen
+                } else if remote.supersedes.contains(&remote_note.updated_at)
+                    && !is_newer(&remote_note.updated_at, &local.updated_at)
+                {
+                    // Their copy of a version this device has moved past
+                } else {
+                    let local_changed = state
+                        .as_ref()
+                        .is_none_or(|s| is_newer(&local.updated_at, &s.synced_updated_at))
+                        && !remote.supersedes.contains(&local.updated_at);
+
+                    let remote_wins = !local_changed || is_newer(&remote_note.updated_at, &local.updated_at);
+
+                    if local_changed {
+                        let local_lock = read_lock(&tx, id)?;
+                        let (loser, loser_lock) = if remote_wins {
+                            (&local, local_lock.as_ref())
+                        } else {
+                            (remote_note, remote.lock.as_ref())
+                        };
+                        // A loser still on the text this device last wrote
+                        // only moved or restyled the note; no edit is lost
+                        let loser_unedited = own
+                            .and_then(|own| Some((own.note.as_ref()?, own.lock.as_ref())))
+                            .is_some_and(|(own_note, own_lock)| same_text(loser, loser_lock, own_note, own_lock));
+                        if !same_text(&local, local_lock.as_ref(), remote_note, remote.lock.as_ref())
+                            && !loser_unedited
+                        {
+                            insert_conflict_copy(&tx, loser, loser_lock, extract_hashtags)?;
+                            report.conflicts += 1;
+                        }
+                    }
+
+                    if remote_wins {
+                        apply_remote(&tx, remote_note, remote.lock.as_ref(), extract_hashtags)?;
+                        set_state(&tx, id, &remote_note.updated_at, &[])?;
+                        report.imported += 1;
+                    } else {
+                        let mut superseded = superseded;
+                        superseded.push(remote_note.updated_at.clone());
+                        set_state(&tx, id, &remote_note.updated_at, &superseded)?;
+                    }
+                }
+            }
+        }
+
+        tx.commit()
+    }
+
+    /// Writes a change file for every note whose version here differs from
+    /// what this device last wrote, and a deletion marker for notes that
+    /// were permanently deleted since.
+    fn publish_changes(&self, notes_dir: &Path, device_id: &str, report: &mut SyncReport) -> SqlResult<()> {
+        let now = Utc::now().to_rfc3339();
+
+        let changes = {
+            let conn = self.conn()?;
+            let mut stmt = conn.prepare(&format!("SELECT {NOTE_COLUMNS} FROM notes"))?;
+            let notes = stmt.query_map([], row_to_note)?.collect::<SqlResult<Vec<_>>>()?;
+
+            let mut changes = Vec::with_capacity(notes.len());
+            for note in notes {
+                let lock = read_lock(&conn, &note.id)?;
+                // The version taken over last is accounted for too, so a
+                // device still on it takes this one without a conflict
+                let supersedes = get_state(&conn, &note.id)?
+                    .map(|state| {
+                        let mut supersedes = state.superseded;
+                        supersedes.push(state.synced_updated_at);
+                        supersedes
+                    })
+                    .unwrap_or_default();
+                changes.push(ChangeFile {
+                    device_id: device_id.to_string(),
+                    note_id: note.id.clone(),
+                    written_at: now.clone(),
+                    note: Some(note),
+                    lock,
+                    supersedes,
+                    deleted_version: None,
+                });
+            }
+            changes
+        };
+
+        for change in &changes {
+            let note_dir = notes_dir.join(&change.note_id);
+            let path = note_dir.join(format!("{device_id}.json"));
+            if read_change_file(&path).is_some_and(|current| same_change(&current, change)) {
+                continue;
+            }
+            std::fs::create_dir_all(&note_dir)
+                .map_err(|e| sync_error(format!("failed to create {}: {e}", note_dir.display())))?;
+            write_change_file(&path, change)?;
+            report.exported += 1;
+        }
+
+        // Our earlier versions of notes that no longer exist become deletions
+        let Ok(entries) = std::fs::read_dir(notes_dir) else {
+            return Ok(());
+        };
+        for entry in entries.flatten() {
+            let path = entry.path().join(format!("{device_id}.json"));
+            let Some(previous) = read_change_file(&path) else {
+                continue;
+            };
+            let Some(previous_note) = &previous.note else {
+                continue;
+            };
+            if changes.iter().any(|change| change.note_id == previous.note_id) {
+                continue;
+            }
+
+            let deletion = ChangeFile {
+                device_id: device_id.to_string(),
+                note_id: previous.note_id.clone(),
+                written_at: now.clone(),
+                note: None,
+                lock: None,
+                supersedes: Vec::new(),
+                deleted_version: Some(previous_note.updated_at.clone()),
+            };
+            write_change_file(&path, &deletion)?;
+            report.exported += 1;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    /// Two devices sharing a sync folder, with one note both have synced.
+    fn synced_pair() -> (TempDatabase, TempDatabase, PathBuf, String) {
+        let a = TempDatabase::new();
+        let b = TempDatabase::new();
+        let dir = a.data_dir.join("sync");
+        let note = a.create_note(0, 0).unwrap();
+        a.sync_with(&dir).unwrap();
+        b.sync_with(&dir).unwrap();
+        (a, b, dir, note.id)
+    }
+
+    fn set_content(db: &TempDatabase, id: &str, content: &str) {
+        db.update_note(id, None, Some(content), None, None, None, None, None, None, None).unwrap();
+    }
+
+    fn notes(db: &TempDatabase) -> Vec<Note> {
+        let conn = db.conn().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {NOTE_COLUMNS} FROM notes ORDER BY content")).unwrap();
+        let notes = stmt.query_map([], row_to_note).unwrap();
+        notes.collect::<SqlResult<Vec<_>>>().unwrap()
+    }
+
+    fn contents(db: &TempDatabase) -> Vec<String> {
+        notes(db).into_iter().map(|note| note.content).collect()
+    }
+
+    fn is_quiet(report: &SyncReport) -> bool {
+        report.imported == 0 && report.exported == 0 && report.conflicts == 0 && report.deleted == 0
+    }
+
+    #[test]
+    fn concurrent_edits_keep_the_loser_as_a_conflict_copy() {
+        let (a, b, dir, id) = synced_pair();
+        set_content(&a, &id, "from a");
+        set_content(&b, &id, "from b");
+
+        a.sync_with(&dir).unwrap();
+        assert_eq!(b.sync_with(&dir).unwrap().conflicts, 1);
+        assert_eq!(a.sync_with(&dir).unwrap().conflicts, 0);
+
+        for db in [&a, &b] {
+            assert_eq!(contents(db), ["from a", "from b"]);
+            assert_eq!(db.get_note(&id).unwrap().unwrap().content, "from b");
+        }
+    }
+
+    #[test]
+    fn deletions_follow_unless_edited_since() {
+        let (a, b, dir, edited) = synced_pair();
+        let untouched = a.create_note(0, 0).unwrap().id;
+        a.sync_with(&dir).unwrap();
+        b.sync_with(&dir).unwrap();
+
+        a.delete_note(&edited).unwrap();
+        a.delete_note(&untouched).unwrap();
+        a.empty_trash().unwrap();
+        set_content(&b, &edited, "kept");
+
+        a.sync_with(&dir).unwrap();
+        assert_eq!(b.sync_with(&dir).unwrap().deleted, 1);
+        a.sync_with(&dir).unwrap();
+
+        for db in [&a, &b] {
+            assert!(db.get_note(&untouched).unwrap().is_none());
+            assert_eq!(db.get_note(&edited).unwrap().unwrap().content, "kept");
+        }
+    }
+
+    #[test]
+    fn syncing_again_changes_nothing() {
+        let (a, b, dir, id) = synced_pair();
+        set_content(&a, &id, "one");
+        let other = b.create_note(40, 40).unwrap().id;
+        set_content(&b, &other, "two");
+
+        for _ in 0..2 {
+            a.sync_with(&dir).unwrap();
+            b.sync_with(&dir).unwrap();
+        }
+        assert!(is_quiet(&a.sync_with(&dir).unwrap()));
+        assert!(is_quiet(&b.sync_with(&dir).unwrap()));
+        assert_eq!(contents(&a), contents(&b));
+        assert_eq!(contents(&a), ["one", "two"]);
+    }
+
+    #[test]
+    fn locked_loser_is_copied_with_its_lock() {
+        let (a, b, dir, id) = synced_pair();
+        set_content(&a, &id, "secret");
+        a.lock_note(&id, "pass").unwrap();
+        set_content(&b, &id, "plain");
+
+        a.sync_with(&dir).unwrap();
+        assert_eq!(b.sync_with(&dir).unwrap().conflicts, 1);
+        a.sync_with(&dir).unwrap();
+
+        for db in [&a, &b] {
+            let copy = notes(db).into_iter().find(|note| note.id != id).unwrap();
+            assert!(copy.is_locked);
+            assert_eq!(db.view_locked_note(&copy.id, "pass").unwrap(), "secret");
+            assert_eq!(db.get_note(&id).unwrap().unwrap().content, "plain");
+        }
+        assert_eq!(b.unlock_note(&notes(&b)[0].id, "pass").unwrap().content, "secret");
+    }
+
+    #[test]
+    fn remote_edits_keep_local_window_geometry() {
+        let (a, b, dir, id) = synced_pair();
+        b.update_note(&id, None, None, None, Some(500), Some(400), Some(320), Some(240), None, None)
+            .unwrap();
+        b.close_note(&id).unwrap();
+        set_content(&a, &id, "edited");
+
+        b.sync_with(&dir).unwrap();
+        a.sync_with(&dir).unwrap();
+        let report = b.sync_with(&dir).unwrap();
+        assert_eq!((report.imported, report.conflicts), (1, 0));
+
+        let note = b.get_note(&id).unwrap().unwrap();
+        assert_eq!(note.content, "edited");
+        assert_eq!((note.pos_x, note.pos_y, note.width, note.height), (500, 400, 320, 240));
+        assert!(!note.is_open);
+        assert_eq!(notes(&a).len(), 1);
+    }
+}
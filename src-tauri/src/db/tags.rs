@@ -0,0 +1,470 @@
+use std::ops::Range;
+
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+
+use super::{locks, revisions, row_to_note, Database, Note, NOTE_COLUMNS};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    /// Number of non-trashed notes carrying the tag
+    pub note_count: i64,
+}
+
+/// Normalizes user input into a tag name: surrounding whitespace and a
+/// leading `#` are dropped. Returns `None` if nothing is left.
+pub fn normalize_tag_name(name: &str) -> Option<String> {
+    let name = name.trim().trim_start_matches('#').trim();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Extracts inline `#hashtags` from note content, in order of first
+/// appearance, without duplicates (compared case-insensitively).
+///
+/// A tag must start at the beginning of a word and contain at least one
+/// letter, so Markdown headings (`# Title`), issue numbers (`#42`) and URL
+/// fragments (`page#section`) are not picked up.
+pub fn extract_hashtags(content: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for (_, tag) in hashtag_spans(content) {
+        if !tags.iter().any(|t| t.to_lowercase() == tag.to_lowercase()) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Byte ranges of the hashtags in `content`, `#` included, with their tag
+/// text. See `extract_hashtags` for what counts as a hashtag.
+fn hashtag_spans(content: &str) -> Vec<(Range<usize>, &str)> {
+    let mut spans = Vec::new();
+    let mut prev: Option<char> = None;
+    let mut chars = content.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let at_word_start = prev.is_none_or(|p| p.is_whitespace() || p == '(');
+        prev = Some(c);
+        if c != '#' || !at_word_start {
+            continue;
+        }
+
+        let start = i + 1;
+        let mut end = start;
+        while let Some(&(j, next)) = chars.peek().filter(|(_, next)| is_tag_char(*next)) {
+            end = j + next.len_utf8();
+            prev = Some(next);
+            chars.next();
+        }
+
+        let tag = content[start..end].trim_end_matches(['-', '/']);
+        if tag.chars().any(char::is_alphabetic) {
+            spans.push((i..start + tag.len(), tag));
+        }
+    }
+
+    spans
+}
+
+/// Replaces `#from` hashtags (compared case-insensitively) in `content` with
+/// `#to`. Returns `None` if there were none.
+fn retag_hashtags(content: &str, from: &str, to: &str) -> Option<String> {
+    let from = from.to_lowercase();
+    let ranges: Vec<_> = hashtag_spans(content)
+        .into_iter()
+        .filter(|(_, tag)| tag.to_lowercase() == from)
+        .map(|(range, _)| range)
+        .collect();
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let mut result = content.to_string();
+    for range in ranges.into_iter().rev() {
+        result.replace_range(range, &format!("#{to}"));
+    }
+    Some(result)
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '/'
+}
+
+// `tags.name` is declared COLLATE NOCASE, so lookups ignore case
+fn find_tag_id(conn: &Connection, name: &str) -> SqlResult<Option<i64>> {
+    let Some(name) = normalize_tag_name(name) else {
+        return Ok(None);
+    };
+    conn.query_row("SELECT id FROM tags WHERE name = ?", [name], |row| row.get(0))
+        .optional()
+}
+
+pub(super) fn get_or_create_tag(conn: &Connection, name: &str) -> SqlResult<i64> {
+    if let Some(id) = find_tag_id(conn, name)? {
+        return Ok(id);
+    }
+    conn.execute(
+        "INSERT INTO tags (name, created_at) VALUES (?, ?)",
+        rusqlite::params![name, Utc::now().to_rfc3339()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Rewrites `#from` hashtags to `#to` in every unlocked note tagged
+/// `tag_id`, keeping a revision of each. Nothing is rewritten when `to`
+/// can't be written as a hashtag. Returns each tagged note's id, the source
+/// of its tag row and whether its content was rewritten.
+fn retag_notes(
+    conn: &Connection,
+    tag_id: i64,
+    from: &str,
+    to: &str,
+    now: &str,
+) -> SqlResult<Vec<(String, String, bool)>> {
+    let to_is_hashtag = extract_hashtags(&format!("#{to}")) == [to];
+
+    let tagged = {
+        let mut stmt = conn.prepare(
+            "SELECT nt.note_id, nt.source, n.content FROM note_tags nt
+             JOIN notes n ON n.id = nt.note_id WHERE nt.tag_id = ?",
+        )?;
+        let rows = stmt
+            .query_map([tag_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<SqlResult<Vec<_>>>()?;
+        rows
+    };
+
+    let mut notes = Vec::with_capacity(tagged.len());
+    for (note_id, tag_source, content) in tagged {
+        let new_content = if to_is_hashtag && !locks::is_locked(conn, &note_id)? {
+            retag_hashtags(&content, from, to)
+        } else {
+            None
+        };
+        if let Some(new_content) = &new_content {
+            revisions::snapshot_before_edit(conn, &note_id, None, Some(new_content), now, "edit")?;
+            conn.execute(
+                "UPDATE notes SET content = ?, updated_at = ? WHERE id = ?",
+                rusqlite::params![new_content, now, note_id],
+            )?;
+        }
+        notes.push((note_id, tag_source, new_content.is_some()));
+    }
+    Ok(notes)
+}
+
+fn tag_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT),
+        Some(message),
+    )
+}
+
+/// Makes the note's hashtag-sourced tags match the hashtags in `content`.
+/// Tags added by hand are never touched.
+pub(super) fn sync_hashtags(conn: &Connection, note_id: &str, content: &str) -> SqlResult<()> {
+    let hashtags = extract_hashtags(content);
+
+    let mut tag_ids = Vec::with_capacity(hashtags.len());
+    for name in &hashtags {
+        let tag_id = get_or_create_tag(conn, name)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO note_tags (note_id, tag_id, source) VALUES (?, ?, 'hashtag')",
+            rusqlite::params![note_id, tag_id],
+        )?;
+        tag_ids.push(tag_id);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT tag_id FROM note_tags WHERE note_id = ? AND source = 'hashtag'",
+    )?;
+    let existing = stmt
+        .query_map([note_id], |row| row.get::<_, i64>(0))?
+        .collect::<SqlResult<Vec<_>>>()?;
+
+    for tag_id in existing.into_iter().filter(|id| !tag_ids.contains(id)) {
+        conn.execute(
+            "DELETE FROM note_tags WHERE note_id = ? AND tag_id = ?",
+            rusqlite::params![note_id, tag_id],
+        )?;
+    }
+
+    Ok(())
+}
+
+impl Database {
+    pub fn list_tags(&self) -> SqlResult<Vec<Tag>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.name, COUNT(n.id)
+             FROM tags t
+             LEFT JOIN note_tags nt ON nt.tag_id = t.id
+             LEFT JOIN notes n ON n.id = nt.note_id AND n.deleted_at IS NULL
+             GROUP BY t.id
+             ORDER BY t.name COLLATE NOCASE",
+        )?;
+        let tags = stmt
+            .query_map([], |row| {
+                Ok(Tag {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    note_count: row.get(2)?,
+                })
+            })?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(tags)
+    }
+
+    /// Names of the tags on a note, alphabetically.
+    pub fn get_note_tags(&self, note_id: &str) -> SqlResult<Vec<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT t.name FROM tags t
+             JOIN note_tags nt ON nt.tag_id = t.id
+             WHERE nt.note_id = ?
+             ORDER BY t.name COLLATE NOCASE",
+        )?;
+        let names = stmt
+            .query_map([note_id], |row| row.get(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(names)
+    }
+
+    /// Tags a note by hand, creating the tag if it does not exist yet.
+    pub fn add_note_tag(&self, note_id: &str, name: &str) -> SqlResult<()> {
+        let name = normalize_tag_name(name)
+            .ok_or_else(|| tag_error("tag name cannot be empty".to_string()))?;
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let tag_id = get_or_create_tag(&tx, &name)?;
+        // A hand-added tag is promoted to manual so hashtag sync leaves it alone
+        tx.execute(
+            "INSERT INTO note_tags (note_id, tag_id, source) VALUES (?, ?, 'manual')
+             ON CONFLICT (note_id, tag_id) DO UPDATE SET source = 'manual'",
+            rusqlite::params![note_id, tag_id],
+        )?;
+        tx.commit()
+    }
+
+    pub fn remove_note_tag(&self, note_id: &str, name: &str) -> SqlResult<()> {
+        let conn = self.conn()?;
+        if let Some(tag_id) = find_tag_id(&conn, name)? {
+            conn.execute(
+                "DELETE FROM note_tags WHERE note_id = ? AND tag_id = ?",
+                rusqlite::params![note_id, tag_id],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Renames a tag. Fails if another tag already has the new name; use
+    /// `merge_tags` to combine them instead. `#old` hashtags in note content
+    /// are rewritten to `#new` so hashtag sync doesn't bring the old name
+    /// back; where that isn't possible the note keeps the tag as a
+    /// hand-added one. Returns the ids of the notes whose content was
+    /// rewritten.
+    pub fn rename_tag(&self, old_name: &str, new_name: &str) -> SqlResult<Vec<String>> {
+        let new_name = normalize_tag_name(new_name)
+            .ok_or_else(|| tag_error("tag name cannot be empty".to_string()))?;
+        let now = Utc::now().to_rfc3339();
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let tag_id = find_tag_id(&tx, old_name)?
+            .ok_or_else(|| tag_error(format!("tag not found: {old_name}")))?;
+
+        if let Some(existing) = find_tag_id(&tx, &new_name)? {
+            if existing != tag_id {
+                return Err(tag_error(format!("tag already exists: {new_name}")));
+            }
+        }
+        let current: String = tx.query_row("SELECT name FROM tags WHERE id = ?", [tag_id], |row| row.get(0))?;
+
+        tx.execute(
+            "UPDATE tags SET name = ? WHERE id = ?",
+            rusqlite::params![new_name, tag_id],
+        )?;
+
+        let mut rewritten = Vec::new();
+        for (note_id, tag_source, retagged) in retag_notes(&tx, tag_id, &current, &new_name, &now)? {
+            if retagged {
+                rewritten.push(note_id);
+            } else if tag_source == "hashtag" {
+                tx.execute(
+                    "UPDATE note_tags SET source = 'manual' WHERE note_id = ? AND tag_id = ?",
+                    rusqlite::params![note_id, tag_id],
+                )?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(rewritten)
+    }
+
+    /// Moves every note tagged `source` over to `target` and deletes `source`.
+    /// `target` is created if needed. `#source` hashtags in note content are
+    /// rewritten to `#target`, so hashtag sync doesn't bring `source` back;
+    /// where that isn't possible (locked notes, or a target that can't be
+    /// written as a hashtag) the note keeps `target` as a hand-added tag.
+    /// Returns the ids of the notes whose content was rewritten.
+    pub fn merge_tags(&self, source: &str, target: &str) -> SqlResult<Vec<String>> {
+        let target = normalize_tag_name(target)
+            .ok_or_else(|| tag_error("tag name cannot be empty".to_string()))?;
+        let now = Utc::now().to_rfc3339();
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let source_id = find_tag_id(&tx, source)?
+            .ok_or_else(|| tag_error(format!("tag not found: {source}")))?;
+        let target_id = get_or_create_tag(&tx, &target)?;
+        if source_id == target_id {
+            return Ok(Vec::new());
+        }
+        let source_name: String = tx.query_row("SELECT name FROM tags WHERE id = ?", [source_id], |row| row.get(0))?;
+
+        let mut rewritten = Vec::new();
+        for (note_id, tag_source, retagged) in retag_notes(&tx, source_id, &source_name, &target, &now)? {
+            // A hashtag row only stays one if the hashtag now names the target
+            let tag_source = if tag_source == "hashtag" && !retagged { "manual" } else { tag_source.as_str() };
+            tx.execute(
+                "INSERT INTO note_tags (note_id, tag_id, source) VALUES (?, ?, ?)
+                 ON CONFLICT (note_id, tag_id) DO UPDATE SET
+                     source = CASE WHEN excluded.source = 'manual' THEN 'manual' ELSE note_tags.source END",
+                rusqlite::params![note_id, target_id, tag_source],
+            )?;
+            if retagged {
+                rewritten.push(note_id);
+            }
+        }
+        tx.execute("DELETE FROM tags WHERE id = ?", [source_id])?;
+
+        tx.commit()?;
+        Ok(rewritten)
+    }
+
+    pub fn delete_tag(&self, name: &str) -> SqlResult<()> {
+        let conn = self.conn()?;
+        if let Some(tag_id) = find_tag_id(&conn, name)? {
+            conn.execute("DELETE FROM tags WHERE id = ?", [tag_id])?;
+        }
+        Ok(())
+    }
+
+    /// Non-trashed notes carrying the given tags. With `match_all` a note
+    /// needs every tag (AND); otherwise any one of them is enough (OR).
+    pub fn get_notes_by_tags(&self, names: &[String], match_all: bool) -> SqlResult<Vec<Note>> {
+        let mut names: Vec<String> = names.iter().filter_map(|n| normalize_tag_name(n)).collect();
+        names.sort_by_key(|n| n.to_lowercase());
+        names.dedup_by_key(|n| n.to_lowercase());
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let required = if match_all { names.len() } else { 1 };
+        let placeholders = vec!["?"; names.len()].join(", ");
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {NOTE_COLUMNS} FROM notes
+             WHERE deleted_at IS NULL AND id IN (
+                 SELECT nt.note_id FROM note_tags nt
+                 JOIN tags t ON t.id = nt.tag_id
+                 WHERE t.name IN ({placeholders})
+                 GROUP BY nt.note_id
+                 HAVING COUNT(DISTINCT t.id) >= {required}
+             )
+             ORDER BY created_at"
+        ))?;
+
+        let notes = stmt
+            .query_map(rusqlite::params_from_iter(names.iter()), row_to_note)?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        Ok(notes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    #[test]
+    fn retags_only_whole_hashtags() {
+        let content = "#Old and #old/sub, (#old) not#old #older # old";
+        assert_eq!(
+            retag_hashtags(content, "old", "new").as_deref(),
+            Some("#new and #old/sub, (#new) not#old #older # old"),
+        );
+        assert_eq!(retag_hashtags("no tags", "old", "new"), None);
+        assert_eq!(extract_hashtags(content), ["Old", "old/sub", "older"]);
+    }
+
+    #[test]
+    fn merged_hashtags_do_not_bring_the_source_back() {
+        let db = TempDatabase::new();
+        db.set_setting("extract_hashtags", "true").unwrap();
+        let note = db.create_note(0, 0).unwrap();
+        db.update_note(&note.id, None, Some("buy #groceries today"), None, None, None, None, None, None, None)
+            .unwrap();
+
+        let rewritten = db.merge_tags("groceries", "shopping").unwrap();
+        assert_eq!(rewritten, [note.id.as_str()]);
+        let note = db.get_note(&note.id).unwrap().unwrap();
+        assert_eq!(note.content, "buy #shopping today");
+
+        db.update_note(&note.id, None, Some("buy #shopping tomorrow"), None, None, None, None, None, None, None)
+            .unwrap();
+        let names: Vec<_> = db.list_tags().unwrap().into_iter().map(|tag| tag.name).collect();
+        assert_eq!(names, ["shopping"]);
+        assert_eq!(db.get_note_tags(&note.id).unwrap(), ["shopping"]);
+    }
+
+    #[test]
+    fn merging_into_a_non_hashtag_name_keeps_the_tag_by_hand() {
+        let db = TempDatabase::new();
+        db.set_setting("extract_hashtags", "true").unwrap();
+        let note = db.create_note(0, 0).unwrap();
+        db.update_note(&note.id, None, Some("#todo"), None, None, None, None, None, None, None).unwrap();
+
+        assert!(db.merge_tags("todo", "to do").unwrap().is_empty());
+        db.update_note(&note.id, None, Some("nothing"), None, None, None, None, None, None, None).unwrap();
+        assert_eq!(db.get_note_tags(&note.id).unwrap(), ["to do"]);
+    }
+
+    #[test]
+    fn renamed_hashtags_do_not_bring_the_old_name_back() {
+        let db = TempDatabase::new();
+        db.set_setting("extract_hashtags", "true").unwrap();
+        let note = db.create_note(0, 0).unwrap();
+        db.update_note(&note.id, None, Some("call #Mom later"), None, None, None, None, None, None, None)
+            .unwrap();
+        let locked = db.create_note(0, 0).unwrap();
+        db.update_note(&locked.id, None, Some("#mom secret"), None, None, None, None, None, None, None)
+            .unwrap();
+        db.lock_note(&locked.id, "pass").unwrap();
+
+        let rewritten = db.rename_tag("mom", "family").unwrap();
+        assert_eq!(rewritten, [note.id.as_str()]);
+        assert_eq!(db.get_note(&note.id).unwrap().unwrap().content, "call #family later");
+
+        db.update_note(&note.id, None, Some("call #family tomorrow"), None, None, None, None, None, None, None)
+            .unwrap();
+        let names: Vec<_> = db.list_tags().unwrap().into_iter().map(|tag| tag.name).collect();
+        assert_eq!(names, ["family"]);
+        assert_eq!(db.get_note_tags(&note.id).unwrap(), ["family"]);
+        // The locked note can't be rewritten, so it keeps the tag by hand
+        db.unlock_note(&locked.id, "pass").unwrap();
+        assert_eq!(db.get_note_tags(&locked.id).unwrap(), ["family"]);
+    }
+}
@@ -0,0 +1,162 @@
+use chrono::{DateTime, Local, Utc};
+use rusqlite::{OptionalExtension, Result as SqlResult};
+use uuid::Uuid;
+
+use super::{insert_note, row_to_note, tags, Database, Note, NOTE_COLUMNS};
+
+fn template_error(message: &str) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT),
+        Some(message.to_string()),
+    )
+}
+
+/// Replaces `{{date}}`, `{{time}}` and `{{weekday}}` with `now` in local
+/// time. Other `{{...}}` text is left as is.
+pub fn expand_placeholders(text: &str, now: DateTime<Local>) -> String {
+    text.replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+        .replace("{{weekday}}", &now.format("%A").to_string())
+}
+
+impl Database {
+    /// Marks a note as a template, or back as an ordinary note.
+    pub fn set_note_template(&self, id: &str, is_template: bool) -> SqlResult<()> {
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE notes SET is_template = ? WHERE id = ?",
+            rusqlite::params![is_template as i32, id],
+        )?;
+        if updated == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        Ok(())
+    }
+
+    /// Templates outside the trash, by title.
+    pub fn list_templates(&self) -> SqlResult<Vec<Note>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {NOTE_COLUMNS} FROM notes
+             WHERE is_template = 1 AND deleted_at IS NULL
+             ORDER BY title COLLATE NOCASE"
+        ))?;
+        let notes = stmt.query_map([], row_to_note)?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(notes)
+    }
+
+    /// Creates an open note at the given position from a template: its title
+    /// and content with placeholders expanded, plus its mode, size, opacity
+    /// and always-on-top setting.
+    pub fn create_note_from_template(&self, template_id: &str, pos_x: i32, pos_y: i32) -> SqlResult<Note> {
+        let extract_hashtags = self.get_settings()?.extract_hashtags;
+        let mut conn = self.conn()?;
+
+        let template = conn
+            .query_row(
+                &format!("SELECT {NOTE_COLUMNS} FROM notes WHERE id = ? AND deleted_at IS NULL"),
+                [template_id],
+                row_to_note,
+            )
+            .optional()?
+            .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        if !template.is_template {
+            return Err(template_error("note is not a template"));
+        }
+        if template.is_locked {
+            return Err(template_error("template is locked"));
+        }
+
+        let local = Local::now();
+        let now = local.with_timezone(&Utc).to_rfc3339();
+        let note = Note {
+            id: Uuid::new_v4().to_string(),
+            title: expand_placeholders(&template.title, local),
+            content: expand_placeholders(&template.content, local),
+            pos_x,
+            pos_y,
+            is_open: true,
+            is_minimized: false,
+            created_at: now.clone(),
+            updated_at: now,
+            deleted_at: None,
+            notebook_id: None,
+            is_locked: false,
+            is_template: false,
+            ..template
+        };
+
+        let tx = conn.transaction()?;
+        insert_note(&tx, &note)?;
+        if extract_hashtags {
+            tags::sync_hashtags(&tx, &note.id, &note.content)?;
+        }
+        tx.commit()?;
+
+        Ok(note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    #[test]
+    fn expands_known_placeholders_only() {
+        // 2026-01-09 is a Friday
+        let now = Local.with_ymd_and_hms(2026, 1, 9, 14, 5, 0).unwrap();
+        assert_eq!(
+            expand_placeholders("{{weekday}} {{date}} at {{time}}, {{name}}", now),
+            "Friday 2026-01-09 at 14:05, {{name}}"
+        );
+    }
+
+    #[test]
+    fn notes_from_templates_carry_their_look() {
+        let db = TempDatabase::new();
+        let template = db.create_note(0, 0).unwrap();
+        db.update_note(
+            &template.id,
+            Some("Standup {{date}}"),
+            Some("- [ ] yesterday"),
+            Some("todo"),
+            None,
+            None,
+            Some(420),
+            Some(260),
+            Some(0.5),
+            Some(false),
+        )
+        .unwrap();
+        db.set_note_template(&template.id, true).unwrap();
+
+        let note = db.create_note_from_template(&template.id, 40, 50).unwrap();
+        assert_ne!(note.id, template.id);
+        assert!(note.title.starts_with("Standup 20"));
+        assert_eq!(note.content, "- [ ] yesterday");
+        assert_eq!((note.mode.as_str(), note.width, note.height), ("todo", 420, 260));
+        assert_eq!((note.opacity, note.always_on_top), (0.5, false));
+        assert_eq!((note.pos_x, note.pos_y), (40, 50));
+        assert!(note.is_open && !note.is_template);
+
+        let stored = db.get_note(&note.id).unwrap().unwrap();
+        assert_eq!((stored.title, stored.width), (note.title, 420));
+    }
+
+    #[test]
+    fn only_unlocked_templates_can_be_used() {
+        let db = TempDatabase::new();
+        let plain = db.create_note(0, 0).unwrap();
+        assert!(db.create_note_from_template(&plain.id, 0, 0).is_err());
+
+        let locked = db.create_note(0, 0).unwrap();
+        db.set_note_template(&locked.id, true).unwrap();
+        db.lock_note(&locked.id, "pass").unwrap();
+        assert!(db.create_note_from_template(&locked.id, 0, 0).is_err());
+
+        assert_eq!(db.get_all_notes().unwrap().len(), 2);
+    }
+}
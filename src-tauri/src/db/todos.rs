@@ -0,0 +1,236 @@
+use chrono::Utc;
+use rusqlite::{Result as SqlResult, Transaction, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+use super::{links, locks, revisions, tags, Database, Note};
+
+const CHECKED: &str = "- [x] ";
+const UNCHECKED: &str = "- [ ] ";
+
+/// One line of a todo-mode note.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub checked: bool,
+    pub text: String,
+}
+
+pub(super) fn todo_error(code: std::os::raw::c_int, message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(code), Some(message))
+}
+
+pub(super) fn index_error(index: usize) -> rusqlite::Error {
+    todo_error(rusqlite::ffi::SQLITE_RANGE, format!("no todo item at index {index}"))
+}
+
+/// Reads todo-mode content the same way the todo view does: one item per
+/// line, `- [x] ` / `- [ ] ` marking its state. Lines without a marker are
+/// unchecked items. Blank content is a single empty item, which the todo
+/// view shows for it, so indices match what the user sees.
+pub fn parse_todos(content: &str) -> Vec<TodoItem> {
+    if content.trim().is_empty() {
+        return vec![TodoItem { checked: false, text: String::new() }];
+    }
+
+    content
+        .split('\n')
+        .map(|line| {
+            if let Some(text) = line.strip_prefix(CHECKED) {
+                TodoItem { checked: true, text: text.to_string() }
+            } else if let Some(text) = line.strip_prefix(UNCHECKED) {
+                TodoItem { checked: false, text: text.to_string() }
+            } else {
+                TodoItem { checked: false, text: line.to_string() }
+            }
+        })
+        .collect()
+}
+
+/// Whether `items` is the lone empty item of a blank list.
+pub(super) fn is_blank_list(items: &[TodoItem]) -> bool {
+    matches!(items, [only] if !only.checked && only.text.is_empty())
+}
+
+pub fn serialize_todos(items: &[TodoItem]) -> String {
+    items
+        .iter()
+        .map(|item| format!("{}{}", if item.checked { CHECKED } else { UNCHECKED }, item.text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Writes `items` back as the content of note `id` when they differ from
+/// the items of `content`, keeping a revision of the old content plus links and hashtags
+/// in step. Returns whether anything changed.
+pub(super) fn save_todos(
+    tx: &Transaction,
+    id: &str,
+    content: &str,
+    items: &[TodoItem],
+    now: &str,
+    extract_hashtags: bool,
+    source: &str,
+) -> SqlResult<bool> {
+    if parse_todos(content) == items {
+        return Ok(false);
+    }
+    let updated = serialize_todos(items);
+
+    revisions::snapshot_before_edit(tx, id, None, Some(&updated), now, source)?;
+    tx.execute(
+        "UPDATE notes SET content = ?, updated_at = ? WHERE id = ?",
+        rusqlite::params![updated, now, id],
+    )?;
+    links::sync_links(tx, id, &updated)?;
+    if extract_hashtags {
+        tags::sync_hashtags(tx, id, &updated)?;
+    }
+    Ok(true)
+}
+
+/// Content of a todo-mode note that may be edited.
+pub(super) fn editable_todo_content(tx: &Transaction, id: &str) -> SqlResult<String> {
+    let (mode, content): (String, String) = tx
+        .query_row("SELECT mode, content FROM notes WHERE id = ?", [id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+    if locks::is_locked(tx, id)? {
+        return Err(locks::locked_error());
+    }
+    if mode != "todo" {
+        return Err(todo_error(rusqlite::ffi::SQLITE_MISMATCH, "note is not a todo list".to_string()));
+    }
+    Ok(content)
+}
+
+impl Database {
+    /// Applies `edit` to the items of a todo-mode note and saves the result,
+    /// all in one write transaction so nothing else can change the note in
+    /// between. Returns the updated note.
+    fn edit_todos(&self, id: &str, edit: impl FnOnce(&mut Vec<TodoItem>) -> SqlResult<()>) -> SqlResult<Note> {
+        let now = Utc::now().to_rfc3339();
+        let extract_hashtags = self.get_settings()?.extract_hashtags;
+
+        {
+            let mut conn = self.conn()?;
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+            let content = editable_todo_content(&tx, id)?;
+            let mut items = parse_todos(&content);
+            edit(&mut items)?;
+            save_todos(&tx, id, &content, &items, &now, extract_hashtags, "edit")?;
+
+            tx.commit()?;
+        }
+
+        self.get_note(id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+    }
+
+    pub fn toggle_todo(&self, id: &str, index: usize) -> SqlResult<Note> {
+        self.edit_todos(id, |items| {
+            let item = items.get_mut(index).ok_or_else(|| index_error(index))?;
+            item.checked = !item.checked;
+            Ok(())
+        })
+    }
+
+    /// Adds an unchecked item at `index`, or at the end. On a blank list it
+    /// takes the place of the lone empty item.
+    pub fn add_todo(&self, id: &str, text: &str, index: Option<usize>) -> SqlResult<Note> {
+        // Each item is a single line
+        let text = text.replace(['\r', '\n'], " ");
+        self.edit_todos(id, |items| {
+            let index = index.unwrap_or(items.len());
+            if index > items.len() {
+                return Err(index_error(index));
+            }
+            if is_blank_list(items) {
+                items.clear();
+                items.push(TodoItem { checked: false, text });
+            } else {
+                items.insert(index, TodoItem { checked: false, text });
+            }
+            Ok(())
+        })
+    }
+
+    pub fn remove_todo(&self, id: &str, index: usize) -> SqlResult<Note> {
+        self.edit_todos(id, |items| {
+            if index >= items.len() {
+                return Err(index_error(index));
+            }
+            items.remove(index);
+            Ok(())
+        })
+    }
+
+    /// Moves the item at `from` so it ends up at index `to`.
+    pub fn move_todo(&self, id: &str, from: usize, to: usize) -> SqlResult<Note> {
+        self.edit_todos(id, |items| {
+            if from >= items.len() {
+                return Err(index_error(from));
+            }
+            if to >= items.len() {
+                return Err(index_error(to));
+            }
+            let item = items.remove(from);
+            items.insert(to, item);
+            Ok(())
+        })
+    }
+
+    /// Removes every checked item.
+    pub fn clear_completed(&self, id: &str) -> SqlResult<Note> {
+        self.edit_todos(id, |items| {
+            items.retain(|item| !item.checked);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+    use super::*;
+
+    fn item(checked: bool, text: &str) -> TodoItem {
+        TodoItem { checked, text: text.to_string() }
+    }
+
+    #[test]
+    fn blank_content_is_one_empty_item() {
+        for content in ["", "  ", "\n\n"] {
+            assert_eq!(parse_todos(content), [item(false, "")]);
+        }
+    }
+
+    #[test]
+    fn parse_and_serialize_round_trip() {
+        let content = "- [x] done\n- [ ] open\n- [ ] \n- [ ] - [x] nested marker";
+        let items = parse_todos(content);
+        assert_eq!(
+            items,
+            [item(true, "done"), item(false, "open"), item(false, ""), item(false, "- [x] nested marker")]
+        );
+        assert_eq!(serialize_todos(&items), content);
+        assert_eq!(parse_todos(&serialize_todos(&items)), items);
+    }
+
+    #[test]
+    fn unmarked_lines_are_unchecked_items() {
+        assert_eq!(parse_todos("milk\n- [x] eggs"), [item(false, "milk"), item(true, "eggs")]);
+        assert_eq!(serialize_todos(&parse_todos("milk")), "- [ ] milk");
+    }
+
+    #[test]
+    fn blank_list_edits_match_the_todo_view() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        db.update_note(&note.id, None, None, Some("todo"), None, None, None, None, None, None).unwrap();
+
+        // Nothing to clear leaves the note as it was
+        assert_eq!(db.clear_completed(&note.id).unwrap().content, "");
+        assert_eq!(db.toggle_todo(&note.id, 0).unwrap().content, "- [x] ");
+        db.update_note(&note.id, None, Some(""), None, None, None, None, None, None, None).unwrap();
+        assert_eq!(db.add_todo(&note.id, "first", None).unwrap().content, "- [ ] first");
+    }
+}
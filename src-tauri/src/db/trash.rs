@@ -0,0 +1,102 @@
+use chrono::{Duration, Utc};
+use rusqlite::Result as SqlResult;
+
+use super::{row_to_note, Database, Note, NOTE_COLUMNS};
+
+impl Database {
+    /// Notes in the trash, most recently deleted first.
+    pub fn list_trash(&self) -> SqlResult<Vec<Note>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {NOTE_COLUMNS} FROM notes
+             WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+        ))?;
+
+        let notes = stmt.query_map([], row_to_note)?.collect::<SqlResult<Vec<_>>>()?;
+
+        Ok(notes)
+    }
+
+    /// Takes a note back out of the trash. It comes back closed. Returns
+    /// `false` if the note was not in the trash.
+    pub fn restore_note(&self, id: &str) -> SqlResult<bool> {
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn()?;
+        let changed = conn.execute(
+            "UPDATE notes SET deleted_at = NULL, updated_at = ?
+             WHERE id = ? AND deleted_at IS NOT NULL",
+            rusqlite::params![now, id],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Permanently deletes every trashed note, along with attachment files
+    /// nothing else refers to. Returns how many notes were removed.
+    pub fn empty_trash(&self) -> SqlResult<usize> {
+        let removed = {
+            let conn = self.conn()?;
+            conn.execute("DELETE FROM notes WHERE deleted_at IS NOT NULL", [])?
+        };
+        self.remove_orphaned_blobs()?;
+        Ok(removed)
+    }
+
+    /// Permanently deletes trashed notes older than the `trash_retention_days`
+    /// setting. A retention of zero or less, or one reaching back before the
+    /// earliest representable date, keeps trashed notes forever.
+    pub fn purge_expired_trash(&self) -> SqlResult<usize> {
+        let retention_days = self.get_settings()?.trash_retention_days;
+        if retention_days <= 0 {
+            return Ok(0);
+        }
+
+        let Some(cutoff) = Duration::try_days(retention_days).and_then(|d| Utc::now().checked_sub_signed(d)) else {
+            return Ok(0);
+        };
+        let cutoff = cutoff.to_rfc3339();
+        let removed = {
+            let conn = self.conn()?;
+            conn.execute(
+                "DELETE FROM notes WHERE deleted_at IS NOT NULL AND deleted_at < ?",
+                [cutoff],
+            )?
+        };
+        if removed > 0 {
+            self.remove_orphaned_blobs()?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+
+    #[test]
+    fn huge_retention_keeps_trash_forever() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        db.delete_note(&note.id).unwrap();
+
+        for days in [i64::MAX, i64::MAX / 86_400, 1_000_000_000] {
+            db.set_setting("trash_retention_days", &days.to_string()).unwrap();
+            assert_eq!(db.purge_expired_trash().unwrap(), 0);
+        }
+        assert_eq!(db.list_trash().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn purges_notes_past_retention() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        db.delete_note(&note.id).unwrap();
+        db.conn()
+            .unwrap()
+            .execute("UPDATE notes SET deleted_at = '2000-01-01T00:00:00+00:00' WHERE id = ?", [&note.id])
+            .unwrap();
+
+        db.set_setting("trash_retention_days", "30").unwrap();
+        assert_eq!(db.purge_expired_trash().unwrap(), 1);
+        assert!(db.list_trash().unwrap().is_empty());
+    }
+}
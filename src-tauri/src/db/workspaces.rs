@@ -0,0 +1,175 @@
+use chrono::Utc;
+use rusqlite::{OptionalExtension, Result as SqlResult};
+use serde::{Deserialize, Serialize};
+
+use super::Database;
+
+/// A saved set of open notes and where their windows were.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workspace {
+    pub name: String,
+    pub note_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+fn workspace_error(message: &str) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT),
+        Some(message.to_string()),
+    )
+}
+
+const WORKSPACE_QUERY: &str = "SELECT w.name, count(wn.note_id), w.created_at, w.updated_at
+    FROM workspaces w LEFT JOIN workspace_notes wn ON wn.workspace = w.name";
+
+fn row_to_workspace(row: &rusqlite::Row) -> SqlResult<Workspace> {
+    Ok(Workspace {
+        name: row.get(0)?,
+        note_count: row.get(1)?,
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+    })
+}
+
+impl Database {
+    /// Saves the notes that are open now, with their geometry and opacity,
+    /// under `name`, replacing a workspace of the same name.
+    pub fn save_workspace(&self, name: &str) -> SqlResult<Workspace> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(workspace_error("workspace name is empty"));
+        }
+        let now = Utc::now().to_rfc3339();
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO workspaces (name, created_at, updated_at) VALUES (?1, ?2, ?2)
+             ON CONFLICT (name) DO UPDATE SET updated_at = excluded.updated_at",
+            rusqlite::params![name, now],
+        )?;
+        tx.execute("DELETE FROM workspace_notes WHERE workspace = ?", [name])?;
+        let saved = tx.execute(
+            "INSERT INTO workspace_notes (workspace, note_id, pos_x, pos_y, width, height, opacity)
+             SELECT ?, id, pos_x, pos_y, width, height, opacity FROM notes
+             WHERE is_open = 1 AND deleted_at IS NULL",
+            [name],
+        )?;
+        if saved == 0 {
+            return Err(workspace_error("no notes are open"));
+        }
+
+        let workspace = tx.query_row(
+            &format!("{WORKSPACE_QUERY} WHERE w.name = ? GROUP BY w.name"),
+            [name],
+            row_to_workspace,
+        )?;
+        tx.commit()?;
+        Ok(workspace)
+    }
+
+    pub fn list_workspaces(&self) -> SqlResult<Vec<Workspace>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "{WORKSPACE_QUERY} GROUP BY w.name ORDER BY w.name COLLATE NOCASE"
+        ))?;
+        let workspaces = stmt.query_map([], row_to_workspace)?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(workspaces)
+    }
+
+    pub fn delete_workspace(&self, name: &str) -> SqlResult<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM workspaces WHERE name = ?", [name.trim()])?;
+        Ok(())
+    }
+
+    /// Opens exactly the workspace's notes, back where they were saved, and
+    /// closes every other note. Notes deleted or trashed since are skipped.
+    /// The caller brings the windows in line with `is_open`.
+    pub fn apply_workspace(&self, name: &str) -> SqlResult<()> {
+        let now = Utc::now().to_rfc3339();
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+
+        let exists = tx
+            .query_row("SELECT 1 FROM workspaces WHERE name = ?", [name.trim()], |_| Ok(()))
+            .optional()?;
+        if exists.is_none() {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        tx.execute(
+            "UPDATE notes SET is_open = 0, updated_at = ?1
+             WHERE is_open = 1 AND id NOT IN (SELECT note_id FROM workspace_notes WHERE workspace = ?2)",
+            rusqlite::params![now, name.trim()],
+        )?;
+        tx.execute(
+            "UPDATE notes SET
+                 is_open = 1,
+                 pos_x = wn.pos_x, pos_y = wn.pos_y,
+                 width = wn.width, height = wn.height,
+                 opacity = wn.opacity,
+                 updated_at = ?1
+             FROM workspace_notes wn
+             WHERE wn.workspace = ?2 AND wn.note_id = notes.id AND notes.deleted_at IS NULL",
+            rusqlite::params![now, name.trim()],
+        )?;
+
+        tx.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TempDatabase;
+
+    fn move_to(db: &TempDatabase, id: &str, x: i32, width: i32) {
+        db.update_note(id, None, None, None, Some(x), Some(x), Some(width), Some(width), None, None).unwrap();
+    }
+
+    #[test]
+    fn saving_needs_open_notes_and_replaces_by_name() {
+        let db = TempDatabase::new();
+        let note = db.create_note(0, 0).unwrap();
+        db.close_note(&note.id).unwrap();
+        assert!(db.save_workspace("Focus").is_err());
+        assert!(db.list_workspaces().unwrap().is_empty());
+
+        db.open_note(&note.id).unwrap();
+        assert_eq!(db.save_workspace(" Focus ").unwrap().note_count, 1);
+        let other = db.create_note(0, 0).unwrap();
+        let saved = db.save_workspace("Focus").unwrap();
+        assert_eq!((saved.name.as_str(), saved.note_count), ("Focus", 2));
+        assert_eq!(db.list_workspaces().unwrap().len(), 1);
+
+        // Deleted notes drop out of the workspace
+        db.delete_note(&other.id).unwrap();
+        db.empty_trash().unwrap();
+        assert_eq!(db.list_workspaces().unwrap()[0].note_count, 1);
+    }
+
+    #[test]
+    fn applying_restores_the_saved_layout() {
+        let db = TempDatabase::new();
+        let kept = db.create_note(0, 0).unwrap();
+        let trashed = db.create_note(0, 0).unwrap();
+        move_to(&db, &kept.id, 100, 400);
+        db.save_workspace("Desk").unwrap();
+
+        move_to(&db, &kept.id, 500, 250);
+        db.close_note(&kept.id).unwrap();
+        db.delete_note(&trashed.id).unwrap();
+        let stray = db.create_note(0, 0).unwrap();
+
+        db.apply_workspace("Desk").unwrap();
+        let kept = db.get_note(&kept.id).unwrap().unwrap();
+        assert!(kept.is_open);
+        assert_eq!((kept.pos_x, kept.pos_y, kept.width, kept.height), (100, 100, 400, 400));
+        assert!(!db.get_note(&stray.id).unwrap().unwrap().is_open);
+        let trashed = db.get_note(&trashed.id).unwrap().unwrap();
+        assert!(!trashed.is_open && trashed.deleted_at.is_some());
+
+        assert!(db.apply_workspace("Missing").is_err());
+    }
+}
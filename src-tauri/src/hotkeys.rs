@@ -38,10 +38,10 @@ pub fn register_hotkeys(app: &AppHandle) -> Result<(), String> {
         let visible = NOTES_VISIBLE.load(Ordering::SeqCst);
 
         if visible {
-            close_all_note_windows(app);
+            close_all_note_windows(app, None);
             NOTES_VISIBLE.store(false, Ordering::SeqCst);
         } else {
-            show_all_note_windows(app);
+            show_all_note_windows(app, None);
             NOTES_VISIBLE.store(true, Ordering::SeqCst);
         }
     }).map_err(|e| e.to_string())?;
@@ -0,0 +1,231 @@
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use interprocess::local_socket::{prelude::*, GenericFilePath, GenericNamespaced, Listener, ListenerOptions, Name, Stream};
+use tauri::{AppHandle, Manager};
+
+use crate::commands;
+use crate::db::Database;
+use crate::note_window::{create_note_window, focus_note_window};
+
+/// What a launch asks the running app to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// `--new [text]`
+    New(Option<String>),
+    /// `--show-all`, and a plain second launch
+    ShowAll,
+    /// `--open <id>`
+    Open(String),
+}
+
+/// How long a later launch waits for the running app to carry out its
+/// arguments before giving up.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the running app waits for a connected launch to send its
+/// arguments, so a stuck client can't block later launches.
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Tries at claiming the socket before giving up, in case another launch
+/// starts listening at the same moment.
+const CLAIM_ATTEMPTS: u32 = 3;
+const CLAIM_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Arguments received while an encrypted database is still locked. They are
+/// carried out by `run_pending_args` once it is unlocked.
+#[derive(Default)]
+pub struct PendingArgs(Mutex<Vec<Vec<String>>>);
+
+/// Outcome of `claim`.
+pub enum Claim {
+    /// This is the only instance; it should serve `listener`.
+    Primary(Listener),
+    /// Another instance is running and has handled the arguments.
+    Forwarded,
+}
+
+/// Socket the primary instance listens on, one per user.
+fn socket_name() -> std::io::Result<Name<'static>> {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+    let name = format!("com.hoverthought.hud.{user}.sock");
+
+    if GenericNamespaced::is_supported() {
+        name.to_ns_name::<GenericNamespaced>()
+    } else {
+        // macOS has no abstract sockets; its temp dir is per user anyway
+        std::env::temp_dir().join(name).to_fs_name::<GenericFilePath>()
+    }
+}
+
+/// Parses `--new [text]`, `--show-all` and `--open <id>`. Anything else is
+/// ignored so unrelated flags passed by the OS don't stop the launch.
+pub fn parse_args(args: &[String]) -> Vec<Action> {
+    let mut actions = Vec::new();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--new" => {
+                let text = args.next_if(|next| !next.starts_with("--")).cloned();
+                actions.push(Action::New(text));
+            }
+            "--show-all" => actions.push(Action::ShowAll),
+            "--open" => match args.next_if(|next| !next.starts_with("--")) {
+                Some(id) => actions.push(Action::Open(id.clone())),
+                None => eprintln!("--open needs a note id"),
+            },
+            _ => {}
+        }
+    }
+    actions
+}
+
+/// Hands `args` to an already running instance if there is one, or else
+/// starts listening for later launches.
+pub fn claim(args: &[String]) -> std::io::Result<Claim> {
+    let name = socket_name()?;
+
+    let mut attempt = 1;
+    loop {
+        if let Ok(stream) = Stream::connect(name.clone()) {
+            forward(stream, args)?;
+            return Ok(Claim::Forwarded);
+        }
+
+        // Nobody answered, so a leftover socket file is from a crashed instance
+        match ListenerOptions::new().name(name.clone()).try_overwrite(true).create_sync() {
+            Ok(listener) => return Ok(Claim::Primary(listener)),
+            Err(_) if attempt < CLAIM_ATTEMPTS => {
+                attempt += 1;
+                std::thread::sleep(CLAIM_RETRY_DELAY);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Sends `args` to the running instance and prints its reply unless that
+/// is "ok".
+fn forward(stream: Stream, args: &[String]) -> std::io::Result<()> {
+    stream.set_send_timeout(Some(FORWARD_TIMEOUT))?;
+    stream.set_recv_timeout(Some(FORWARD_TIMEOUT))?;
+
+    let mut stream = BufReader::new(stream);
+    let message = serde_json::to_string(args).map_err(std::io::Error::other)?;
+    stream.get_mut().write_all(format!("{message}\n").as_bytes())?;
+
+    let mut reply = String::new();
+    stream.read_line(&mut reply)?;
+    let reply = reply.trim_end();
+    if reply != "ok" {
+        eprintln!("{reply}");
+    }
+    Ok(())
+}
+
+/// Serves later launches: each connection sends its arguments as one JSON
+/// line and gets back "ok" or an error once they were carried out.
+pub fn listen(app: &AppHandle, listener: Listener) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept instance connection: {}", e);
+                    continue;
+                }
+            };
+
+            if stream.set_recv_timeout(Some(RECEIVE_TIMEOUT)).is_err()
+                || stream.set_send_timeout(Some(RECEIVE_TIMEOUT)).is_err()
+            {
+                continue;
+            }
+
+            let mut stream = BufReader::new(stream);
+            let mut line = String::new();
+            if stream.read_line(&mut line).is_err() {
+                continue;
+            }
+            let reply = match serde_json::from_str::<Vec<String>>(&line) {
+                Ok(args) => match run_args(&app, &args) {
+                    Ok(()) => "ok".to_string(),
+                    Err(e) => e,
+                },
+                Err(e) => format!("Invalid message: {e}"),
+            };
+            let _ = stream.get_mut().write_all(format!("{reply}\n").as_bytes());
+        }
+    });
+}
+
+/// Carries out the actions in `args`. A launch without any brings the notes
+/// to the front. While an encrypted database is locked they are queued
+/// instead and the unlock window is brought up.
+pub fn run_args(app: &AppHandle, args: &[String]) -> Result<(), String> {
+    if let Some(pending) = app.try_state::<PendingArgs>() {
+        // Checked under the lock so `run_pending_args` can't miss these
+        let mut pending = pending.0.lock().map_err(|_| "Pending arguments lock poisoned".to_string())?;
+        if app.try_state::<Database>().is_none() {
+            if !args.is_empty() {
+                pending.push(args.to_vec());
+            }
+            if let Some(window) = app.get_webview_window("unlock") {
+                let _ = window.set_focus();
+            }
+            return Ok(());
+        }
+    }
+
+    let mut actions = parse_args(args);
+    if actions.is_empty() {
+        actions.push(Action::ShowAll);
+    }
+
+    for action in actions {
+        run_action(app, action)?;
+    }
+    Ok(())
+}
+
+/// Carries out the arguments queued while the database was locked.
+pub fn run_pending_args(app: &AppHandle) {
+    let pending = match app.try_state::<PendingArgs>() {
+        Some(pending) => pending.0.lock().map(|mut queue| std::mem::take(&mut *queue)).unwrap_or_default(),
+        None => return,
+    };
+    for args in pending {
+        if let Err(e) = run_args(app, &args) {
+            eprintln!("Failed to handle arguments: {}", e);
+        }
+    }
+}
+
+fn run_action(app: &AppHandle, action: Action) -> Result<(), String> {
+    match action {
+        Action::New(text) => {
+            let db = app.state::<Database>();
+            let note = db.create_note(100, 100).map_err(|e| e.to_string())?;
+            if let Some(text) = text {
+                db.update_note(&note.id, None, Some(&text), None, None, None, None, None, None, None)
+                    .map_err(|e| e.to_string())?;
+            }
+            let note = db
+                .get_note(&note.id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| "Note not found".to_string())?;
+            create_note_window(app, &note)?;
+            focus_note_window(app, &note.id);
+        }
+        Action::ShowAll => commands::show_all_notes(app.clone())?,
+        Action::Open(id) => {
+            commands::open_note(app.clone(), id.clone())?;
+            focus_note_window(app, &id);
+        }
+    }
+    Ok(())
+}
@@ -1,52 +1,212 @@
+// Everything but `db` and `crypto` is the desktop app, built with the `app`
+// feature; the `notary` CLI needs only the database.
+#[cfg(feature = "app")]
+mod api;
+#[cfg(feature = "app")]
 mod commands;
+mod crypto;
 mod db;
+#[cfg(feature = "app")]
 mod hotkeys;
+#[cfg(feature = "app")]
+mod instance;
+#[cfg(feature = "app")]
 mod note_window;
+#[cfg(feature = "app")]
+mod reminders;
 
-use tauri::Manager;
+#[cfg(feature = "app")]
+use std::collections::HashMap;
+#[cfg(feature = "app")]
+use std::time::Duration;
+#[cfg(feature = "app")]
+use tauri::{AppHandle, Manager};
 
-pub use db::{Database, Note, Settings};
+pub use db::{ArchiveImport, ArchiveStrategy, Attachment, Backup, Database, EncryptionStatus, MarkdownExport, MarkdownImport, Note, Notebook, NoteRevision, OutgoingLink, Recurrence, RecurringTodo, Reminder, SearchResult, Settings, SkippedFile, SkippedNote, SyncReport, Tag, TodoCompletion, TodoItem, TodoRecurrence, Workspace, MAX_ATTACHMENT_BYTES, MIN_NOTE_HEIGHT, MIN_NOTE_WIDTH};
 
+#[cfg(feature = "app")]
+/// How often the database is snapshotted while the app runs.
+const BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[cfg(feature = "app")]
+/// How often the database is checked for writes by other processes.
+const EXTERNAL_CHANGES_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(feature = "app")]
+/// How often the sync folder is checked while sync is on.
+const SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+#[cfg(feature = "app")]
+/// How often trashed notes past the retention period are purged.
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[cfg(feature = "app")]
+/// How often recurring todo items are checked for a new period.
+const TODO_ROLLOVER_INTERVAL: Duration = Duration::from_secs(60);
+
+#[cfg(feature = "app")]
+/// Puts the opened database into app state and brings up the note windows.
+pub(crate) fn start(app_handle: &AppHandle, db: Database) {
+    // Store database in app state
+    app_handle.manage(db);
+
+    // Purge expired trash on startup and then periodically
+    let purge_handle = app_handle.clone();
+    std::thread::spawn(move || loop {
+        if let Err(e) = purge_handle.state::<Database>().purge_expired_trash() {
+            eprintln!("Failed to purge trash: {}", e);
+        }
+        std::thread::sleep(TRASH_PURGE_INTERVAL);
+    });
+
+    // Back up on startup and then periodically
+    let backup_handle = app_handle.clone();
+    std::thread::spawn(move || loop {
+        if let Err(e) = backup_handle.state::<Database>().create_backup() {
+            eprintln!("Failed to back up database: {}", e);
+        }
+        std::thread::sleep(BACKUP_INTERVAL);
+    });
+
+    // Pick up other devices' changes and publish ours while sync is on
+    let sync_handle = app_handle.clone();
+    std::thread::spawn(move || loop {
+        let db = sync_handle.state::<Database>();
+        if db.get_settings().is_ok_and(|settings| !settings.sync_dir.is_empty()) {
+            match db.sync_now() {
+                Ok(report) if report.changed_notes() => {
+                    if let Err(e) = note_window::reload_note_windows(&sync_handle, &db) {
+                        eprintln!("Failed to reload notes after sync: {}", e);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to sync: {}", e),
+            }
+        }
+        std::thread::sleep(SYNC_INTERVAL);
+    });
+
+    // Show edits made by other processes, e.g. the `notary` CLI
+    let watch_handle = app_handle.clone();
+    std::thread::spawn(move || {
+        let db = watch_handle.state::<Database>();
+        let mut version = db.data_version().ok();
+        let mut seen: HashMap<String, String> = db
+            .get_open_notes()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|note| (note.id, note.updated_at))
+            .collect();
+        loop {
+            std::thread::sleep(EXTERNAL_CHANGES_INTERVAL);
+            let current = db.data_version().ok();
+            if current == version {
+                continue;
+            }
+            version = current;
+            if let Err(e) = note_window::reload_changed_note_windows(&watch_handle, &db, &mut seen) {
+                eprintln!("Failed to reload notes changed elsewhere: {}", e);
+            }
+        }
+    });
+
+    // Reset recurring todo items when their period rolls over
+    let rollover_handle = app_handle.clone();
+    std::thread::spawn(move || loop {
+        match rollover_handle.state::<Database>().roll_over_recurring_todos() {
+            Ok(notes) => {
+                for note in notes {
+                    note_window::notify_note_updated(&rollover_handle, &note);
+                }
+            }
+            Err(e) => eprintln!("Failed to roll over recurring todos: {}", e),
+        }
+        std::thread::sleep(TODO_ROLLOVER_INTERVAL);
+    });
+
+    // Fire reminders, including any missed while the app was closed
+    reminders::start_scheduler(app_handle);
+
+    // Serve the scripting API if it was turned on
+    app_handle.manage(api::ApiServer::default());
+    if let Err(e) = api::start_server(app_handle) {
+        eprintln!("Failed to start API server: {}", e);
+    }
+
+    // Register global hotkeys
+    if let Err(e) = hotkeys::register_hotkeys(app_handle) {
+        eprintln!("Failed to register hotkeys: {}", e);
+    }
+
+    // Restore open notes
+    let db = app_handle.state::<Database>();
+    if let Err(e) = note_window::restore_open_notes(app_handle, &db) {
+        eprintln!("Failed to restore notes: {}", e);
+    }
+
+    // Ensure at least one visible window exists
+    let notes = db.get_all_notes().unwrap_or_default();
+    let has_visible_window = app_handle.webview_windows().keys().any(|label| label.starts_with("note-"));
+
+    if notes.is_empty() {
+        // No notes at all — create a welcome note
+        if let Ok(note) = db.create_note(100, 100) {
+            let _ = db.update_note(&note.id, Some("Welcome"), Some("Welcome to HoverThought HUD!\n\nUse + to create notes\nUse the menu to see all notes"), None, None, None, None, None, None, None);
+            let _ = note_window::create_note_window(app_handle, &note);
+        }
+    } else if !has_visible_window {
+        // Notes exist but none are open — force-open the most recent one
+        if let Some(most_recent) = notes.first() {
+            let _ = db.open_note(&most_recent.id);
+            let _ = note_window::create_note_window(app_handle, most_recent);
+        }
+    }
+}
+
+#[cfg(feature = "app")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Only one instance may use the database; later launches hand their
+    // arguments to it and exit
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let listener = match instance::claim(&args) {
+        Ok(instance::Claim::Forwarded) => return,
+        Ok(instance::Claim::Primary(listener)) => listener,
+        Err(e) => {
+            // Running anyway could leave two instances writing to one store
+            eprintln!("Failed to check for a running instance: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     tauri::Builder::default()
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
-        .setup(|app| {
+        .plugin(tauri_plugin_notification::init())
+        .setup(move |app| {
+            app.manage(instance::PendingArgs::default());
+            instance::listen(app.handle(), listener);
+
             let app_data_dir = app.path().app_data_dir()
                 .map_err(|e| format!("Failed to get app data dir: {e}"))?;
-            let db = Database::new(app_data_dir)
-                .map_err(|e| format!("Failed to initialize database: {e}"))?;
-
-            // Store database in app state
-            app.manage(db);
-
-            // Register global hotkeys
-            let app_handle = app.handle().clone();
-            if let Err(e) = hotkeys::register_hotkeys(&app_handle) {
-                eprintln!("Failed to register hotkeys: {}", e);
-            }
 
-            // Restore open notes
-            let db = app.state::<Database>();
-            if let Err(e) = note_window::restore_open_notes(&app_handle, &db) {
-                eprintln!("Failed to restore notes: {}", e);
+            // An encrypted store can't be read until the master password is
+            // entered; the arguments wait until then
+            if Database::is_encrypted(&app_data_dir) {
+                note_window::create_unlock_window(app.handle())?;
+                if let Err(e) = instance::run_args(app.handle(), &args) {
+                    eprintln!("Failed to handle arguments: {}", e);
+                }
+                return Ok(());
             }
 
-            // Ensure at least one visible window exists
-            let notes = db.get_all_notes().unwrap_or_default();
-            let has_visible_window = app_handle.webview_windows().len() > 0;
+            let db = Database::new(app_data_dir)
+                .map_err(|e| format!("Failed to initialize database: {e}"))?;
+            start(app.handle(), db);
 
-            if notes.is_empty() {
-                // No notes at all — create a welcome note
-                if let Ok(note) = db.create_note(100, 100) {
-                    let _ = db.update_note(&note.id, Some("Welcome"), Some("Welcome to HoverThought HUD!\n\nUse + to create notes\nUse the menu to see all notes"), None, None, None, None, None, None, None);
-                    let _ = note_window::create_note_window(&app_handle, &note);
-                }
-            } else if !has_visible_window {
-                // Notes exist but none are open — force-open the most recent one
-                if let Some(most_recent) = notes.first() {
-                    let _ = db.open_note(&most_recent.id);
-                    let _ = note_window::create_note_window(&app_handle, most_recent);
+            // Same arguments a later launch would forward, e.g. `--new "text"`
+            if !args.is_empty() {
+                if let Err(e) = instance::run_args(app.handle(), &args) {
+                    eprintln!("Failed to handle arguments: {}", e);
                 }
             }
 
@@ -60,14 +220,91 @@ pub fn run() {
             commands::close_note,
             commands::open_note,
             commands::delete_note,
+            commands::list_trash,
+            commands::restore_note,
+            commands::empty_trash,
             commands::set_opacity,
             commands::set_always_on_top,
             commands::get_settings,
             commands::set_theme,
             commands::set_default_opacity,
+            commands::set_trash_retention_days,
+            commands::set_extract_hashtags,
+            commands::set_backup_retention,
+            commands::set_sync_dir,
+            commands::set_api_settings,
+            commands::get_api_token,
+            commands::regenerate_api_token,
             commands::minimize_all_notes,
             commands::show_all_notes,
+            commands::gather_all_notes,
+            commands::arrange_notes,
             commands::set_all_opacity,
+            commands::list_note_revisions,
+            commands::get_note_revision,
+            commands::restore_note_revision,
+            commands::search_notes,
+            commands::list_tags,
+            commands::get_note_tags,
+            commands::add_note_tag,
+            commands::remove_note_tag,
+            commands::rename_tag,
+            commands::merge_tags,
+            commands::delete_tag,
+            commands::get_notes_by_tags,
+            commands::list_notebooks,
+            commands::create_notebook,
+            commands::rename_notebook,
+            commands::move_notebook,
+            commands::delete_notebook,
+            commands::move_note_to_notebook,
+            commands::get_notebook_notes,
+            commands::show_notebook,
+            commands::hide_notebook,
+            commands::get_backlinks,
+            commands::get_outgoing_links,
+            commands::rename_note,
+            commands::add_attachment,
+            commands::add_attachment_from_path,
+            commands::list_attachments,
+            commands::read_attachment,
+            commands::remove_attachment,
+            commands::lock_note,
+            commands::view_locked_note,
+            commands::unlock_note,
+            commands::unlock_database,
+            commands::get_encryption_status,
+            commands::enable_encryption,
+            commands::change_encryption_password,
+            commands::disable_encryption,
+            commands::list_backups,
+            commands::create_backup,
+            commands::restore_backup,
+            commands::export_markdown,
+            commands::import_markdown,
+            commands::export_archive,
+            commands::import_archive,
+            commands::sync_now,
+            commands::toggle_todo,
+            commands::add_todo,
+            commands::remove_todo,
+            commands::move_todo,
+            commands::clear_completed,
+            commands::save_workspace,
+            commands::apply_workspace,
+            commands::list_workspaces,
+            commands::delete_workspace,
+            commands::set_note_template,
+            commands::list_templates,
+            commands::create_note_from_template,
+            commands::set_todo_recurrence,
+            commands::list_recurring_todos,
+            commands::get_todo_history,
+            commands::set_reminder,
+            commands::list_reminders,
+            commands::snooze_reminder,
+            commands::dismiss_reminder,
+            commands::acknowledge_reminder,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,5 +1,220 @@
-use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder, WebviewWindow};
-use crate::db::{Database, Note};
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use tauri::{AppHandle, Emitter, LogicalPosition, LogicalSize, Manager, Monitor, WebviewUrl, WebviewWindowBuilder, WebviewWindow};
+use crate::db::{Database, Note, MIN_NOTE_HEIGHT, MIN_NOTE_WIDTH};
+
+/// Height of the note title bar, which has to stay on screen to drag a note.
+const TITLE_BAR_HEIGHT: i32 = 32;
+
+/// How wide a piece of the title bar has to be on screen to grab it.
+const MIN_VISIBLE_WIDTH: i32 = 80;
+
+/// A rectangle in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn of_note(note: &Note) -> Self {
+        Rect { x: note.pos_x, y: note.pos_y, width: note.width, height: note.height }
+    }
+
+    /// Usable part of a monitor, without taskbars and docks.
+    fn work_area(monitor: &Monitor) -> Self {
+        let scale = monitor.scale_factor();
+        let area = monitor.work_area();
+        let logical = |value: f64| (value / scale).round() as i32;
+        Rect {
+            x: logical(area.position.x as f64),
+            y: logical(area.position.y as f64),
+            width: logical(area.size.width as f64),
+            height: logical(area.size.height as f64),
+        }
+    }
+
+    /// Width and height of the part `other` shares with this rectangle.
+    /// Saturates, as stored positions can be anywhere.
+    fn overlap(&self, other: &Rect) -> (i32, i32) {
+        let right = self.x.saturating_add(self.width).min(other.x.saturating_add(other.width));
+        let bottom = self.y.saturating_add(self.height).min(other.y.saturating_add(other.height));
+        let width = right.saturating_sub(self.x.max(other.x));
+        let height = bottom.saturating_sub(self.y.max(other.y));
+        (width.max(0), height.max(0))
+    }
+
+    fn contains(&self, other: &Rect) -> bool {
+        self.overlap(other) == (other.width, other.height)
+    }
+
+    /// `other` shrunk to fit (not below the minimum note size) and moved
+    /// just far enough to be inside this rectangle.
+    pub fn clamp(&self, other: &Rect) -> Rect {
+        let width = other.width.min(self.width).max(MIN_NOTE_WIDTH);
+        let height = other.height.min(self.height).max(MIN_NOTE_HEIGHT);
+        Rect {
+            x: other.x.min(self.x.saturating_add(self.width - width)).max(self.x),
+            y: other.y.min(self.y.saturating_add(self.height - height)).max(self.y),
+            width,
+            height,
+        }
+    }
+}
+
+/// Work areas of the connected monitors.
+pub fn work_areas(app: &AppHandle) -> Vec<Rect> {
+    app.available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(Rect::work_area)
+        .collect()
+}
+
+/// Work area of the primary monitor, or of any monitor if none is primary.
+pub fn primary_work_area(app: &AppHandle) -> Option<Rect> {
+    app.primary_monitor()
+        .ok()
+        .flatten()
+        .map(|monitor| Rect::work_area(&monitor))
+        .or_else(|| work_areas(app).into_iter().next())
+}
+
+/// The work area `rect` overlaps most, if it overlaps any.
+fn area_under(rect: &Rect, areas: &[Rect]) -> Option<Rect> {
+    areas
+        .iter()
+        .map(|area| (area, area.overlap(rect)))
+        .filter(|(_, (width, height))| *width > 0 && *height > 0)
+        .max_by_key(|(_, (width, height))| *width as i64 * *height as i64)
+        .map(|(area, _)| *area)
+}
+
+/// Where a note has to go so its title bar can be reached, or `None` when
+/// it already can be. A note that is mostly off screen is pulled into the
+/// monitor it overlaps most, or onto `fallback` when it overlaps none.
+fn recovered_rect(note: &Note, areas: &[Rect], fallback: Rect) -> Option<Rect> {
+    let rect = Rect::of_note(note);
+    let title_bar = Rect { height: TITLE_BAR_HEIGHT, ..rect };
+    let reachable = areas.iter().any(|area| {
+        let (width, height) = area.overlap(&title_bar);
+        width >= MIN_VISIBLE_WIDTH.min(rect.width) && height >= TITLE_BAR_HEIGHT
+    });
+    if reachable {
+        return None;
+    }
+
+    let target = area_under(&rect, areas).unwrap_or(fallback);
+    Some(target.clamp(&rect))
+}
+
+/// How `arrange_rects` lays notes out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrangeStrategy {
+    /// Grid filling the screen
+    Tile,
+    /// Overlapping diagonal, each note keeping its size
+    Cascade,
+    /// Columns of equal-height notes along the left edge
+    StackLeft,
+    /// Columns of equal-height notes along the right edge
+    StackRight,
+}
+
+/// Space between arranged notes and around the screen edge.
+const ARRANGE_GAP: i32 = 8;
+
+/// Offset between cascaded notes, enough to show each title bar.
+const CASCADE_STEP: i32 = 32;
+
+/// New geometry for notes with the current geometry `rects`, laid out
+/// inside `area` in the same order. No note gets smaller than the minimum
+/// size; when they don't all fit they overlap rather than shrink further.
+pub fn arrange_rects(strategy: ArrangeStrategy, area: Rect, rects: &[Rect]) -> Vec<Rect> {
+    let count = rects.len() as i32;
+    if count == 0 {
+        return Vec::new();
+    }
+    let inner = Rect {
+        x: area.x + ARRANGE_GAP,
+        y: area.y + ARRANGE_GAP,
+        width: (area.width - 2 * ARRANGE_GAP).max(MIN_NOTE_WIDTH),
+        height: (area.height - 2 * ARRANGE_GAP).max(MIN_NOTE_HEIGHT),
+    };
+    // Size of each of `parts` cells splitting `length` with gaps between
+    let cell = |length: i32, parts: i32, min: i32| ((length - ARRANGE_GAP * (parts - 1)) / parts).max(min);
+
+    match strategy {
+        ArrangeStrategy::Tile => {
+            let fit = (inner.width + ARRANGE_GAP) / (MIN_NOTE_WIDTH + ARRANGE_GAP);
+            let columns = ((count as f64).sqrt().ceil() as i32).min(fit.max(1));
+            let rows = (count + columns - 1) / columns;
+            let width = cell(inner.width, columns, MIN_NOTE_WIDTH);
+            let height = cell(inner.height, rows, MIN_NOTE_HEIGHT);
+
+            (0..count)
+                .map(|i| {
+                    inner.clamp(&Rect {
+                        x: inner.x + (i % columns) * (width + ARRANGE_GAP),
+                        y: inner.y + (i / columns) * (height + ARRANGE_GAP),
+                        width,
+                        height,
+                    })
+                })
+                .collect()
+        }
+        ArrangeStrategy::Cascade => {
+            let sized: Vec<Rect> = rects.iter().map(|rect| inner.clamp(rect)).collect();
+            let tallest = sized.iter().map(|rect| rect.height).max().unwrap_or(MIN_NOTE_HEIGHT);
+            // Start again from the top, shifted right, once the bottom is reached
+            let per_run = ((inner.height - tallest) / CASCADE_STEP + 1).max(1);
+
+            sized
+                .iter()
+                .enumerate()
+                .map(|(i, rect)| {
+                    let i = i as i32;
+                    inner.clamp(&Rect {
+                        x: inner.x + (i % per_run + i / per_run) * CASCADE_STEP,
+                        y: inner.y + (i % per_run) * CASCADE_STEP,
+                        ..*rect
+                    })
+                })
+                .collect()
+        }
+        ArrangeStrategy::StackLeft | ArrangeStrategy::StackRight => {
+            let per_column = ((inner.height + ARRANGE_GAP) / (MIN_NOTE_HEIGHT + ARRANGE_GAP)).max(1);
+            let rows = count.min(per_column);
+            let height = cell(inner.height, rows, MIN_NOTE_HEIGHT);
+            // As wide as the widest note, up to a third of the screen
+            let widest = rects.iter().map(|rect| rect.width).max().unwrap_or(MIN_NOTE_WIDTH);
+            let width = widest.min(inner.width / 3).max(MIN_NOTE_WIDTH);
+
+            (0..count)
+                .map(|i| {
+                    let offset = (i / per_column) * (width + ARRANGE_GAP);
+                    let x = match strategy {
+                        ArrangeStrategy::StackLeft => inner.x + offset,
+                        _ => inner.x + inner.width - width - offset,
+                    };
+                    inner.clamp(&Rect { x, y: inner.y + (i % per_column) * (height + ARRANGE_GAP), width, height })
+                })
+                .collect()
+        }
+    }
+}
+
+/// Saves a note's new geometry and returns the note as updated.
+pub fn move_note(db: &Database, note: &Note, rect: Rect) -> Result<Note, String> {
+    db.update_note(&note.id, None, None, None, Some(rect.x), Some(rect.y), Some(rect.width), Some(rect.height), None, None)
+        .map_err(|e| e.to_string())?;
+    Ok(Note { pos_x: rect.x, pos_y: rect.y, width: rect.width, height: rect.height, ..note.clone() })
+}
 
 pub fn create_note_window(app: &AppHandle, note: &Note) -> Result<(), String> {
     let label = format!("note-{}", note.id);
@@ -18,7 +233,7 @@ pub fn create_note_window(app: &AppHandle, note: &Note) -> Result<(), String> {
         .always_on_top(note.always_on_top)
         .position(note.pos_x as f64, note.pos_y as f64)
         .inner_size(note.width as f64, note.height as f64)
-        .min_inner_size(200.0, 150.0)
+        .min_inner_size(MIN_NOTE_WIDTH as f64, MIN_NOTE_HEIGHT as f64)
         .visible(true);
 
     builder.build().map_err(|e| e.to_string())?;
@@ -26,10 +241,72 @@ pub fn create_note_window(app: &AppHandle, note: &Note) -> Result<(), String> {
     Ok(())
 }
 
+/// Window asking for the master password of an encrypted database. Nothing
+/// else is shown until `unlock_database` succeeds.
+pub fn create_unlock_window(app: &AppHandle) -> Result<(), String> {
+    if app.get_webview_window("unlock").is_some() {
+        return Ok(());
+    }
+
+    let url = WebviewUrl::App("index.html".into());
+
+    WebviewWindowBuilder::new(app, "unlock", url)
+        .title("HoverThought HUD")
+        .inner_size(320.0, 180.0)
+        .resizable(false)
+        .center()
+        .always_on_top(true)
+        .visible(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Pushes fresh note data to the note's window so changes made outside the
+/// editor (restores, scripted edits, ...) show up without reopening it.
+pub fn notify_note_updated(app: &AppHandle, note: &Note) {
+    let label = format!("note-{}", note.id);
+    if app.get_webview_window(&label).is_some() {
+        let _ = app.emit_to(label.as_str(), "note-updated", note);
+    }
+}
+
+/// Moves, resizes and re-pins the note's window to match `note`, then
+/// pushes the new data to it.
+pub fn refresh_note_window(app: &AppHandle, note: &Note) {
+    let label = format!("note-{}", note.id);
+    if let Some(window) = app.get_webview_window(&label) {
+        let _ = window.set_position(LogicalPosition::new(note.pos_x as f64, note.pos_y as f64));
+        let _ = window.set_size(LogicalSize::new(note.width as f64, note.height as f64));
+        let _ = window.set_always_on_top(note.always_on_top);
+        notify_note_updated(app, note);
+    }
+}
+
+/// Brings a note's window to the front, also when minimized or hidden.
+pub fn focus_note_window(app: &AppHandle, id: &str) {
+    if let Some(window) = app.get_webview_window(&format!("note-{id}")) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Opens a window for every open note. Notes left off screen, e.g. on a
+/// monitor that has since been unplugged, are moved back on screen first.
 pub fn restore_open_notes(app: &AppHandle, db: &Database) -> Result<(), String> {
     let notes = db.get_open_notes().map_err(|e| e.to_string())?;
+    let areas = work_areas(app);
+    let fallback = primary_work_area(app);
 
-    for note in notes {
+    for mut note in notes {
+        if let Some(rect) = fallback.and_then(|fallback| recovered_rect(&note, &areas, fallback)) {
+            match move_note(db, &note, rect) {
+                Ok(moved) => note = moved,
+                Err(e) => eprintln!("Failed to move note {} on screen: {}", note.id, e),
+            }
+        }
         if let Err(e) = create_note_window(app, &note) {
             eprintln!("Failed to create window for note {}: {}", note.id, e);
         }
@@ -38,24 +315,244 @@ pub fn restore_open_notes(app: &AppHandle, db: &Database) -> Result<(), String>
     Ok(())
 }
 
-pub fn close_all_note_windows(app: &AppHandle) {
-    let windows: Vec<(String, WebviewWindow)> = app.webview_windows()
-        .into_iter()
-        .filter(|(label, _): &(String, WebviewWindow)| label.starts_with("note-"))
-        .collect();
+/// Brings note windows in line with a database that was replaced wholesale,
+/// e.g. by restoring a backup: open notes get a window, windows of notes that
+/// are gone or closed are closed, and the rest are moved and refreshed.
+pub fn reload_note_windows(app: &AppHandle, db: &Database) -> Result<(), String> {
+    let notes = db.get_open_notes().map_err(|e| e.to_string())?;
 
-    for (_, window) in windows {
-        let _ = window.hide();
+    // Open the new windows first so the app is never left without one
+    restore_open_notes(app, db)?;
+
+    for (label, window) in app.webview_windows() {
+        let Some(id) = label.strip_prefix("note-") else {
+            continue;
+        };
+        match notes.iter().find(|note| note.id == id) {
+            Some(note) => refresh_note_window(app, note),
+            None => {
+                let _ = window.close();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Catches the windows up with changes another process made to the database:
+/// windows are opened and closed to match, and notes whose `updated_at`
+/// differs from `seen` are refreshed. `seen` is updated to the current notes.
+pub fn reload_changed_note_windows(
+    app: &AppHandle,
+    db: &Database,
+    seen: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    let notes = db.get_open_notes().map_err(|e| e.to_string())?;
+    restore_open_notes(app, db)?;
+
+    for (label, window) in app.webview_windows() {
+        let Some(id) = label.strip_prefix("note-") else {
+            continue;
+        };
+        match notes.iter().find(|note| note.id == id) {
+            Some(note) if seen.get(id) != Some(&note.updated_at) => refresh_note_window(app, note),
+            Some(_) => {}
+            None => {
+                let _ = window.close();
+            }
+        }
     }
+
+    *seen = notes.into_iter().map(|note| (note.id, note.updated_at)).collect();
+    Ok(())
 }
 
-pub fn show_all_note_windows(app: &AppHandle) {
-    let windows: Vec<(String, WebviewWindow)> = app.webview_windows()
+/// Note windows, optionally limited to notes filed under `notebook_id` or
+/// any notebook nested below it.
+fn note_windows(app: &AppHandle, notebook_id: Option<&str>) -> Vec<WebviewWindow> {
+    let windows = app.webview_windows()
         .into_iter()
-        .filter(|(label, _): &(String, WebviewWindow)| label.starts_with("note-"))
-        .collect();
+        .filter(|(label, _): &(String, WebviewWindow)| label.starts_with("note-"));
+
+    let Some(notebook_id) = notebook_id else {
+        return windows.map(|(_, window)| window).collect();
+    };
+
+    let db = app.state::<Database>();
+    let labels: Vec<String> = match db.get_notebook_notes(notebook_id, true) {
+        Ok(notes) => notes.iter().map(|note| format!("note-{}", note.id)).collect(),
+        Err(e) => {
+            eprintln!("Failed to load notebook {}: {}", notebook_id, e);
+            return Vec::new();
+        }
+    };
+
+    windows
+        .filter(|(label, _)| labels.contains(label))
+        .map(|(_, window)| window)
+        .collect()
+}
+
+pub fn close_all_note_windows(app: &AppHandle, notebook_id: Option<&str>) {
+    for window in note_windows(app, notebook_id) {
+        let _ = window.hide();
+    }
+}
 
-    for (_, window) in windows {
+pub fn show_all_note_windows(app: &AppHandle, notebook_id: Option<&str>) {
+    for window in note_windows(app, notebook_id) {
         let _ = window.show();
     }
 }
+
+/// Moves every note onto the primary monitor, keeping notes from another
+/// monitor at the same place relative to its corner, and brings the open
+/// ones to the front.
+pub fn gather_note_windows(app: &AppHandle, db: &Database) -> Result<(), String> {
+    let primary = primary_work_area(app).ok_or_else(|| "No monitor found".to_string())?;
+    let areas = work_areas(app);
+
+    for note in db.get_all_notes().map_err(|e| e.to_string())? {
+        let rect = Rect::of_note(&note);
+        if primary.contains(&rect) {
+            continue;
+        }
+        let moved = match area_under(&rect, &areas) {
+            Some(source) if source != primary => Rect {
+                x: primary.x + rect.x - source.x,
+                y: primary.y + rect.y - source.y,
+                ..rect
+            },
+            _ => rect,
+        };
+        let note = move_note(db, &note, primary.clamp(&moved))?;
+        refresh_note_window(app, &note);
+    }
+
+    show_all_note_windows(app, None);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Rect = Rect { x: 0, y: 0, width: 1920, height: 1080 };
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    fn note_at(rect: Rect) -> Note {
+        Note {
+            id: String::new(),
+            title: String::new(),
+            content: String::new(),
+            mode: "text".to_string(),
+            pos_x: rect.x,
+            pos_y: rect.y,
+            width: rect.width,
+            height: rect.height,
+            opacity: 1.0,
+            is_open: true,
+            is_minimized: false,
+            always_on_top: false,
+            created_at: String::new(),
+            updated_at: String::new(),
+            deleted_at: None,
+            notebook_id: None,
+            is_locked: false,
+            is_template: false,
+        }
+    }
+
+    #[test]
+    fn clamp_moves_and_shrinks_into_the_area() {
+        assert_eq!(SCREEN.clamp(&rect(1800, -50, 400, 300)), rect(1520, 0, 400, 300));
+        assert_eq!(SCREEN.clamp(&rect(10, 10, 3000, 2000)), SCREEN);
+        assert_eq!(SCREEN.clamp(&rect(10, 10, 50, 50)), rect(10, 10, MIN_NOTE_WIDTH, MIN_NOTE_HEIGHT));
+    }
+
+    #[test]
+    fn clamp_into_an_area_below_the_minimum_size() {
+        let tiny = rect(100, 100, 150, 100);
+        assert_eq!(tiny.clamp(&rect(500, 500, 300, 300)), rect(100, 100, MIN_NOTE_WIDTH, MIN_NOTE_HEIGHT));
+        assert_eq!(tiny.clamp(&rect(-500, -500, 300, 300)), rect(100, 100, MIN_NOTE_WIDTH, MIN_NOTE_HEIGHT));
+    }
+
+    #[test]
+    fn far_off_screen_geometry_does_not_overflow() {
+        let far = rect(i32::MAX - 10, i32::MIN + 10, 400, 300);
+        assert_eq!(SCREEN.overlap(&far), (0, 0));
+        assert_eq!(far.overlap(&SCREEN), (0, 0));
+        assert_eq!(SCREEN.clamp(&far), rect(1520, 0, 400, 300));
+        // Right edges past the largest coordinate are cut off there
+        assert_eq!(rect(i32::MAX - 10, 0, 100, 100).overlap(&rect(i32::MAX - 20, 0, 100, 100)), (10, 100));
+    }
+
+    #[test]
+    fn reachable_notes_stay_put() {
+        assert_eq!(recovered_rect(&note_at(rect(100, 100, 300, 200)), &[SCREEN], SCREEN), None);
+        // Enough of the title bar sticks out at the bottom right
+        assert_eq!(recovered_rect(&note_at(rect(1800, 1040, 300, 200)), &[SCREEN], SCREEN), None);
+    }
+
+    #[test]
+    fn unreachable_notes_are_recovered() {
+        let second = rect(1920, 0, 1280, 1024);
+        // Mostly on the right monitor, title bar under the top edge of neither
+        let hidden = note_at(rect(1900, -20, 300, 200));
+        assert_eq!(recovered_rect(&hidden, &[SCREEN, second], SCREEN), Some(rect(1920, 0, 300, 200)));
+        // The right monitor was unplugged
+        let gone = note_at(rect(2500, 300, 300, 200));
+        assert_eq!(recovered_rect(&gone, &[SCREEN], SCREEN), Some(rect(1620, 300, 300, 200)));
+        // No monitors reported at all
+        assert_eq!(recovered_rect(&gone, &[], SCREEN), Some(rect(1620, 300, 300, 200)));
+        let far = note_at(rect(i32::MIN, i32::MAX, 300, 200));
+        assert_eq!(recovered_rect(&far, &[SCREEN], SCREEN), Some(rect(0, 880, 300, 200)));
+    }
+
+    #[test]
+    fn arranging_nothing_or_one_note() {
+        for strategy in [ArrangeStrategy::Tile, ArrangeStrategy::Cascade, ArrangeStrategy::StackLeft, ArrangeStrategy::StackRight] {
+            assert!(arrange_rects(strategy, SCREEN, &[]).is_empty());
+            assert_eq!(arrange_rects(strategy, SCREEN, &[rect(0, 0, 300, 200)]).len(), 1);
+        }
+        assert_eq!(arrange_rects(ArrangeStrategy::Tile, SCREEN, &[rect(0, 0, 300, 200)]), [rect(8, 8, 1904, 1064)]);
+        assert_eq!(
+            arrange_rects(ArrangeStrategy::StackRight, SCREEN, &[rect(0, 0, 300, 200)]),
+            [rect(1612, 8, 300, 1064)]
+        );
+    }
+
+    #[test]
+    fn tile_fills_a_grid() {
+        let tiled = arrange_rects(ArrangeStrategy::Tile, SCREEN, &[rect(0, 0, 300, 200); 4]);
+        assert_eq!(
+            tiled,
+            [rect(8, 8, 948, 528), rect(964, 8, 948, 528), rect(8, 544, 948, 528), rect(964, 544, 948, 528)]
+        );
+    }
+
+    #[test]
+    fn cascade_wraps_back_to_the_top() {
+        let short = rect(0, 0, 1920, 400);
+        let cascaded = arrange_rects(ArrangeStrategy::Cascade, short, &[rect(0, 0, 300, 200); 8]);
+        // (384 - 200) / 32 + 1 = 6 notes per run
+        assert_eq!(cascaded[5], rect(168, 168, 300, 200));
+        assert_eq!(cascaded[6], rect(40, 8, 300, 200));
+        assert_eq!(cascaded[7], rect(72, 40, 300, 200));
+    }
+
+    #[test]
+    fn arranging_on_a_tiny_area_keeps_the_minimum_size() {
+        let tiny = rect(50, 50, 120, 90);
+        let rects = [rect(0, 0, 300, 200), rect(i32::MAX - 5, i32::MIN + 5, 300, 200), rect(0, 0, 10, 10)];
+        for strategy in [ArrangeStrategy::Tile, ArrangeStrategy::Cascade, ArrangeStrategy::StackLeft, ArrangeStrategy::StackRight] {
+            for arranged in arrange_rects(strategy, tiny, &rects) {
+                assert!(arranged.width >= MIN_NOTE_WIDTH && arranged.height >= MIN_NOTE_HEIGHT, "{strategy:?}");
+                assert_eq!((arranged.x, arranged.y), (58, 58), "{strategy:?}");
+            }
+        }
+    }
+}
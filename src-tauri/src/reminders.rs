@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::commands;
+use crate::db::{Database, Note, Reminder};
+use crate::note_window::focus_note_window;
+
+/// How often the scheduler looks for due reminders.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Fires reminders as they come due. The first check runs right away, so
+/// reminders missed while the app was closed go off at launch.
+pub fn start_scheduler(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let due = app.state::<Database>().take_due_reminders();
+        match due {
+            Ok(due) => {
+                for (reminder, note) in due {
+                    fire(&app, &reminder, &note);
+                }
+            }
+            Err(e) => eprintln!("Failed to check reminders: {}", e),
+        }
+        std::thread::sleep(CHECK_INTERVAL);
+    });
+}
+
+/// Raises a desktop notification and brings the note to the front, opening
+/// its window if it was hidden.
+fn fire(app: &AppHandle, reminder: &Reminder, note: &Note) {
+    let title = if note.title.trim().is_empty() { "Reminder" } else { note.title.as_str() };
+    // Locked notes only name themselves
+    let body = if note.is_locked {
+        String::new()
+    } else {
+        note.content.lines().find(|line| !line.trim().is_empty()).unwrap_or_default().to_string()
+    };
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        eprintln!("Failed to show reminder notification: {}", e);
+    }
+
+    if let Err(e) = commands::open_note(app.clone(), note.id.clone()) {
+        eprintln!("Failed to open note for reminder: {}", e);
+        return;
+    }
+    focus_note_window(app, &note.id);
+    // A window created just now may not be listening yet; it finds the
+    // fired reminder itself when it loads
+    let _ = app.emit_to(format!("note-{}", note.id).as_str(), "reminder-fired", reminder);
+}
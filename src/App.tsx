@@ -1,8 +1,10 @@
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { NoteCard } from './components/NoteCard';
+import { UnlockScreen } from './components/UnlockScreen';
 
 // No URL routing — identify what to render from the window label.
-// Note windows are created with label "note-{uuid}".
+// Note windows are created with label "note-{uuid}"; "unlock" asks for the
+// master password of an encrypted database.
 const windowLabel = getCurrentWindow().label;
 
 function App() {
@@ -11,6 +13,10 @@ function App() {
     return <NoteCard noteId={noteId} />;
   }
 
+  if (windowLabel === 'unlock') {
+    return <UnlockScreen />;
+  }
+
   return (
     <div className="p-4 text-sm opacity-50">
       HoverThought HUD — press Ctrl+Alt+N to create a note
@@ -1,5 +1,7 @@
-import { useCallback } from 'react';
+import { useCallback, useEffect, useState } from 'react';
+import { listen } from '@tauri-apps/api/event';
 import { useNote } from '../hooks/useNote';
+import { acknowledgeReminder, dismissReminder, listReminders, lockNote, Recurrence, Reminder, unlockNote, setReminder, snoozeReminder, viewLockedNote } from '../lib/tauri';
 import { TitleBar } from './TitleBar';
 import { TransparencySlider } from './TransparencySlider';
 import { NoteEditor } from './NoteEditor';
@@ -8,8 +10,132 @@ interface NoteCardProps {
   noteId: string;
 }
 
+const SNOOZE_MINUTES = 10;
+
+// Accepts minutes from now ("30"), a time today or tomorrow ("14:30"),
+// or a local date and time ("2025-03-01 09:00")
+function parseReminderTime(input: string): Date | null {
+  const text = input.trim();
+  if (/^\d+$/.test(text)) {
+    return new Date(Date.now() + parseInt(text, 10) * 60_000);
+  }
+  const time = text.match(/^(\d{1,2}):(\d{2})$/);
+  if (time) {
+    const at = new Date();
+    at.setHours(parseInt(time[1], 10), parseInt(time[2], 10), 0, 0);
+    if (at.getTime() <= Date.now()) at.setDate(at.getDate() + 1);
+    return at;
+  }
+  const at = new Date(text.replace(' ', 'T'));
+  return isNaN(at.getTime()) ? null : at;
+}
+
 export function NoteCard({ noteId }: NoteCardProps) {
   const { note, loading, error, updateContent, updateOpacity, updateAlwaysOnTop, updateTitle, saveNow, flushAndGetContent } = useNote(noteId);
+  const [passphrase, setPassphrase] = useState('');
+  const [unlockError, setUnlockError] = useState<string | null>(null);
+  // Plaintext of a locked note being viewed; only ever held in memory
+  const [revealed, setRevealed] = useState<string | null>(null);
+  const [ringing, setRinging] = useState<Reminder | null>(null);
+
+  // Stays up until snoozed or dismissed, also for reminders that went off
+  // before this window was listening
+  useEffect(() => {
+    listReminders(noteId)
+      .then((reminders) => {
+        const fired = reminders.find((r) => r.fired_at && !r.snoozed_until);
+        if (fired) setRinging(fired);
+      })
+      .catch(console.error);
+
+    let unlisten: (() => void) | undefined;
+    listen<Reminder>('reminder-fired', (event) => {
+      if (event.payload.note_id === noteId) setRinging(event.payload);
+    }).then((fn) => {
+      unlisten = fn;
+    });
+    return () => {
+      if (unlisten) unlisten();
+    };
+  }, [noteId]);
+
+  const handleSetReminder = useCallback(async () => {
+    const existing = await listReminders(noteId).catch(() => []);
+    if (existing.length > 0) {
+      const when = existing.map((r) => new Date(r.snoozed_until ?? r.fire_at).toLocaleString() + (r.recurrence ? ` (${r.recurrence})` : '')).join('\n');
+      if (!confirm(`Current reminders:\n${when}\n\nReplace them with a new one?`)) return;
+      await Promise.all(existing.map((r) => dismissReminder(r.id))).catch(console.error);
+      setRinging(null);
+    }
+
+    const input = prompt('Remind at (minutes from now, HH:MM, or YYYY-MM-DD HH:MM):');
+    if (!input) return;
+    const at = parseReminderTime(input);
+    if (!at) {
+      alert('Could not read that time');
+      return;
+    }
+    const repeat = prompt('Repeat (none, daily, weekdays, weekly):', 'none');
+    if (repeat === null) return;
+    const recurrence = repeat.trim().toLowerCase();
+    if (!['none', '', 'daily', 'weekdays', 'weekly'].includes(recurrence)) {
+      alert('Repeat must be none, daily, weekdays or weekly');
+      return;
+    }
+    await setReminder(noteId, at.toISOString(), recurrence === 'none' || recurrence === '' ? null : (recurrence as Recurrence))
+      .catch((e) => alert(String(e)));
+  }, [noteId]);
+
+  const handleSnooze = async () => {
+    if (!ringing) return;
+    await snoozeReminder(ringing.id, SNOOZE_MINUTES).catch(console.error);
+    setRinging(null);
+  };
+
+  // Recurring reminders keep their schedule
+  const handleDismiss = async () => {
+    if (!ringing) return;
+    await acknowledgeReminder(ringing.id).catch(console.error);
+    setRinging(null);
+  };
+
+  // Window stays open; the note-updated event swaps in the redacted note
+  const handleLock = useCallback(async () => {
+    const first = prompt('Passphrase to lock this note:');
+    if (!first) return;
+    if (prompt('Repeat passphrase:') !== first) {
+      alert('Passphrases do not match');
+      return;
+    }
+    await saveNow();
+    await lockNote(noteId, first).catch((e) => alert(String(e)));
+  }, [noteId, saveNow]);
+
+  const handleView = async () => {
+    try {
+      setRevealed(await viewLockedNote(noteId, passphrase));
+      setPassphrase('');
+      setUnlockError(null);
+    } catch (e) {
+      setUnlockError(String(e));
+    }
+  };
+
+  const handleRemoveLock = async () => {
+    if (!confirm('Remove the lock and store this note unencrypted again?')) return;
+    try {
+      await unlockNote(noteId, passphrase);
+      setPassphrase('');
+      setUnlockError(null);
+    } catch (e) {
+      setUnlockError(String(e));
+    }
+  };
+
+  // Forget viewed plaintext once the note is no longer locked, e.g. after removing the lock
+  useEffect(() => {
+    if (!note?.is_locked) setRevealed(null);
+  }, [note?.is_locked]);
 
   // Appends a new todo line to the note content
   const addTodoLine = useCallback(() => {
@@ -50,10 +176,73 @@ export function NoteCard({ noteId }: NoteCardProps) {
         onTitleChange={updateTitle}
         onGetLiveContent={flushAndGetContent}
         onAddTodo={addTodoLine}
+        isLocked={note.is_locked}
+        onLock={handleLock}
+        onSetReminder={handleSetReminder}
+        isTemplate={note.is_template}
       />
 
+      {ringing && (
+        <div className="flex items-center gap-2 px-2 py-1 text-xs bg-amber-100 dark:bg-amber-900">
+          <span className="flex-1">⏰ Reminder</span>
+          <button
+            onClick={handleSnooze}
+            className="px-2 py-0.5 bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+          >
+            Snooze {SNOOZE_MINUTES} min
+          </button>
+          <button
+            onClick={handleDismiss}
+            className="px-2 py-0.5 bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+          >
+            Dismiss
+          </button>
+        </div>
+      )}
+
       <div className="flex-1 relative overflow-hidden">
-        <NoteEditor content={note.content} onChange={updateContent} />
+        {note.is_locked && revealed !== null ? (
+          <div className="h-full flex flex-col">
+            <div className="flex-1 overflow-auto p-3 text-sm whitespace-pre-wrap select-text">{revealed}</div>
+            <div className="flex justify-end gap-2 px-2 py-1">
+              <button
+                onClick={() => setRevealed(null)}
+                className="px-3 py-1 text-xs bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+              >
+                Hide
+              </button>
+            </div>
+          </div>
+        ) : note.is_locked ? (
+          <div className="h-full flex flex-col items-center justify-center gap-2 p-4">
+            <span className="text-sm opacity-60">🔒 This note is locked</span>
+            <input
+              type="password"
+              value={passphrase}
+              onChange={(e) => setPassphrase(e.target.value)}
+              onKeyDown={(e) => { if (e.key === 'Enter') handleView(); }}
+              placeholder="Passphrase"
+              className="w-full max-w-48 px-2 py-1 text-xs rounded bg-gray-100 dark:bg-gray-700 focus:outline-none"
+            />
+            <div className="flex gap-2">
+              <button
+                onClick={handleView}
+                className="px-3 py-1 text-xs bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+              >
+                View
+              </button>
+              <button
+                onClick={handleRemoveLock}
+                className="px-3 py-1 text-xs bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+              >
+                Remove lock
+              </button>
+            </div>
+            {unlockError && <span className="text-xs text-red-500">{unlockError}</span>}
+          </div>
+        ) : (
+          <NoteEditor content={note.content} onChange={updateContent} />
+        )}
 
         {/* Resize handle */}
         <div className="resize-handle">
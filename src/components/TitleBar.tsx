@@ -1,5 +1,19 @@
 import { useState, useEffect } from 'react';
-import { startDragging, createNote, minimizeWindow, closeNoteWindow, deleteNote, openNote, getAllNotes, updateNote, minimizeAllNotes, showAllNotes, setAllOpacity, Note } from '../lib/tauri';
+import { startDragging, createNote, createNoteFromTemplate, listTemplates, setNoteTemplate, minimizeWindow, closeNoteWindow, deleteNote, openNote, getAllNotes, updateNote, minimizeAllNotes, showAllNotes, gatherAllNotes, arrangeNotes, setAllOpacity, searchNotes, listTrash, restoreNote, emptyTrash, listBackups, createBackup, restoreBackup, listWorkspaces, saveWorkspace, applyWorkspace, deleteWorkspace, exportMarkdown, importMarkdown, exportArchive, importArchive, getSettings, setSyncDir, syncNow, setApiSettings, getApiToken, regenerateApiToken, getEncryptionStatus, enableEncryption, changeEncryptionPassword, disableEncryption, ArchiveStrategy, ArrangeStrategy, Backup, Note, SearchResult, Workspace } from '../lib/tauri';
+
+// Renders search output, where matches are wrapped in <mark>…</mark>, without
+// interpreting the rest of the note text as HTML.
+function Highlighted({ text }: { text: string }) {
+  return (
+    <>
+      {text.split(/(<mark>.*?<\/mark>)/g).map((part, i) =>
+        part.startsWith('<mark>') && part.endsWith('</mark>')
+          ? <mark key={i} className="bg-yellow-200 dark:bg-yellow-700 rounded-sm">{part.slice(6, -7)}</mark>
+          : <span key={i}>{part}</span>
+      )}
+    </>
+  );
+}
 
 interface TitleBarProps {
   noteId: string;
@@ -12,22 +26,68 @@ interface TitleBarProps {
   onTitleChange: (title: string) => void;
   onGetLiveContent: () => Promise<string>;
   onAddTodo: () => void;
+  isLocked: boolean;
+  onLock: () => void;
+  onSetReminder: () => void;
+  isTemplate: boolean;
 }
 
-export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onOpacityChange, onBeforeClose, onTitleChange, onGetLiveContent, onAddTodo }: TitleBarProps) {
+export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onOpacityChange, onBeforeClose, onTitleChange, onGetLiveContent, onAddTodo, isLocked, onLock, onSetReminder, isTemplate }: TitleBarProps) {
   const [showMenu, setShowMenu] = useState(false);
   const [showSettings, setShowSettings] = useState(false);
   const [notes, setNotes] = useState<Note[]>([]);
   const [isEditingTitle, setIsEditingTitle] = useState(false);
   const [editTitle, setEditTitle] = useState(title);
   const [isSynced, setIsSynced] = useState(false);
+  const [searchQuery, setSearchQuery] = useState('');
+  const [searchResults, setSearchResults] = useState<SearchResult[]>([]);
+  const [showTrash, setShowTrash] = useState(false);
+  const [trash, setTrash] = useState<Note[]>([]);
+  const [showBackups, setShowBackups] = useState(false);
+  const [backups, setBackups] = useState<Backup[]>([]);
+  const [showWorkspaces, setShowWorkspaces] = useState(false);
+  const [workspaces, setWorkspaces] = useState<Workspace[]>([]);
 
   useEffect(() => {
     if (showMenu) {
       getAllNotes().then(setNotes).catch(console.error);
+    } else {
+      setSearchQuery('');
+      setShowTrash(false);
+      setShowBackups(false);
+      setShowWorkspaces(false);
     }
   }, [showMenu]);
 
+  useEffect(() => {
+    if (showTrash) {
+      listTrash().then(setTrash).catch(console.error);
+    }
+  }, [showTrash]);
+
+  useEffect(() => {
+    if (showWorkspaces) {
+      listWorkspaces().then(setWorkspaces).catch(console.error);
+    }
+  }, [showWorkspaces]);
+
+  useEffect(() => {
+    if (showBackups) {
+      listBackups().then(setBackups).catch(console.error);
+    }
+  }, [showBackups]);
+
+  useEffect(() => {
+    if (!searchQuery.trim()) {
+      setSearchResults([]);
+      return;
+    }
+    const timeout = window.setTimeout(() => {
+      searchNotes(searchQuery, 30).then(setSearchResults).catch(console.error);
+    }, 150);
+    return () => clearTimeout(timeout);
+  }, [searchQuery]);
+
   useEffect(() => {
     setEditTitle(title);
   }, [title]);
@@ -60,6 +120,24 @@ export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onO
     await createNote();
   };
 
+  const handleNewFromTemplate = async () => {
+    setShowMenu(false);
+    const templates = await listTemplates();
+    if (templates.length === 0) {
+      alert('No templates yet. Choose "Use as template" in a note\'s settings to add one.');
+      return;
+    }
+    const list = templates.map((t, i) => `${i + 1}. ${t.title || 'Untitled'}`).join('\n');
+    const choice = prompt(`Template:\n${list}`, '1');
+    if (!choice) return;
+    const template = templates[parseInt(choice, 10) - 1];
+    if (!template) {
+      alert('No such template');
+      return;
+    }
+    await createNoteFromTemplate(template.id).catch((e) => alert(String(e)));
+  };
+
   const handleMinimize = async () => await minimizeWindow();
 
   const handleClose = async () => {
@@ -68,11 +146,67 @@ export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onO
   };
 
   const handleDelete = async () => {
-    if (confirm('Delete this note permanently?')) {
+    if (confirm('Move this note to the trash?')) {
       await deleteNote(noteId);
     }
   };
 
+  const handleRestoreNote = async (id: string) => {
+    await restoreNote(id);
+    setTrash(await listTrash());
+    setNotes(await getAllNotes());
+  };
+
+  const handleEmptyTrash = async () => {
+    if (confirm('Permanently delete all notes in the trash?')) {
+      await emptyTrash();
+      setTrash([]);
+    }
+  };
+
+  const handleBackupNow = async () => {
+    try {
+      await createBackup();
+      setBackups(await listBackups());
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleRestoreBackup = async (backup: Backup) => {
+    const when = new Date(backup.created_at).toLocaleString();
+    if (!confirm(`Replace all notes with the ${backup.kind} backup from ${when}?`)) return;
+    try {
+      await restoreBackup(backup.name);
+      setShowMenu(false);
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleSaveWorkspace = async () => {
+    const name = prompt('Save the open notes as workspace:');
+    if (!name?.trim()) return;
+    if (workspaces.some((w) => w.name === name.trim()) && !confirm(`Replace workspace "${name.trim()}"?`)) return;
+    try {
+      await saveWorkspace(name);
+      setWorkspaces(await listWorkspaces());
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleApplyWorkspace = async (workspace: Workspace) => {
+    setShowMenu(false);
+    await applyWorkspace(workspace.name).catch((e) => alert(String(e)));
+  };
+
+  const handleDeleteWorkspace = async (workspace: Workspace) => {
+    if (!confirm(`Delete workspace "${workspace.name}"?`)) return;
+    await deleteWorkspace(workspace.name).catch((e) => alert(String(e)));
+    setWorkspaces(await listWorkspaces());
+  };
+
   const handleOpenNote = async (id: string) => {
     setShowMenu(false);
     if (id !== noteId) await openNote(id);
@@ -116,12 +250,195 @@ export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onO
 
   const handleMinimizeAll = async () => { setShowSettings(false); await minimizeAllNotes(); };
   const handleShowAll = async () => { setShowSettings(false); await showAllNotes(); };
+  const handleArrange = async (strategy: ArrangeStrategy) => { setShowSettings(false); await arrangeNotes(strategy).catch((e) => alert(String(e))); };
+  const handleGatherAll = async () => { setShowSettings(false); await gatherAllNotes().catch((e) => alert(String(e))); };
 
   const handleAddTodo = () => {
     setShowSettings(false);
     onAddTodo();
   };
 
+  const handleLock = () => {
+    setShowSettings(false);
+    onLock();
+  };
+
+  const handleSetReminder = () => {
+    setShowSettings(false);
+    onSetReminder();
+  };
+
+  const handleToggleTemplate = async () => {
+    setShowSettings(false);
+    await setNoteTemplate(noteId, !isTemplate).catch((e) => alert(String(e)));
+  };
+
+  const handleExportMarkdown = async () => {
+    setShowSettings(false);
+    const dir = prompt('Folder to export notes into:');
+    if (!dir) return;
+    try {
+      const report = await exportMarkdown(dir);
+      const count = report.exported.length;
+      const lines = [
+        `Exported ${count} note${count === 1 ? '' : 's'}`,
+        ...report.skipped.map((s) => `${s.title.trim() || '(untitled)'}: ${s.reason}`),
+      ];
+      alert(lines.join('\n'));
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleImportMarkdown = async () => {
+    setShowSettings(false);
+    const dir = prompt('Folder to import .md / .txt files from:');
+    if (!dir) return;
+    try {
+      const report = await importMarkdown(dir);
+      const lines = [
+        `Created ${report.created.length}, updated ${report.updated.length}, skipped ${report.skipped.length}`,
+        ...report.skipped.map((s) => `${s.file}: ${s.reason}`),
+      ];
+      alert(lines.join('\n'));
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleExportArchive = async () => {
+    setShowSettings(false);
+    const path = prompt('File to write the archive to:', 'notary-archive.json');
+    if (!path) return;
+    try {
+      const count = await exportArchive(path);
+      alert(`Archived ${count} note${count === 1 ? '' : 's'}`);
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleImportArchive = async () => {
+    setShowSettings(false);
+    const path = prompt('Archive file to import:');
+    if (!path) return;
+    const strategy = prompt(
+      'For notes that already exist: keep_newer, skip_existing, or replace_all (deletes every current note)',
+      'keep_newer',
+    ) as ArchiveStrategy | null;
+    if (!strategy) return;
+    if (!['keep_newer', 'skip_existing', 'replace_all'].includes(strategy)) {
+      alert(`Unknown option: ${strategy}`);
+      return;
+    }
+    if (strategy === 'replace_all' && !confirm('Replace all notes and settings with the archive?')) return;
+    try {
+      const report = await importArchive(path, strategy);
+      const omitted = report.attachments_omitted
+        ? `\n${report.attachments_omitted} attachment(s) are not part of archives and were not imported.`
+        : '';
+      alert(`Created ${report.created}, updated ${report.updated}, skipped ${report.skipped}${omitted}`);
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleSyncFolder = async () => {
+    setShowSettings(false);
+    try {
+      const settings = await getSettings();
+      const dir = prompt('Folder shared with your other devices (leave empty to turn sync off):', settings.sync_dir);
+      if (dir === null) return;
+      await setSyncDir(dir.trim() || null);
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleSyncNow = async () => {
+    setShowSettings(false);
+    try {
+      const report = await syncNow();
+      alert(
+        `Received ${report.imported}, sent ${report.exported}, deleted ${report.deleted}` +
+          (report.conflicts ? `\n${report.conflicts} conflicting edit${report.conflicts === 1 ? '' : 's'} kept as conflict copies` : ''),
+      );
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleScriptingApi = async () => {
+    setShowSettings(false);
+    try {
+      const settings = await getSettings();
+      if (settings.api_enabled) {
+        const token = await getApiToken();
+        const url = `http://127.0.0.1:${settings.api_port}/notes`;
+        if (!confirm(`API is on at ${url}\nToken: ${token}\n\nTurn it off?`)) {
+          if (confirm('Replace the token? Scripts using the current one will stop working.')) {
+            alert(`New token: ${await regenerateApiToken()}`);
+          }
+          return;
+        }
+        await setApiSettings(false, settings.api_port);
+        return;
+      }
+
+      const input = prompt('Serve the scripting API on localhost port:', String(settings.api_port));
+      if (input === null) return;
+      const port = Number(input);
+      if (!Number.isInteger(port) || port < 1 || port > 65535) {
+        alert(`Invalid port: ${input}`);
+        return;
+      }
+      await setApiSettings(true, port);
+      alert(`API is on at http://127.0.0.1:${port}/notes\nSend the header "Authorization: Bearer ${await getApiToken()}"`);
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
+  const handleDatabaseEncryption = async () => {
+    setShowSettings(false);
+    try {
+      const status = await getEncryptionStatus();
+      if (!status.supported) {
+        alert('This build does not support database encryption');
+        return;
+      }
+
+      if (!status.enabled) {
+        const password = prompt('Master password to encrypt all notes:');
+        if (!password) return;
+        if (prompt('Repeat master password:') !== password) {
+          alert('Passwords do not match');
+          return;
+        }
+        await enableEncryption(password);
+        alert('Database encrypted. You will be asked for this password at startup.');
+        return;
+      }
+
+      const current = prompt('Current master password (leave the next prompt empty to turn encryption off):');
+      if (!current) return;
+      const next = prompt('New master password:');
+      if (!next) {
+        if (confirm('Turn off database encryption?')) {
+          await disableEncryption(current);
+        }
+        return;
+      }
+      if (prompt('Repeat new master password:') !== next) {
+        alert('Passwords do not match');
+        return;
+      }
+      await changeEncryptionPassword(current, next);
+    } catch (e) {
+      alert(String(e));
+    }
+  };
+
   return (
     <div
       className="titlebar flex items-center justify-between px-2 py-1 cursor-move select-none rounded-t-lg"
@@ -149,7 +466,43 @@ export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onO
               >
                 <span className="text-green-600">+</span> New Note
               </button>
+              <button
+                onClick={handleNewFromTemplate}
+                className="w-full px-3 py-2 text-left text-sm hover:bg-gray-100 dark:hover:bg-gray-700 flex items-center gap-2"
+              >
+                <span className="text-green-600">⧉</span> New from Template…
+              </button>
               <div className="border-t border-gray-200 dark:border-gray-700 my-1" />
+              <div className="px-2 pb-1">
+                <input
+                  type="text"
+                  value={searchQuery}
+                  onChange={(e) => setSearchQuery(e.target.value)}
+                  placeholder="Search notes…"
+                  className="w-full px-2 py-1 text-xs rounded bg-gray-100 dark:bg-gray-700 focus:outline-none"
+                />
+              </div>
+              {searchQuery.trim() ? (
+                <div className="max-h-48 overflow-y-auto">
+                  {searchResults.map((result) => (
+                    <button
+                      key={result.id}
+                      onClick={() => handleOpenNote(result.id)}
+                      className="w-full px-3 py-1.5 text-left hover:bg-gray-100 dark:hover:bg-gray-700 block"
+                    >
+                      <div className="text-sm truncate">
+                        {result.title ? <Highlighted text={result.title_highlight} /> : 'Untitled'}
+                      </div>
+                      <div className="text-xs text-gray-500 truncate">
+                        <Highlighted text={result.snippet} />
+                      </div>
+                    </button>
+                  ))}
+                  {searchResults.length === 0 && (
+                    <div className="px-3 py-2 text-sm text-gray-500">No matches</div>
+                  )}
+                </div>
+              ) : (
               <div className="max-h-48 overflow-y-auto">
                 {notes.map((note) => (
                   <div
@@ -180,6 +533,111 @@ export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onO
                   <div className="px-3 py-2 text-sm text-gray-500">No notes</div>
                 )}
               </div>
+              )}
+              <div className="border-t border-gray-200 dark:border-gray-700 my-1" />
+              <button
+                onClick={() => setShowTrash(!showTrash)}
+                className="w-full px-3 py-1.5 text-left text-xs text-gray-500 hover:bg-gray-100 dark:hover:bg-gray-700"
+              >
+                {showTrash ? '▾' : '▸'} Trash
+              </button>
+              {showTrash && (
+                <div className="max-h-32 overflow-y-auto">
+                  {trash.map((note) => (
+                    <div key={note.id} className="flex items-center justify-between px-3 py-1">
+                      <span className="truncate text-sm text-gray-500">{note.title || 'Untitled'}</span>
+                      <button
+                        onClick={() => handleRestoreNote(note.id)}
+                        className="ml-2 px-2 py-0.5 text-xs bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+                      >
+                        Restore
+                      </button>
+                    </div>
+                  ))}
+                  {trash.length === 0 ? (
+                    <div className="px-3 py-1 text-xs text-gray-500">Trash is empty</div>
+                  ) : (
+                    <button
+                      onClick={handleEmptyTrash}
+                      className="w-full px-3 py-1.5 text-left text-xs text-red-500 hover:bg-gray-100 dark:hover:bg-gray-700"
+                    >
+                      Empty trash
+                    </button>
+                  )}
+                </div>
+              )}
+              <button
+                onClick={() => setShowWorkspaces(!showWorkspaces)}
+                className="w-full px-3 py-1.5 text-left text-xs text-gray-500 hover:bg-gray-100 dark:hover:bg-gray-700"
+              >
+                {showWorkspaces ? '▾' : '▸'} Workspaces
+              </button>
+              {showWorkspaces && (
+                <div className="max-h-32 overflow-y-auto">
+                  {workspaces.map((workspace) => (
+                    <div key={workspace.name} className="flex items-center justify-between px-3 py-1">
+                      <span className="truncate text-sm text-gray-500">
+                        {workspace.name} · {workspace.note_count}
+                      </span>
+                      <div className="flex gap-1">
+                        <button
+                          onClick={() => handleApplyWorkspace(workspace)}
+                          className="ml-2 px-2 py-0.5 text-xs bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+                        >
+                          Apply
+                        </button>
+                        <button
+                          onClick={() => handleDeleteWorkspace(workspace)}
+                          className="px-2 py-0.5 text-xs bg-gray-200 dark:bg-gray-600 hover:bg-red-500 hover:text-white rounded"
+                        >
+                          Delete
+                        </button>
+                      </div>
+                    </div>
+                  ))}
+                  {workspaces.length === 0 && (
+                    <div className="px-3 py-1 text-xs text-gray-500">No workspaces yet</div>
+                  )}
+                  <button
+                    onClick={handleSaveWorkspace}
+                    className="w-full px-3 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700"
+                  >
+                    Save open notes as workspace…
+                  </button>
+                </div>
+              )}
+              <button
+                onClick={() => setShowBackups(!showBackups)}
+                className="w-full px-3 py-1.5 text-left text-xs text-gray-500 hover:bg-gray-100 dark:hover:bg-gray-700"
+              >
+                {showBackups ? '▾' : '▸'} Backups
+              </button>
+              {showBackups && (
+                <div className="max-h-32 overflow-y-auto">
+                  {backups.map((backup) => (
+                    <div key={backup.name} className="flex items-center justify-between px-3 py-1">
+                      <span className="truncate text-sm text-gray-500">
+                        {new Date(backup.created_at).toLocaleString()} · {backup.kind}
+                      </span>
+                      <button
+                        onClick={() => handleRestoreBackup(backup)}
+                        className="ml-2 px-2 py-0.5 text-xs bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+                      >
+                        Restore
+                      </button>
+                    </div>
+                  ))}
+                  {backups.length === 0 && (
+                    <div className="px-3 py-1 text-xs text-gray-500">No backups yet</div>
+                  )}
+                  <button
+                    onClick={handleBackupNow}
+                    className="w-full px-3 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700"
+                  >
+                    Back up now
+                  </button>
+                </div>
+              )}
             </div>
           )}
         </div>
@@ -224,7 +682,7 @@ export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onO
         <button
           onClick={handleDelete}
           className="titlebar-button hover:!bg-red-500 hover:!text-white"
-          title="Delete note permanently"
+          title="Move note to trash"
         >
           <svg xmlns="http://www.w3.org/2000/svg" width="12" height="12" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" strokeLinecap="round" strokeLinejoin="round">
             <path d="M3 6h18" /><path d="M19 6v14c0 1-1 2-2 2H7c-1 0-2-1-2-2V6" /><path d="M8 6V4c0-1 1-2 2-2h4c1 0 2 1 2 2v2" />
@@ -276,6 +734,85 @@ export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onO
                 <span>☐</span> Add Todo Item
               </button>
 
+              <button
+                onClick={handleSetReminder}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>⏰</span> Reminder…
+              </button>
+
+              <button
+                onClick={handleToggleTemplate}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>⧉</span> {isTemplate ? 'Stop using as template' : 'Use as template'}
+              </button>
+
+              {!isLocked && (
+                <button
+                  onClick={handleLock}
+                  className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+                >
+                  <span>🔒</span> Lock with passphrase
+                </button>
+              )}
+
+              <button
+                onClick={handleDatabaseEncryption}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>🛡</span> Database encryption…
+              </button>
+
+              <button
+                onClick={handleExportMarkdown}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>⇪</span> Export to Markdown…
+              </button>
+
+              <button
+                onClick={handleImportMarkdown}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>⇩</span> Import from Markdown…
+              </button>
+
+              <button
+                onClick={handleExportArchive}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>⇪</span> Export archive…
+              </button>
+
+              <button
+                onClick={handleImportArchive}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>⇩</span> Import archive…
+              </button>
+
+              <button
+                onClick={handleSyncFolder}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>⇄</span> Sync folder…
+              </button>
+
+              <button
+                onClick={handleSyncNow}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>↻</span> Sync now
+              </button>
+
+              <button
+                onClick={handleScriptingApi}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2 mb-1"
+              >
+                <span>⌘</span> Scripting API…
+              </button>
+
               <div className="border-t border-gray-200 dark:border-gray-700 my-2" />
 
               {/* All Notes section */}
@@ -311,6 +848,25 @@ export function TitleBar({ noteId, title, alwaysOnTop, opacity, onTogglePin, onO
                 <svg xmlns="http://www.w3.org/2000/svg" width="11" height="11" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2"><polyline points="17 11 12 6 7 11" /><polyline points="17 18 12 13 7 18" /></svg>
                 Show all
               </button>
+              <button
+                onClick={handleGatherAll}
+                className="w-full px-2 py-1.5 text-left text-xs hover:bg-gray-100 dark:hover:bg-gray-700 rounded flex items-center gap-2"
+              >
+                <svg xmlns="http://www.w3.org/2000/svg" width="11" height="11" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2"><rect x="3" y="3" width="18" height="18" rx="2" /><polyline points="9 12 12 15 15 12" /></svg>
+                Gather all onto main screen
+              </button>
+              <div className="flex items-center gap-1 px-2 py-1.5 text-xs">
+                <span className="text-gray-500 mr-1">Arrange</span>
+                {([['tile', 'Tile'], ['cascade', 'Cascade'], ['stack_left', '◧ Stack'], ['stack_right', 'Stack ◨']] as [ArrangeStrategy, string][]).map(([strategy, label]) => (
+                  <button
+                    key={strategy}
+                    onClick={() => handleArrange(strategy)}
+                    className="px-1.5 py-0.5 bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+                  >
+                    {label}
+                  </button>
+                ))}
+              </div>
             </div>
           )}
         </div>
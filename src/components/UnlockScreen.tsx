@@ -0,0 +1,44 @@
+import { useState } from 'react';
+import { unlockDatabase } from '../lib/tauri';
+
+// Shown in the "unlock" window when the database is encrypted at rest.
+export function UnlockScreen() {
+  const [password, setPassword] = useState('');
+  const [error, setError] = useState<string | null>(null);
+  const [busy, setBusy] = useState(false);
+
+  const handleUnlock = async () => {
+    setBusy(true);
+    try {
+      await unlockDatabase(password);
+    } catch (e) {
+      setError(String(e));
+      setPassword('');
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  return (
+    <div className="h-screen flex flex-col items-center justify-center gap-2 p-4 bg-white dark:bg-gray-800">
+      <span className="text-sm opacity-60">🔒 Enter the master password</span>
+      <input
+        type="password"
+        autoFocus
+        value={password}
+        onChange={(e) => setPassword(e.target.value)}
+        onKeyDown={(e) => { if (e.key === 'Enter') handleUnlock(); }}
+        placeholder="Password"
+        className="w-full max-w-56 px-2 py-1 text-xs rounded bg-gray-100 dark:bg-gray-700 focus:outline-none"
+      />
+      <button
+        onClick={handleUnlock}
+        disabled={busy}
+        className="px-3 py-1 text-xs bg-gray-200 dark:bg-gray-600 hover:bg-blue-500 hover:text-white rounded"
+      >
+        Unlock
+      </button>
+      {error && <span className="text-xs text-red-500">{error}</span>}
+    </div>
+  );
+}
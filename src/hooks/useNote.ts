@@ -2,7 +2,7 @@ import { useState, useEffect, useCallback, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { listen } from '@tauri-apps/api/event';
-import { getNote, updateNote, Note } from '../lib/tauri';
+import { getNote, updateNote, renameNote, getBacklinks, Note } from '../lib/tauri';
 
 export function useNote(noteId: string) {
   const [note, setNote] = useState<Note | null>(null);
@@ -84,13 +84,20 @@ export function useNote(noteId: string) {
     []
   );
 
-  // Update title
+  // Update title, rewriting [[links]] to the old title in other notes if the user agrees
+  const oldTitle = note?.title.trim() ?? '';
   const updateTitle = useCallback(
     async (title: string) => {
       setNote((prev) => (prev ? { ...prev, title } : null));
-      await updateNote(noteId, { title });
+      let rewriteLinks = false;
+      if (oldTitle && oldTitle !== title.trim()) {
+        const linking = (await getBacklinks(noteId)).filter((n) => n.id !== noteId);
+        rewriteLinks = linking.length > 0
+          && confirm(`Update [[${oldTitle}]] links in ${linking.length} other note(s) to the new title?`);
+      }
+      await renameNote(noteId, title, rewriteLinks);
     },
-    [noteId]
+    [noteId, oldTitle]
   );
 
   // Listen for window move/resize events
@@ -149,6 +156,21 @@ export function useNote(noteId: string) {
     };
   }, []);
 
+  // Listen for note-updated events (revision restores and other backend edits)
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    listen<Note>('note-updated', (event) => {
+      if (event.payload.id !== noteId) return;
+      pendingContentRef.current = null;
+      setNote(event.payload);
+    }).then((fn) => {
+      unlisten = fn;
+    });
+    return () => {
+      if (unlisten) unlisten();
+    };
+  }, [noteId]);
+
   // Flush pending saves and return live content
   const flushAndGetContent = useCallback(async (): Promise<string> => {
     await saveNow();
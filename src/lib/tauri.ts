@@ -16,11 +16,147 @@ export interface Note {
   always_on_top: boolean;
   created_at: string;
   updated_at: string;
+  deleted_at: string | null;
+  notebook_id: string | null;
+  is_locked: boolean;
+  is_template: boolean;
+}
+
+export interface Notebook {
+  id: string;
+  name: string;
+  parent_id: string | null;
+  note_count: number;
+  created_at: string;
+  updated_at: string;
+}
+
+export interface NoteRevision {
+  id: number;
+  note_id: string;
+  title: string;
+  content: string;
+  source: string;
+  created_at: string;
+}
+
+export interface SearchResult {
+  id: string;
+  title: string;
+  title_highlight: string;
+  snippet: string;
+  rank: number;
+}
+
+export interface OutgoingLink {
+  target: string;
+  note_id: string | null;
+  note_title: string | null;
+}
+
+export interface Attachment {
+  id: string;
+  note_id: string;
+  file_name: string;
+  mime_type: string;
+  size: number;
+  hash: string;
+  created_at: string;
 }
 
 export interface Settings {
   theme: string;
   default_opacity: number;
+  trash_retention_days: number;
+  extract_hashtags: boolean;
+  backup_keep_daily: number;
+  backup_keep_weekly: number;
+  sync_dir: string;
+  api_enabled: boolean;
+  api_port: number;
+}
+
+export interface Backup {
+  name: string;
+  kind: 'daily' | 'weekly' | 'restore';
+  created_at: string;
+  size: number;
+}
+
+export interface MarkdownExport {
+  exported: string[];
+  skipped: { id: string; title: string; reason: string }[];
+}
+
+export interface MarkdownImport {
+  created: string[];
+  updated: string[];
+  skipped: { file: string; reason: string }[];
+}
+
+export interface SyncReport {
+  imported: number;
+  exported: number;
+  conflicts: number;
+  deleted: number;
+}
+
+export type Recurrence = 'daily' | 'weekdays' | 'weekly';
+
+// "weekly:mon" ... "weekly:sun" repeats on that day
+export type TodoRecurrence = 'daily' | 'weekdays' | `weekly:${'mon' | 'tue' | 'wed' | 'thu' | 'fri' | 'sat' | 'sun'}`;
+
+export interface RecurringTodo {
+  id: string;
+  note_id: string;
+  text: string;
+  recurrence: TodoRecurrence;
+  occurrence: string;
+  created_at: string;
+}
+
+export interface TodoCompletion {
+  occurrence: string;
+  completed: boolean;
+  recorded_at: string;
+}
+
+export interface Reminder {
+  id: string;
+  note_id: string;
+  fire_at: string;
+  recurrence: Recurrence | null;
+  fired_at: string | null;
+  snoozed_until: string | null;
+  created_at: string;
+}
+
+export interface Workspace {
+  name: string;
+  note_count: number;
+  created_at: string;
+  updated_at: string;
+}
+
+export type ArchiveStrategy = 'replace_all' | 'skip_existing' | 'keep_newer';
+
+export interface ArchiveImport {
+  created: number;
+  updated: number;
+  skipped: number;
+  /** Attachments of the archived notes; archives don't carry the files */
+  attachments_omitted: number;
+}
+
+export interface EncryptionStatus {
+  supported: boolean;
+  enabled: boolean;
+}
+
+export interface Tag {
+  id: number;
+  name: string;
+  note_count: number;
 }
 
 export async function createNote(posX?: number, posY?: number): Promise<Note> {
@@ -71,6 +207,18 @@ export async function deleteNote(id: string): Promise<void> {
   return invoke('delete_note', { id });
 }
 
+export async function listTrash(): Promise<Note[]> {
+  return invoke('list_trash');
+}
+
+export async function restoreNote(id: string): Promise<Note> {
+  return invoke('restore_note', { id });
+}
+
+export async function emptyTrash(): Promise<number> {
+  return invoke('empty_trash');
+}
+
 export async function openNote(id: string): Promise<Note> {
   return invoke('open_note', { id });
 }
@@ -99,6 +247,14 @@ export async function setDefaultOpacity(opacity: number): Promise<void> {
   return invoke('set_default_opacity', { opacity });
 }
 
+export async function setTrashRetentionDays(days: number): Promise<void> {
+  return invoke('set_trash_retention_days', { days });
+}
+
+export async function setExtractHashtags(enabled: boolean): Promise<void> {
+  return invoke('set_extract_hashtags', { enabled });
+}
+
 export function startDragging(): Promise<void> {
   return getCurrentWindow().startDragging();
 }
@@ -119,6 +275,299 @@ export async function showAllNotes(): Promise<void> {
   return invoke('show_all_notes');
 }
 
+export async function gatherAllNotes(): Promise<void> {
+  return invoke('gather_all_notes');
+}
+
+export type ArrangeStrategy = 'tile' | 'cascade' | 'stack_left' | 'stack_right';
+
+// `monitor` indexes the connected monitors; the primary one when omitted
+export async function arrangeNotes(strategy: ArrangeStrategy, monitor?: number): Promise<void> {
+  return invoke('arrange_notes', { strategy, monitor });
+}
+
 export async function setAllOpacity(opacity: number): Promise<void> {
   return invoke('set_all_opacity', { opacity });
 }
+
+export async function listNoteRevisions(id: string): Promise<NoteRevision[]> {
+  return invoke('list_note_revisions', { id });
+}
+
+export async function getNoteRevision(revisionId: number): Promise<NoteRevision | null> {
+  return invoke('get_note_revision', { revisionId });
+}
+
+export async function restoreNoteRevision(revisionId: number): Promise<Note> {
+  return invoke('restore_note_revision', { revisionId });
+}
+
+export async function searchNotes(query: string, limit?: number): Promise<SearchResult[]> {
+  return invoke('search_notes', { query, limit });
+}
+
+export async function listTags(): Promise<Tag[]> {
+  return invoke('list_tags');
+}
+
+export async function getNoteTags(id: string): Promise<string[]> {
+  return invoke('get_note_tags', { id });
+}
+
+export async function addNoteTag(id: string, tag: string): Promise<void> {
+  return invoke('add_note_tag', { id, tag });
+}
+
+export async function removeNoteTag(id: string, tag: string): Promise<void> {
+  return invoke('remove_note_tag', { id, tag });
+}
+
+export async function renameTag(oldName: string, newName: string): Promise<void> {
+  return invoke('rename_tag', { oldName, newName });
+}
+
+export async function mergeTags(source: string, target: string): Promise<void> {
+  return invoke('merge_tags', { source, target });
+}
+
+export async function deleteTag(tag: string): Promise<void> {
+  return invoke('delete_tag', { tag });
+}
+
+export async function getNotesByTags(tags: string[], matchAll = true): Promise<Note[]> {
+  return invoke('get_notes_by_tags', { tags, matchAll });
+}
+
+export async function listNotebooks(): Promise<Notebook[]> {
+  return invoke('list_notebooks');
+}
+
+export async function createNotebook(name: string, parentId?: string): Promise<Notebook> {
+  return invoke('create_notebook', { name, parentId });
+}
+
+export async function renameNotebook(id: string, name: string): Promise<void> {
+  return invoke('rename_notebook', { id, name });
+}
+
+export async function moveNotebook(id: string, parentId?: string): Promise<void> {
+  return invoke('move_notebook', { id, parentId });
+}
+
+export async function deleteNotebook(id: string): Promise<void> {
+  return invoke('delete_notebook', { id });
+}
+
+export async function moveNoteToNotebook(id: string, notebookId?: string): Promise<void> {
+  return invoke('move_note_to_notebook', { id, notebookId });
+}
+
+export async function getNotebookNotes(notebookId: string, includeNested = true): Promise<Note[]> {
+  return invoke('get_notebook_notes', { notebookId, includeNested });
+}
+
+export async function showNotebook(notebookId: string): Promise<void> {
+  return invoke('show_notebook', { notebookId });
+}
+
+export async function hideNotebook(notebookId: string): Promise<void> {
+  return invoke('hide_notebook', { notebookId });
+}
+
+export async function getBacklinks(id: string): Promise<Note[]> {
+  return invoke('get_backlinks', { id });
+}
+
+export async function getOutgoingLinks(id: string): Promise<OutgoingLink[]> {
+  return invoke('get_outgoing_links', { id });
+}
+
+export async function renameNote(id: string, title: string, rewriteLinks = false): Promise<void> {
+  return invoke('rename_note', { id, title, rewriteLinks });
+}
+
+export async function addAttachment(id: string, fileName: string, data: Uint8Array, mimeType?: string): Promise<Attachment> {
+  return invoke('add_attachment', { id, fileName, mimeType, data: Array.from(data) });
+}
+
+export async function addAttachmentFromPath(id: string, path: string): Promise<Attachment> {
+  return invoke('add_attachment_from_path', { id, path });
+}
+
+export async function listAttachments(id: string): Promise<Attachment[]> {
+  return invoke('list_attachments', { id });
+}
+
+export async function readAttachment(attachmentId: string): Promise<ArrayBuffer> {
+  return invoke('read_attachment', { attachmentId });
+}
+
+export async function removeAttachment(attachmentId: string): Promise<void> {
+  return invoke('remove_attachment', { attachmentId });
+}
+
+export async function lockNote(id: string, passphrase: string): Promise<Note> {
+  return invoke('lock_note', { id, passphrase });
+}
+
+// Decrypted content for reading only; the note stays locked
+export async function viewLockedNote(id: string, passphrase: string): Promise<string> {
+  return invoke('view_locked_note', { id, passphrase });
+}
+
+export async function unlockNote(id: string, passphrase: string): Promise<Note> {
+  return invoke('unlock_note', { id, passphrase });
+}
+
+export async function unlockDatabase(password: string): Promise<void> {
+  return invoke('unlock_database', { password });
+}
+
+export async function getEncryptionStatus(): Promise<EncryptionStatus> {
+  return invoke('get_encryption_status');
+}
+
+export async function enableEncryption(password: string): Promise<void> {
+  return invoke('enable_encryption', { password });
+}
+
+export async function changeEncryptionPassword(currentPassword: string, newPassword: string): Promise<void> {
+  return invoke('change_encryption_password', { currentPassword, newPassword });
+}
+
+export async function disableEncryption(password: string): Promise<void> {
+  return invoke('disable_encryption', { password });
+}
+
+export async function setBackupRetention(daily: number, weekly: number): Promise<void> {
+  return invoke('set_backup_retention', { daily, weekly });
+}
+
+export async function listBackups(): Promise<Backup[]> {
+  return invoke('list_backups');
+}
+
+export async function createBackup(): Promise<void> {
+  return invoke('create_backup');
+}
+
+export async function restoreBackup(name: string): Promise<void> {
+  return invoke('restore_backup', { name });
+}
+
+export async function exportMarkdown(dir: string): Promise<MarkdownExport> {
+  return invoke('export_markdown', { dir });
+}
+
+export async function importMarkdown(dir: string): Promise<MarkdownImport> {
+  return invoke('import_markdown', { dir });
+}
+
+export async function exportArchive(path: string): Promise<number> {
+  return invoke('export_archive', { path });
+}
+
+export async function importArchive(path: string, strategy: ArchiveStrategy): Promise<ArchiveImport> {
+  return invoke('import_archive', { path, strategy });
+}
+
+export async function setSyncDir(dir: string | null): Promise<void> {
+  return invoke('set_sync_dir', { dir });
+}
+
+export async function syncNow(): Promise<SyncReport> {
+  return invoke('sync_now');
+}
+
+export async function setApiSettings(enabled: boolean, port: number): Promise<void> {
+  return invoke('set_api_settings', { enabled, port });
+}
+
+export async function getApiToken(): Promise<string> {
+  return invoke('get_api_token');
+}
+
+export async function regenerateApiToken(): Promise<string> {
+  return invoke('regenerate_api_token');
+}
+
+export async function toggleTodo(id: string, index: number): Promise<Note> {
+  return invoke('toggle_todo', { id, index });
+}
+
+export async function addTodo(id: string, text: string, index?: number): Promise<Note> {
+  return invoke('add_todo', { id, text, index });
+}
+
+export async function removeTodo(id: string, index: number): Promise<Note> {
+  return invoke('remove_todo', { id, index });
+}
+
+export async function moveTodo(id: string, from: number, to: number): Promise<Note> {
+  return invoke('move_todo', { id, from, to });
+}
+
+export async function clearCompleted(id: string): Promise<Note> {
+  return invoke('clear_completed', { id });
+}
+
+export async function saveWorkspace(name: string): Promise<Workspace> {
+  return invoke('save_workspace', { name });
+}
+
+export async function applyWorkspace(name: string): Promise<void> {
+  return invoke('apply_workspace', { name });
+}
+
+export async function listWorkspaces(): Promise<Workspace[]> {
+  return invoke('list_workspaces');
+}
+
+export async function deleteWorkspace(name: string): Promise<void> {
+  return invoke('delete_workspace', { name });
+}
+
+export async function setNoteTemplate(id: string, isTemplate: boolean): Promise<void> {
+  return invoke('set_note_template', { id, isTemplate });
+}
+
+export async function listTemplates(): Promise<Note[]> {
+  return invoke('list_templates');
+}
+
+export async function createNoteFromTemplate(templateId: string, posX?: number, posY?: number): Promise<Note> {
+  return invoke('create_note_from_template', { templateId, posX, posY });
+}
+
+export async function setTodoRecurrence(id: string, index: number, recurrence: TodoRecurrence | null): Promise<RecurringTodo | null> {
+  return invoke('set_todo_recurrence', { id, index, recurrence });
+}
+
+export async function listRecurringTodos(id: string): Promise<RecurringTodo[]> {
+  return invoke('list_recurring_todos', { id });
+}
+
+export async function getTodoHistory(recurringTodoId: string): Promise<TodoCompletion[]> {
+  return invoke('get_todo_history', { recurringTodoId });
+}
+
+export async function setReminder(noteId: string, fireAt: string, recurrence: Recurrence | null): Promise<Reminder> {
+  return invoke('set_reminder', { noteId, fireAt, recurrence });
+}
+
+export async function listReminders(noteId?: string): Promise<Reminder[]> {
+  return invoke('list_reminders', { noteId });
+}
+
+export async function snoozeReminder(id: string, minutes: number): Promise<Reminder> {
+  return invoke('snooze_reminder', { id, minutes });
+}
+
+export async function dismissReminder(id: string): Promise<void> {
+  return invoke('dismiss_reminder', { id });
+}
+
+// Stops a fired reminder ringing; one-off reminders are removed
+export async function acknowledgeReminder(id: string): Promise<void> {
+  return invoke('acknowledge_reminder', { id });
+}